@@ -0,0 +1,203 @@
+// `#[derive(Packet)]` for the packets in mcidle-rs, writing the
+// `ProtocolToID` and `PacketSerializer` impls from the struct's fields.
+// The generated code names `crate::serialize`, so it only works inside
+// mcidle-rs.
+//
+// On the struct:
+//   #[id(KeepAliveCB)]             the `PacketID` to look the id up as in the
+//                                  registry, so it follows the version
+//   #[id(0x1F, state = "play")]    a fixed id for packets the registry doesn't
+//                                  know, the same in every version
+//
+// On fields, which are otherwise written with `serialize::field::Field`:
+//   #[varint]      an i32 sent as a VarInt
+//   #[prefixed]    a Vec sent as a VarInt count then its elements
+//   #[since(107)]  only sent by this protocol version and later, the field is
+//                  left at its default when reading an older version
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitInt, LitStr, Token};
+
+#[proc_macro_derive(Packet, attributes(id, varint, prefixed, since))]
+pub fn derive_packet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Id {
+    Registry(Ident),
+    Fixed(LitInt, Ident), // Id and the `State` variant it's in
+}
+
+impl Parse for Id {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) {
+            return Ok(Id::Registry(input.parse()?));
+        }
+
+        let id: LitInt = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let key: Ident = input.parse()?;
+        if key != "state" {
+            return Err(Error::new(key.span(), "expected `state = \"...\"`"));
+        }
+        input.parse::<Token![=]>()?;
+        let state: LitStr = input.parse()?;
+        let variant = match state.value().as_str() {
+            "handshaking" => "Handshaking",
+            "status" => "Status",
+            "login" => "Login",
+            "play" => "Play",
+            _ => {
+                return Err(Error::new(
+                    state.span(),
+                    "state must be handshaking, status, login or play",
+                ))
+            }
+        };
+        Ok(Id::Fixed(id, Ident::new(variant, state.span())))
+    }
+}
+
+#[derive(PartialEq)]
+enum Encoding {
+    Field,
+    VarInt,
+    Prefixed,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let id_attr = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("id"))
+        .ok_or_else(|| Error::new(name.span(), "missing #[id(...)]"))?;
+    let id = id_attr.parse_args::<Id>()?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(Error::new(name.span(), "packet fields must be named"))
+            }
+        },
+        _ => return Err(Error::new(name.span(), "only structs can be packets")),
+    };
+
+    let mut writes = Vec::new();
+    let mut reads = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let mut encoding = Encoding::Field;
+        let mut since = None;
+        for attr in &field.attrs {
+            if attr.path().is_ident("varint") || attr.path().is_ident("prefixed") {
+                if encoding != Encoding::Field {
+                    return Err(Error::new(attr.span(), "a field has one encoding"));
+                }
+                attr.meta.require_path_only()?;
+                encoding = if attr.path().is_ident("varint") {
+                    Encoding::VarInt
+                } else {
+                    Encoding::Prefixed
+                };
+            } else if attr.path().is_ident("since") {
+                since = Some(attr.parse_args::<LitInt>()?.base10_parse::<i32>()?);
+            }
+        }
+
+        let (write, read) = match encoding {
+            Encoding::Field => (
+                quote!(crate::serialize::field::Field::write_to(&self.#ident, buf)),
+                quote!(crate::serialize::field::Field::read_from(buf)?),
+            ),
+            Encoding::VarInt => (
+                quote!(crate::serialize::var::VarIntWriter::write_var_int(buf, self.#ident)),
+                quote!(crate::serialize::var::VarIntReader::read_var_int(buf)?),
+            ),
+            Encoding::Prefixed => (
+                quote!(crate::serialize::field::write_prefixed(&self.#ident, buf)),
+                quote!(crate::serialize::field::read_prefixed(buf)?),
+            ),
+        };
+
+        match since {
+            Some(since) => {
+                writes.push(quote! {
+                    if *ver as i32 >= #since {
+                        #write;
+                    }
+                });
+                reads.push(quote! {
+                    self.#ident = if *ver as i32 >= #since {
+                        #read
+                    } else {
+                        ::std::default::Default::default()
+                    };
+                });
+            }
+            None => {
+                writes.push(quote!(#write;));
+                reads.push(quote!(self.#ident = #read;));
+            }
+        }
+    }
+
+    let resolve_id = match &id {
+        Id::Registry(kind) => quote!(crate::serialize::packet::PacketID::#kind.id(ver)),
+        Id::Fixed(id, _) => quote!(Some(#id)),
+    };
+    // Fixed ids carry their state with them, registry ones have it in the
+    // registry entry
+    let state = match &id {
+        Id::Registry(_) => quote!(),
+        Id::Fixed(_, state) => quote! {
+            impl #name {
+                pub const STATE: crate::serialize::protocol::State =
+                    crate::serialize::protocol::State::#state;
+            }
+        },
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::serialize::protocol::ProtocolToID for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn resolve_id(&self, ver: &crate::serialize::protocol::ProtocolVersion) -> Option<i32> {
+                #resolve_id
+            }
+        }
+
+        impl #impl_generics crate::serialize::packet::PacketSerializer for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn serialize(
+                &self,
+                buf: &mut crate::serialize::buffer::ByteBuf,
+                ver: &crate::serialize::protocol::ProtocolVersion,
+            ) {
+                #(#writes)*
+            }
+
+            #[allow(unused_variables)]
+            fn deserialize(
+                &mut self,
+                buf: &mut crate::serialize::buffer::ByteBuf,
+                ver: &crate::serialize::protocol::ProtocolVersion,
+            ) -> ::std::result::Result<(), crate::serialize::var::DeserializeError> {
+                #(#reads)*
+                Ok(())
+            }
+        }
+
+        #state
+    })
+}
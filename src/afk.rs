@@ -0,0 +1,406 @@
+use crate::error::Result;
+use crate::mc::Connection;
+use crate::player::PlayerState;
+use crate::serialize::packet::serverbound::{
+    Animation, ChatCommand, ChatMessage, EntityAction, PlayerLook, PlayerPosition,
+};
+use crate::serialize::packet::serverbound::{START_SNEAKING, STOP_SNEAKING};
+use crate::serialize::protocol::ProtocolVersion;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Vanilla's tick, jumps move once per tick
+const TICK: Duration = Duration::from_millis(50);
+
+// Something that shows the server we're still here
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    SwingArm,
+    RotateHead,
+    Jump,  // In place, over a second or so
+    Sneak, // Starts sneaking, or stops if we are
+    Chat(String),
+}
+
+// Runs `action` every `min` to `max`, picked at random each time
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub action: Action,
+    pub min: Duration,
+    pub max: Duration, // Below `min` is the same as `min`
+}
+
+impl Task {
+    fn interval(&self, rng: &mut impl Rng) -> Duration {
+        rng.gen_range(self.min..=self.max.max(self.min))
+    }
+}
+
+// A packet an action sends
+#[derive(Clone, Debug, PartialEq)]
+pub enum Emit {
+    Swing(Animation),
+    Look(PlayerLook),
+    Position(PlayerPosition),
+    Action(EntityAction),
+    Chat(ChatMessage),
+    Command(ChatCommand),
+}
+
+impl Emit {
+    pub fn send(&self, conn: &mut Connection) -> Result<usize> {
+        match self {
+            Emit::Swing(packet) => conn.send_packet(packet),
+            Emit::Look(packet) => conn.send_packet(packet),
+            Emit::Position(packet) => conn.send_packet(packet),
+            Emit::Action(packet) => conn.send_packet(packet),
+            Emit::Chat(packet) => conn.send_packet(packet),
+            Emit::Command(packet) => conn.send_packet(packet),
+        }
+    }
+}
+
+// Runs anti-AFK tasks against a clock that starts at 0, so it can be driven
+// by real or simulated time. One per connection, sneaking doesn't survive a
+// reconnect.
+pub struct AntiAfk {
+    ver: ProtocolVersion,
+    tasks: Vec<Task>,
+    next: Vec<Option<Duration>>, // When each task runs again, None before the first poll
+    queued: VecDeque<(Duration, Emit)>, // The rest of a jump
+    sneaking: bool,
+}
+
+impl AntiAfk {
+    pub fn new(ver: ProtocolVersion, tasks: Vec<Task>) -> AntiAfk {
+        let next = vec![None; tasks.len()];
+        AntiAfk {
+            ver,
+            tasks,
+            next,
+            queued: VecDeque::new(),
+            sneaking: false,
+        }
+    }
+
+    pub fn sneaking(&self) -> bool {
+        self.sneaking
+    }
+
+    // Drops what's left of a jump, it would undo a teleport
+    pub fn interrupt(&mut self) {
+        self.queued.clear();
+    }
+
+    // Packets due by `now`. Runs missed while not polled aren't caught up on.
+    pub fn poll(
+        &mut self,
+        now: Duration,
+        player: &PlayerState,
+        entity_id: i32,
+        rng: &mut impl Rng,
+    ) -> Vec<Emit> {
+        let mut emits = Vec::new();
+        while matches!(self.queued.front(), Some((at, _)) if *at <= now) {
+            emits.push(self.queued.pop_front().unwrap().1);
+        }
+
+        for i in 0..self.tasks.len() {
+            let due = match self.next[i] {
+                Some(due) => due,
+                None => {
+                    self.next[i] = Some(now + self.tasks[i].interval(rng));
+                    continue;
+                }
+            };
+            if due > now {
+                continue;
+            }
+            self.next[i] = Some(now + self.tasks[i].interval(rng));
+            let action = self.tasks[i].action.clone();
+            self.run(&action, now, player, entity_id, rng, &mut emits);
+        }
+        emits
+    }
+
+    fn run(
+        &mut self,
+        action: &Action,
+        now: Duration,
+        player: &PlayerState,
+        entity_id: i32,
+        rng: &mut impl Rng,
+        emits: &mut Vec<Emit>,
+    ) {
+        match action {
+            Action::SwingArm => emits.push(Emit::Swing(Animation { hand: 0 })),
+            Action::RotateHead => emits.push(Emit::Look(PlayerLook {
+                yaw: (player.yaw + rng.gen_range(-60.0..=60.0)) % 360.0,
+                pitch: rng.gen_range(-30.0..=30.0),
+                on_ground: player.on_ground,
+            })),
+            // Still in the air from the last one
+            Action::Jump if !self.queued.is_empty() => {}
+            Action::Jump => {
+                let mut at = now;
+                for (height, on_ground) in jump_heights() {
+                    self.queued.push_back((
+                        at,
+                        Emit::Position(PlayerPosition {
+                            x: player.x,
+                            y: player.y + height,
+                            z: player.z,
+                            on_ground,
+                        }),
+                    ));
+                    at += TICK;
+                }
+                emits.push(self.queued.pop_front().unwrap().1);
+            }
+            Action::Sneak => {
+                self.sneaking = !self.sneaking;
+                emits.push(Emit::Action(EntityAction {
+                    entity_id,
+                    action: if self.sneaking {
+                        START_SNEAKING
+                    } else {
+                        STOP_SNEAKING
+                    },
+                    jump_boost: 0,
+                }));
+            }
+            Action::Chat(message) => match message.strip_prefix('/') {
+                Some(command) if self.ver >= ProtocolVersion::V_1_20_1 => {
+                    emits.push(Emit::Command(ChatCommand {
+                        command: command.to_string(),
+                    }))
+                }
+                _ => emits.push(Emit::Chat(ChatMessage {
+                    message: message.clone(),
+                })),
+            },
+        }
+    }
+}
+
+// Height above the ground each tick of a vanilla jump, ending back on it
+fn jump_heights() -> Vec<(f64, bool)> {
+    let mut heights = Vec::new();
+    let (mut height, mut velocity) = (0.0, 0.42);
+    loop {
+        height += velocity;
+        velocity = (velocity - 0.08) * 0.98;
+        if height <= 0.0 {
+            heights.push((0.0, true));
+            return heights;
+        }
+        heights.push((height, false));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::afk::*;
+    use crate::serialize::buffer::ByteBuf;
+    use crate::serialize::packet::{Packet, PacketID};
+    use crate::serialize::protocol::State;
+    use crate::serialize::registry::{self, Direction};
+    use crate::serialize::var::VarIntReader;
+    use rand::rngs::mock::StepRng;
+
+    const ENTITY_ID: i32 = 42;
+
+    fn seconds(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    fn task(action: Action, min: u64, max: u64) -> Task {
+        Task {
+            action,
+            min: seconds(min),
+            max: seconds(max),
+        }
+    }
+
+    fn standing() -> PlayerState {
+        PlayerState {
+            x: 0.5,
+            y: 64.0,
+            z: -0.5,
+            on_ground: true,
+            ..PlayerState::default()
+        }
+    }
+
+    // Polls every tick for `secs` seconds, following the movement sent the
+    // way the connection does. Each packet comes with when it was sent.
+    fn simulate(
+        afk: &mut AntiAfk,
+        player: &mut PlayerState,
+        secs: u64,
+        rng: &mut impl Rng,
+    ) -> Vec<(Duration, Emit)> {
+        let mut sent = Vec::new();
+        let mut now = Duration::ZERO;
+        while now <= seconds(secs) {
+            for emit in afk.poll(now, player, ENTITY_ID, rng) {
+                let mut buf = match &emit {
+                    Emit::Look(packet) => packet.serialize_with_id(&afk.ver).unwrap(),
+                    Emit::Position(packet) => packet.serialize_with_id(&afk.ver).unwrap(),
+                    _ => Box::new(ByteBuf::new()),
+                };
+                if let Ok(id) = buf.read_var_int() {
+                    let kind =
+                        registry::lookup(&afk.ver, &State::Play, &Direction::Serverbound, id);
+                    player.update_sent(kind, &buf, &afk.ver).unwrap();
+                }
+                sent.push((now, emit));
+            }
+            now += TICK;
+        }
+        sent
+    }
+
+    #[test]
+    fn tasks_run_on_their_intervals() {
+        let mut afk = AntiAfk::new(
+            ProtocolVersion::V_1_12_2,
+            vec![
+                task(Action::SwingArm, 5, 10),
+                task(Action::Sneak, 7, 7),
+                task(Action::Chat("/afk".to_string()), 20, 30),
+            ],
+        );
+
+        // StepRng(0, 0) always picks the shortest interval
+        let sent = simulate(&mut afk, &mut standing(), 20, &mut StepRng::new(0, 0));
+        let swing = Emit::Swing(Animation { hand: 0 });
+        let sneak = |action| {
+            Emit::Action(EntityAction {
+                entity_id: ENTITY_ID,
+                action,
+                jump_boost: 0,
+            })
+        };
+        let chat = Emit::Chat(ChatMessage {
+            message: "/afk".to_string(),
+        });
+        assert_eq!(
+            vec![
+                (seconds(5), swing.clone()),
+                (seconds(7), sneak(START_SNEAKING)),
+                (seconds(10), swing.clone()),
+                (seconds(14), sneak(STOP_SNEAKING)),
+                (seconds(15), swing.clone()),
+                (seconds(20), swing),
+                (seconds(20), chat),
+            ],
+            sent
+        );
+        assert!(!afk.sneaking());
+    }
+
+    #[test]
+    fn random_intervals_stay_in_range() {
+        let mut afk = AntiAfk::new(
+            ProtocolVersion::V_1_12_2,
+            vec![task(Action::RotateHead, 2, 4)],
+        );
+        let mut player = standing();
+        let sent = simulate(&mut afk, &mut player, 120, &mut rand::thread_rng());
+
+        // Polled once a tick, so up to a tick late
+        let mut last = Duration::ZERO;
+        for (at, emit) in &sent {
+            assert!(*at - last >= seconds(2) && *at - last <= seconds(4) + TICK);
+            last = *at;
+            match emit {
+                Emit::Look(look) => assert!(look.pitch.abs() <= 30.0 && look.on_ground),
+                other => panic!("{:?} sent", other),
+            }
+        }
+        assert!(sent.len() >= 30);
+        match &sent.last().unwrap().1 {
+            Emit::Look(look) => assert_eq!(look.yaw, player.yaw),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn jumps_land_where_they_started() {
+        let mut afk = AntiAfk::new(ProtocolVersion::V_1_16_5, vec![task(Action::Jump, 3, 3)]);
+        let mut player = standing();
+        let sent = simulate(&mut afk, &mut player, 4, &mut StepRng::new(0, 0));
+
+        // A position every tick from 3s until back on the ground
+        let heights: Vec<f64> = sent
+            .iter()
+            .enumerate()
+            .map(|(i, (at, emit))| {
+                assert_eq!(seconds(3) + TICK * i as u32, *at);
+                match emit {
+                    Emit::Position(pos) => {
+                        assert_eq!((0.5, -0.5), (pos.x, pos.z));
+                        pos.y - 64.0
+                    }
+                    other => panic!("{:?} sent", other),
+                }
+            })
+            .collect();
+        assert_eq!(12, heights.len());
+        assert!((heights[0] - 0.42).abs() < 1e-9);
+        let peak = heights.iter().cloned().fold(0.0, f64::max);
+        assert!(peak > 1.24 && peak < 1.26);
+        assert_eq!(0.0, *heights.last().unwrap());
+        assert_eq!((64.0, true), (player.y, player.on_ground));
+
+        // A teleport mid-jump drops the rest of it
+        let mut afk = AntiAfk::new(ProtocolVersion::V_1_16_5, vec![task(Action::Jump, 1, 1)]);
+        let mut rng = StepRng::new(0, 0);
+        afk.poll(Duration::ZERO, &player, ENTITY_ID, &mut rng);
+        assert_eq!(1, afk.poll(seconds(1), &player, ENTITY_ID, &mut rng).len());
+        afk.interrupt();
+        assert!(afk
+            .poll(seconds(1) + TICK, &player, ENTITY_ID, &mut rng)
+            .is_empty());
+    }
+
+    #[test]
+    fn commands_follow_the_version() {
+        let mut afk = AntiAfk::new(
+            ProtocolVersion::V_1_20_1,
+            vec![
+                task(Action::Chat("/spawn".to_string()), 1, 1),
+                task(Action::Chat("hi".to_string()), 1, 1),
+            ],
+        );
+        let mut rng = StepRng::new(0, 0);
+        afk.poll(Duration::ZERO, &standing(), ENTITY_ID, &mut rng);
+        let emits = afk.poll(seconds(1), &standing(), ENTITY_ID, &mut rng);
+        assert_eq!(
+            vec![
+                Emit::Command(ChatCommand {
+                    command: "spawn".to_string()
+                }),
+                Emit::Chat(ChatMessage {
+                    message: "hi".to_string()
+                }),
+            ],
+            emits
+        );
+        let command = match &emits[0] {
+            Emit::Command(command) => command,
+            _ => unreachable!(),
+        };
+        let mut buf = command
+            .serialize_with_id(&ProtocolVersion::V_1_20_1)
+            .unwrap();
+        assert_eq!(
+            PacketID::ChatCommand
+                .id(&ProtocolVersion::V_1_20_1)
+                .unwrap(),
+            buf.read_var_int().unwrap()
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use crate::auth::{server_hash, Profile, SessionServer};
+use crate::crypto::{generate_shared_secret, Cfb8, PublicKey};
+use crate::error::{Error, Result};
+use crate::mc::{frame, read_packet, BufferSize};
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::compression::Compression;
+use crate::serialize::frame::FrameDecoder;
+use crate::serialize::packet::clientbound::{
+    EncryptionRequest, LoginDisconnect, LoginSuccess, SetCompression,
+};
+use crate::serialize::packet::serverbound::{EncryptionResponse, Handshake, LoginStart};
+use crate::serialize::packet::{deserialize_new, Packet, PacketID};
+use crate::serialize::protocol::{ProtocolVersion, State};
+use crate::serialize::registry::{self, Direction};
+use crate::serialize::var::VarIntWriter;
+use std::io::{self, Write};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+// Reading side of an `AsyncConnection`
+pub struct ReadHalf {
+    stream: OwnedReadHalf,
+    ver: ProtocolVersion,
+    state: State,
+    compression: Option<Compression>,
+    cipher: Option<Cfb8>,
+    frames: FrameDecoder,
+}
+
+// Writing side of an `AsyncConnection`
+pub struct WriteHalf {
+    stream: OwnedWriteHalf,
+    ver: ProtocolVersion,
+    compression: Option<Compression>,
+    cipher: Option<Cfb8>,
+}
+
+// A connection driven by tokio instead of a thread of its own, so one runtime
+// can host many of them. Log in with it whole, then split it to read and
+// write from separate tasks.
+pub struct AsyncConnection {
+    reader: ReadHalf,
+    writer: WriteHalf,
+    auth: Option<(SessionServer, Profile)>, // None in offline mode
+}
+
+impl ReadHalf {
+    pub fn version(&self) -> ProtocolVersion {
+        self.ver
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+
+    // What a clientbound packet read in the current state is
+    pub fn packet_id(&self, id: i32) -> PacketID {
+        registry::lookup(&self.ver, &self.state, &Direction::Clientbound, id)
+    }
+
+    // Waits for the next packet, None once the other end has closed the
+    // connection. Nothing is lost if the future is dropped before it's done.
+    pub async fn read_packet(&mut self) -> Result<Option<(i32, ByteBuf)>> {
+        let mut chunk = vec![0_u8; BufferSize::Medium as usize];
+        loop {
+            if let Some(frame) = self.frames.next_frame().map_err(Error::Framing)? {
+                return Ok(Some(read_packet(&frame, self.compression)?));
+            }
+
+            let n = self.stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            if let Some(cipher) = self.cipher.as_mut() {
+                cipher.decrypt(&mut chunk[..n]);
+            }
+            self.frames.push(&chunk[..n]);
+        }
+    }
+}
+
+impl WriteHalf {
+    pub async fn send_packet<P: Packet>(&mut self, packet: &P) -> Result<()> {
+        let buf = packet
+            .serialize_with_id(&self.ver)
+            .ok_or_else(|| Error::unsupported::<P>(&self.ver))?;
+        self.write_frame(frame(*buf, self.compression)).await
+    }
+
+    // Sends a packet we only have the id and body of, e.g. one read from
+    // another connection
+    pub async fn send_raw(&mut self, id: i32, data: &[u8]) -> Result<()> {
+        let mut buf = ByteBuf::new();
+        buf.write_var_int(id);
+        buf.write_all(data)?;
+        self.write_frame(frame(buf, self.compression)).await
+    }
+
+    async fn write_frame(&mut self, buf: ByteBuf) -> Result<()> {
+        let mut out = buf.as_slice().to_vec();
+        if let Some(cipher) = self.cipher.as_mut() {
+            cipher.encrypt(&mut out);
+        }
+        Ok(self.stream.write_all(&out).await?)
+    }
+}
+
+impl AsyncConnection {
+    pub async fn connect(
+        addr: impl ToSocketAddrs,
+        ver: ProtocolVersion,
+    ) -> Result<AsyncConnection> {
+        Ok(AsyncConnection::from_stream(
+            TcpStream::connect(addr).await?,
+            ver,
+        ))
+    }
+
+    // Wrap an already connected stream, e.g. one accepted by a listener
+    pub fn from_stream(stream: TcpStream, ver: ProtocolVersion) -> AsyncConnection {
+        let (read, write) = stream.into_split();
+        AsyncConnection {
+            reader: ReadHalf {
+                stream: read,
+                ver,
+                state: State::Handshaking,
+                compression: None,
+                cipher: None,
+                frames: FrameDecoder::new(),
+            },
+            writer: WriteHalf {
+                stream: write,
+                ver,
+                compression: None,
+                cipher: None,
+            },
+            auth: None,
+        }
+    }
+
+    // Join through `session` as `profile` when the server asks for encryption
+    pub fn set_authentication(&mut self, session: SessionServer, profile: Profile) {
+        self.auth = Some((session, profile));
+    }
+
+    pub fn state(&self) -> State {
+        self.reader.state()
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.reader.set_state(state);
+    }
+
+    pub fn packet_id(&self, id: i32) -> PacketID {
+        self.reader.packet_id(id)
+    }
+
+    pub fn compression_enabled(&self) -> bool {
+        self.reader.compression.is_some()
+    }
+
+    pub fn set_compression_threshold(&mut self, threshold: i32) {
+        self.reader.compression = Compression::new(threshold);
+        self.writer.compression = Compression::new(threshold);
+    }
+
+    pub fn encryption_enabled(&self) -> bool {
+        self.reader.cipher.is_some()
+    }
+
+    // Everything sent or read after this call is encrypted with the shared secret
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.reader.cipher = Some(Cfb8::new(shared_secret, shared_secret));
+        self.writer.cipher = Some(Cfb8::new(shared_secret, shared_secret));
+    }
+
+    pub async fn read_packet(&mut self) -> Result<Option<(i32, ByteBuf)>> {
+        self.reader.read_packet().await
+    }
+
+    pub async fn send_packet(&mut self, packet: &impl Packet) -> Result<()> {
+        self.writer.send_packet(packet).await
+    }
+
+    pub async fn send_raw(&mut self, id: i32, data: &[u8]) -> Result<()> {
+        self.writer.send_raw(id, data).await
+    }
+
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        (self.reader, self.writer)
+    }
+
+    // Sends Handshake and Login Start then answers the server's login
+    // packets. Fails with Disconnected if the server kicks us.
+    pub async fn login(
+        &mut self,
+        handshake: &Handshake,
+        login_start: &LoginStart,
+    ) -> Result<LoginSuccess> {
+        self.send_packet(handshake).await?;
+        self.set_state(State::Login);
+        self.send_packet(login_start).await?;
+
+        loop {
+            let (id, mut buf) = match self.read_packet().await? {
+                Some(packet) => packet,
+                None => return Err(Error::Closed),
+            };
+
+            match self.packet_id(id) {
+                PacketID::EncryptionRequest => {
+                    let req = deserialize_new::<EncryptionRequest>(&mut buf, &self.reader.ver)?;
+                    self.handle_encryption_request(&req).await?;
+                }
+                PacketID::SetCompression => {
+                    let set_compression =
+                        deserialize_new::<SetCompression>(&mut buf, &self.reader.ver)?;
+                    self.set_compression_threshold(set_compression.threshold);
+                }
+                PacketID::LoginSuccess => {
+                    self.set_state(State::Play);
+                    return Ok(*deserialize_new::<LoginSuccess>(
+                        &mut buf,
+                        &self.reader.ver,
+                    )?);
+                }
+                PacketID::LoginDisconnect => {
+                    self.set_state(State::Disconnected);
+                    let disconnect =
+                        deserialize_new::<LoginDisconnect>(&mut buf, &self.reader.ver)?;
+                    return Err(Error::Disconnected(disconnect.reason));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn handle_encryption_request(&mut self, req: &EncryptionRequest) -> Result<()> {
+        let public_key = PublicKey::from_der(&req.public_key)?;
+        let shared_secret = generate_shared_secret();
+
+        if let Some((session, profile)) = self.auth.clone() {
+            // The session server client blocks
+            let hash = server_hash(&req.server_id, &shared_secret, &req.public_key);
+            tokio::task::spawn_blocking(move || session.join(&profile, &hash))
+                .await
+                .map_err(|e| Error::Io(io::Error::other(e)))??;
+        }
+
+        let response = EncryptionResponse {
+            shared_secret: public_key.encrypt(&shared_secret)?,
+            verify_token: public_key.encrypt(&req.verify_token)?,
+        };
+        self.send_packet(&response).await?;
+
+        // The server encrypts everything after it reads the response
+        self.enable_encryption(&shared_secret);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_mc::*;
+    use crate::mc::Connection;
+    use crate::serialize::packet::clientbound::KeepAlive;
+    use crate::serialize::packet::serverbound;
+    use crate::serialize::packet::serverbound::LoginState;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+    use std::thread;
+
+    const VER: ProtocolVersion = ProtocolVersion::V_1_12_2;
+
+    fn handshake(port: u16) -> Handshake {
+        Handshake {
+            protocol_version: 340,
+            address: "127.0.0.1".to_string(),
+            port,
+            next_state: LoginState::Login,
+        }
+    }
+
+    fn login_start(username: &str) -> LoginStart {
+        LoginStart {
+            username: username.to_string(),
+            uuid: None,
+        }
+    }
+
+    fn login_success(username: &str) -> LoginSuccess {
+        LoginSuccess {
+            uuid: "c0ffee00-0000-4000-8000-000000000000".to_string(),
+            username: username.to_string(),
+            properties: Vec::new(),
+        }
+    }
+
+    // Reads packets on a blocking connection until at least `n` have arrived
+    fn read_n(conn: &mut Connection, n: usize) -> Vec<(i32, ByteBuf)> {
+        let mut pkts = Vec::new();
+        while pkts.len() < n {
+            pkts.append(&mut conn.read_packets().unwrap());
+        }
+        pkts
+    }
+
+    #[tokio::test]
+    async fn compressed_login_against_blocking_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = Connection::from_stream(stream, VER, BufferSize::Medium);
+            read_n(&mut conn, 2);
+            conn.send_packet(&SetCompression { threshold: 256 })
+                .unwrap();
+            conn.set_compression_threshold(256);
+            conn.send_packet(&login_success("idler")).unwrap();
+            conn.send_packet(&KeepAlive { id: 1337 }).unwrap();
+        });
+
+        let mut conn = AsyncConnection::connect(addr, VER).await.unwrap();
+        let success = conn
+            .login(&handshake(addr.port()), &login_start("idler"))
+            .await
+            .unwrap();
+        assert_eq!("idler", success.username);
+        assert!(conn.compression_enabled());
+        assert_eq!(State::Play, conn.state());
+
+        let (id, mut buf) = conn.read_packet().await.unwrap().unwrap();
+        assert_eq!(PacketID::KeepAliveCB, conn.packet_id(id));
+        assert_eq!(
+            1337,
+            deserialize_new::<KeepAlive>(&mut buf, &VER).unwrap().id
+        );
+
+        // Then the server hangs up
+        server.join().unwrap();
+        assert!(conn.read_packet().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn encrypted_split_halves() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Echoes keep alives back to the client once encryption is on
+        let server = thread::spawn(move || {
+            let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+            let der = private_key.to_public_key().to_public_key_der().unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = Connection::from_stream(stream, VER, BufferSize::Medium);
+            read_n(&mut conn, 2);
+
+            conn.send_packet(&EncryptionRequest {
+                server_id: "".to_string(),
+                public_key: der.as_bytes().to_vec(),
+                verify_token: vec![9, 9, 9, 9],
+            })
+            .unwrap();
+            let mut pkts = read_n(&mut conn, 1);
+            let response = deserialize_new::<EncryptionResponse>(&mut pkts[0].1, &VER).unwrap();
+            let secret = private_key
+                .decrypt(Pkcs1v15Encrypt, &response.shared_secret)
+                .unwrap();
+            let mut shared_secret = [0_u8; 16];
+            shared_secret.copy_from_slice(&secret);
+            conn.enable_encryption(&shared_secret);
+            conn.send_packet(&login_success("idler")).unwrap();
+
+            for (_, mut buf) in read_n(&mut conn, 3) {
+                let keep_alive = deserialize_new::<serverbound::KeepAlive>(&mut buf, &VER).unwrap();
+                conn.send_packet(&KeepAlive { id: keep_alive.id }).unwrap();
+            }
+        });
+
+        let mut conn = AsyncConnection::connect(addr, VER).await.unwrap();
+        conn.login(&handshake(addr.port()), &login_start("idler"))
+            .await
+            .unwrap();
+        assert!(conn.encryption_enabled());
+
+        let (mut reader, mut writer) = conn.into_split();
+        let reading = tokio::spawn(async move {
+            let mut ids = Vec::new();
+            while let Some((_, mut buf)) = reader.read_packet().await.unwrap() {
+                ids.push(deserialize_new::<KeepAlive>(&mut buf, &VER).unwrap().id);
+            }
+            ids
+        });
+        for id in 1..4 {
+            writer
+                .send_packet(&serverbound::KeepAlive { id })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(vec![1, 2, 3], reading.await.unwrap());
+        server.join().unwrap();
+    }
+
+    // A server and a hundred clients on a single threaded runtime
+    #[tokio::test]
+    async fn many_connections_one_thread() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        const CLIENTS: usize = 100;
+
+        tokio::spawn(async move {
+            for _ in 0..CLIENTS {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut conn = AsyncConnection::from_stream(stream, VER);
+                    conn.read_packet().await.unwrap();
+                    let (_, mut buf) = conn.read_packet().await.unwrap().unwrap();
+                    let username = deserialize_new::<LoginStart>(&mut buf, &VER)
+                        .unwrap()
+                        .username;
+                    conn.send_packet(&login_success(&username)).await.unwrap();
+
+                    let (id, mut buf) = conn.read_packet().await.unwrap().unwrap();
+                    assert_eq!(PacketID::KeepAliveSB.id(&VER).unwrap(), id);
+                    let keep_alive =
+                        deserialize_new::<serverbound::KeepAlive>(&mut buf, &VER).unwrap();
+                    conn.send_packet(&KeepAlive { id: keep_alive.id })
+                        .await
+                        .unwrap();
+                });
+            }
+        });
+
+        let clients: Vec<_> = (0..CLIENTS)
+            .map(|i| {
+                tokio::spawn(async move {
+                    let mut conn = AsyncConnection::connect(addr, VER).await.unwrap();
+                    let username = format!("idler{}", i);
+                    let success = conn
+                        .login(&handshake(addr.port()), &login_start(&username))
+                        .await
+                        .unwrap();
+                    assert_eq!(username, success.username);
+
+                    conn.send_packet(&serverbound::KeepAlive { id: i as i64 })
+                        .await
+                        .unwrap();
+                    let (_, mut buf) = conn.read_packet().await.unwrap().unwrap();
+                    deserialize_new::<KeepAlive>(&mut buf, &VER).unwrap().id
+                })
+            })
+            .collect();
+
+        for (i, client) in clients.into_iter().enumerate() {
+            assert_eq!(i as i64, client.await.unwrap());
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use sha1::{Digest, Sha1};
+use std::time::Duration;
+
+pub const MOJANG_SESSION_SERVER: &str = "https://sessionserver.mojang.com";
+
+#[derive(Debug)]
+pub enum AuthError {
+    Rejected(u16, String), // Status code and body from the session server
+    Transport(String),     // Could not reach the session server
+}
+
+// The account we log in as, `id` is the profile UUID without dashes
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub access_token: String,
+}
+
+// Minecraft's hex digest, the SHA1 is read as a signed big endian integer
+// and printed in hex with a minus sign instead of in two's complement
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_key);
+    let mut digest: [u8; 20] = hasher.finalize().into();
+
+    let negative = digest[0] & 0x80 != 0;
+    if negative {
+        // Negate the two's complement value to get its magnitude
+        let mut carry = true;
+        for b in digest.iter_mut().rev() {
+            *b = !*b;
+            if carry {
+                let (sum, overflow) = b.overflowing_add(1);
+                *b = sum;
+                carry = overflow;
+            }
+        }
+    }
+
+    let hex = hex::encode(digest);
+    let hex = match hex.trim_start_matches('0') {
+        "" => "0",
+        trimmed => trimmed,
+    };
+    if negative {
+        format!("-{}", hex)
+    } else {
+        hex.to_string()
+    }
+}
+
+#[derive(Clone)]
+pub struct SessionServer {
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl Default for SessionServer {
+    fn default() -> Self {
+        SessionServer::new(MOJANG_SESSION_SERVER)
+    }
+}
+
+impl SessionServer {
+    // `base_url` is everything before `/session/minecraft/join`
+    pub fn new(base_url: &str) -> SessionServer {
+        SessionServer {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Tells the session server we are joining the server identified by `server_hash`,
+    // this has to happen before the Encryption Response is sent
+    pub fn join(&self, profile: &Profile, server_hash: &str) -> Result<(), AuthError> {
+        let body = serde_json::json!({
+            "accessToken": profile.access_token,
+            "selectedProfile": profile.id,
+            "serverId": server_hash,
+        });
+
+        let url = format!("{}/session/minecraft/join", self.base_url);
+        match self
+            .agent
+            .post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+        {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, response)) => Err(AuthError::Rejected(
+                code,
+                response.into_string().unwrap_or_default(),
+            )),
+            Err(e) => Err(AuthError::Transport(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn hash_of(name: &str) -> String {
+        server_hash(name, &[], &[])
+    }
+
+    #[test]
+    fn server_hash_known_answers() {
+        assert_eq!("4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48", hash_of("Notch"));
+        assert_eq!("-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1", hash_of("jeb_"));
+        assert_eq!("88e16a1019277b15d58faf0541e11910eb756f6", hash_of("simon"));
+    }
+
+    #[test]
+    fn server_hash_concatenates_inputs() {
+        assert_eq!(hash_of("jeb_"), server_hash("j", b"e", b"b_"));
+    }
+
+    // Stub session server answering one request with `status`, returns the
+    // request line and body it received
+    fn stub_session_server(status: &'static str) -> (String, thread::JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                let lower = line.to_ascii_lowercase();
+                if let Some(len) = lower.strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap();
+                }
+            }
+
+            let mut body = vec![0_u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 9\r\nConnection: close\r\n\r\nForbidden",
+                status
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            (
+                request_line.trim_end().to_string(),
+                String::from_utf8(body).unwrap(),
+            )
+        });
+        (base_url, handle)
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            id: "069a79f444e94726a5befca90e38aaf5".to_string(),
+            name: "Notch".to_string(),
+            access_token: "token".to_string(),
+        }
+    }
+
+    #[test]
+    fn join_posts_to_session_server() {
+        let (base_url, server) = stub_session_server("204 No Content");
+        let session = SessionServer::new(&format!("{}/", base_url));
+        assert_eq!(base_url, session.base_url());
+
+        assert!(session
+            .join(&profile(), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1")
+            .is_ok());
+
+        let (request_line, body) = server.join().unwrap();
+        assert_eq!("POST /session/minecraft/join HTTP/1.1", request_line);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("token", json["accessToken"]);
+        assert_eq!("069a79f444e94726a5befca90e38aaf5", json["selectedProfile"]);
+        assert_eq!(
+            "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1",
+            json["serverId"]
+        );
+    }
+
+    #[test]
+    fn join_rejected() {
+        let (base_url, server) = stub_session_server("403 Forbidden");
+        let session = SessionServer::new(&base_url);
+
+        match session.join(&profile(), "0") {
+            Err(AuthError::Rejected(code, body)) => {
+                assert_eq!(403, code);
+                assert_eq!("Forbidden", body);
+            }
+            other => panic!("expected rejection, got {:?}", other),
+        }
+        server.join().unwrap();
+    }
+}
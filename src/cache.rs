@@ -0,0 +1,793 @@
+use crate::chunk::ChunkColumn;
+use crate::entity::Entities;
+use crate::player::PlayerState;
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::metadata::Metadata;
+use crate::serialize::packet::clientbound::{
+    BlockChange, ChunkData, EntityMetadata, EntityTeleport, MultiBlockChange, PlayerPositionAndLook,
+};
+use crate::serialize::packet::{deserialize_new, Packet, PacketID};
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::var::{DeserializeError, VarIntReader};
+use crate::tablist::TabList;
+use byteorder::{BigEndian, ReadBytesExt};
+use std::collections::HashMap;
+
+// A clientbound packet body as it was read, without its id
+type Cached = (PacketID, Vec<u8>);
+
+// An entity's spawn packet and what happened to it since. Where it moved to
+// is kept by the caller's `Entities`.
+struct CachedEntity {
+    spawn: Cached,
+    moved: bool,
+    head_look: Option<Vec<u8>>,
+    metadata: Option<Metadata>,       // Every update merged into one
+    equipment: HashMap<i32, Vec<u8>>, // By slot
+    properties: Option<Vec<u8>>,
+}
+
+// Everything a client needs to spawn into the world the idler is standing in,
+// kept up to date from every clientbound play packet
+pub struct WorldCache {
+    ver: ProtocolVersion,
+    join_game: Option<Vec<u8>>,
+    respawn: Option<Vec<u8>>,
+    // Packets where only the latest one matters
+    latest: HashMap<PacketID, Vec<u8>>,
+    window_items: Option<Vec<u8>>,
+    set_slots: HashMap<(i8, i16), Vec<u8>>, // By window and slot
+    // Whether the current dimension's chunks have sky light
+    sky_light: bool,
+    // With every block change since they loaded applied
+    chunks: HashMap<(i32, i32), ChunkColumn>,
+    entities: HashMap<i32, CachedEntity>,
+}
+
+// Kinds kept in `latest`, in the order they are replayed before the chunks
+const LATEST_BEFORE_CHUNKS: &[PacketID] = &[
+    PacketID::ServerDifficulty,
+    PacketID::PlayerAbilitiesCB,
+    PacketID::HeldItemChangeCB,
+    PacketID::PlayerListHeaderAndFooter,
+    PacketID::WorldBorder,
+    PacketID::TimeUpdate,
+    PacketID::SpawnPosition,
+];
+
+// Kinds kept in `latest`, replayed after the chunks and inventory
+const LATEST_AFTER_CHUNKS: &[PacketID] = &[PacketID::UpdateHealth, PacketID::SetExperience];
+
+const PLAYER_INVENTORY: i8 = 0;
+
+// Dimension id in Join Game and Respawn, the only one with sky light
+const OVERWORLD: i32 = 0;
+
+fn is_spawn(kind: PacketID) -> bool {
+    matches!(
+        kind,
+        PacketID::SpawnObject
+            | PacketID::SpawnExperienceOrb
+            | PacketID::SpawnGlobalEntity
+            | PacketID::SpawnMob
+            | PacketID::SpawnPainting
+            | PacketID::SpawnPlayer
+    )
+}
+
+impl WorldCache {
+    pub fn new(ver: ProtocolVersion) -> WorldCache {
+        WorldCache {
+            ver,
+            join_game: None,
+            respawn: None,
+            latest: HashMap::new(),
+            window_items: None,
+            set_slots: HashMap::new(),
+            sky_light: true,
+            chunks: HashMap::new(),
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    // Nothing is cached from a malformed packet
+    pub fn update(&mut self, kind: PacketID, data: &[u8]) -> Result<(), DeserializeError> {
+        let mut fields = ByteBuf::from(data);
+
+        match kind {
+            PacketID::JoinGame => {
+                if self.ver == ProtocolVersion::V_1_12_2 {
+                    fields.read_i32::<BigEndian>()?; // Entity id
+                    fields.read_u8()?; // Gamemode
+                    self.sky_light = fields.read_i32::<BigEndian>()? == OVERWORLD;
+                }
+                self.clear_world();
+                self.join_game = Some(data.to_vec());
+                self.respawn = None;
+            }
+            PacketID::Respawn => {
+                if self.ver == ProtocolVersion::V_1_12_2 {
+                    self.sky_light = fields.read_i32::<BigEndian>()? == OVERWORLD;
+                }
+                // The server sends the world again after a respawn
+                self.clear_world();
+                self.respawn = Some(data.to_vec());
+            }
+            PacketID::WindowItems if data.first() == Some(&(PLAYER_INVENTORY as u8)) => {
+                self.window_items = Some(data.to_vec());
+                self.set_slots.clear();
+            }
+            PacketID::SetSlot => {
+                let window = fields.read_i8()?;
+                let slot = fields.read_i16::<BigEndian>()?;
+                // Window -1 is the item on the cursor
+                if window == PLAYER_INVENTORY || window == -1 {
+                    self.set_slots.insert((window, slot), data.to_vec());
+                }
+            }
+            PacketID::ChunkData => {
+                let packet = deserialize_new::<ChunkData>(&mut fields, &self.ver)?;
+                if packet.full {
+                    let column = ChunkColumn::decode(&packet, self.sky_light)?;
+                    self.chunks.insert((packet.x, packet.z), column);
+                } else if let Some(column) = self.chunks.get_mut(&(packet.x, packet.z)) {
+                    column.update(&packet)?;
+                }
+            }
+            PacketID::UnloadChunk => {
+                let x = fields.read_i32::<BigEndian>()?;
+                let z = fields.read_i32::<BigEndian>()?;
+                self.chunks.remove(&(x, z));
+            }
+            PacketID::BlockChange => {
+                let change = deserialize_new::<BlockChange>(&mut fields, &self.ver)?;
+                if let Some(column) = self.chunks.get_mut(&change.location.chunk()) {
+                    column.apply_block_change(&change)?;
+                }
+            }
+            PacketID::MultiBlockChange => {
+                let change = deserialize_new::<MultiBlockChange>(&mut fields, &self.ver)?;
+                let pos = (change.chunk_x, change.chunk_z);
+                if let Some(column) = self.chunks.get_mut(&pos) {
+                    column.apply_multi_block_change(&change)?;
+                }
+            }
+            PacketID::DestroyEntities => {
+                let count = fields.read_var_int()?;
+                for _ in 0..count {
+                    self.entities.remove(&fields.read_var_int()?);
+                }
+            }
+            kind if is_spawn(kind) => {
+                let id = fields.read_var_int()?;
+                let entity = CachedEntity {
+                    spawn: (kind, data.to_vec()),
+                    moved: false,
+                    head_look: None,
+                    metadata: None,
+                    equipment: HashMap::new(),
+                    properties: None,
+                };
+                self.entities.insert(id, entity);
+            }
+            PacketID::EntityRelativeMove
+            | PacketID::EntityLookAndRelativeMove
+            | PacketID::EntityLook
+            | PacketID::EntityTeleport => {
+                let id = fields.read_var_int()?;
+                if let Some(entity) = self.entities.get_mut(&id) {
+                    entity.moved = true;
+                }
+            }
+            PacketID::EntityHeadLook => {
+                let id = fields.read_var_int()?;
+                if let Some(entity) = self.entities.get_mut(&id) {
+                    entity.head_look = Some(data.to_vec());
+                }
+            }
+            PacketID::EntityMetadata => {
+                let update = deserialize_new::<EntityMetadata>(&mut fields, &self.ver)?;
+                if let Some(entity) = self.entities.get_mut(&update.entity_id) {
+                    entity
+                        .metadata
+                        .get_or_insert_with(Metadata::default)
+                        .merge(update.metadata);
+                }
+            }
+            PacketID::EntityEquipment => {
+                let id = fields.read_var_int()?;
+                let slot = fields.read_var_int()?;
+                if let Some(entity) = self.entities.get_mut(&id) {
+                    entity.equipment.insert(slot, data.to_vec());
+                }
+            }
+            PacketID::EntityProperties => {
+                let id = fields.read_var_int()?;
+                if let Some(entity) = self.entities.get_mut(&id) {
+                    entity.properties = Some(data.to_vec());
+                }
+            }
+            kind if LATEST_BEFORE_CHUNKS.contains(&kind) || LATEST_AFTER_CHUNKS.contains(&kind) => {
+                self.latest.insert(kind, data.to_vec());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn clear_world(&mut self) {
+        self.chunks.clear();
+        self.entities.clear();
+    }
+
+    // The packets to send a client that just logged in, in the order the
+    // server would send them. Empty until Join Game has been seen. The client
+    // is put where `player` is, with the id of the last teleport, which the
+    // server has already had confirmed. Entities are put where `entities` has
+    // them and the tab list is taken from `tab_list`, both fed the same packets.
+    pub fn replay(
+        &self,
+        player: Option<&PlayerState>,
+        entities: &Entities,
+        tab_list: &TabList,
+    ) -> Vec<(i32, Vec<u8>)> {
+        let join_game = match self.join_game.as_ref() {
+            Some(join_game) => join_game,
+            None => return Vec::new(),
+        };
+
+        let mut pkts: Vec<Cached> = vec![(PacketID::JoinGame, join_game.clone())];
+        if let Some(respawn) = self.respawn.as_ref() {
+            pkts.push((PacketID::Respawn, respawn.clone()));
+        }
+
+        for kind in LATEST_BEFORE_CHUNKS {
+            if let Some(data) = self.latest.get(kind) {
+                pkts.push((*kind, data.clone()));
+            }
+        }
+
+        if let Some(player) = player {
+            let teleport = PlayerPositionAndLook {
+                x: player.x,
+                y: player.y,
+                z: player.z,
+                yaw: player.yaw,
+                pitch: player.pitch,
+                flags: 0,
+                teleport_id: player.teleport_id,
+            };
+            pkts.push((PacketID::PlayerPositionAndLookCB, teleport.body(&self.ver)));
+        }
+
+        // Everyone at once, rather than everything that happened to the list
+        if let Some(item) = tab_list.replay() {
+            pkts.push((PacketID::PlayerListItem, item.body(&self.ver)));
+        }
+
+        for column in self.chunks.values() {
+            pkts.push((PacketID::ChunkData, column.encode().body(&self.ver)));
+        }
+
+        if let Some(data) = self.window_items.as_ref() {
+            pkts.push((PacketID::WindowItems, data.clone()));
+        }
+        for data in self.set_slots.values() {
+            pkts.push((PacketID::SetSlot, data.clone()));
+        }
+
+        for kind in LATEST_AFTER_CHUNKS {
+            if let Some(data) = self.latest.get(kind) {
+                pkts.push((*kind, data.clone()));
+            }
+        }
+
+        for (&id, entity) in &self.entities {
+            pkts.push(entity.spawn.clone());
+            // However far it went, one teleport puts it where it is now
+            let position = entities.get(id).filter(|_| entity.moved);
+            if let Some(position) = position {
+                let teleport = EntityTeleport {
+                    entity_id: id,
+                    x: position.x,
+                    y: position.y,
+                    z: position.z,
+                    yaw: position.yaw,
+                    pitch: position.pitch,
+                    on_ground: position.on_ground,
+                };
+                pkts.push((PacketID::EntityTeleport, teleport.body(&self.ver)));
+            }
+            if let Some(data) = entity.head_look.as_ref() {
+                pkts.push((PacketID::EntityHeadLook, data.clone()));
+            }
+            if let Some(metadata) = entity.metadata.as_ref() {
+                let update = EntityMetadata {
+                    entity_id: id,
+                    metadata: metadata.clone(),
+                };
+                pkts.push((PacketID::EntityMetadata, update.body(&self.ver)));
+            }
+            for data in entity.equipment.values() {
+                pkts.push((PacketID::EntityEquipment, data.clone()));
+            }
+            if let Some(data) = entity.properties.as_ref() {
+                pkts.push((PacketID::EntityProperties, data.clone()));
+            }
+        }
+
+        pkts.into_iter()
+            // Everything was read from or made for this version
+            .filter_map(|(kind, data)| Some((kind.id(&self.ver)?, data)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::*;
+    use crate::serialize::bytes::WriteBytes;
+    use crate::serialize::field::Field;
+    use crate::serialize::metadata::Value;
+    use crate::serialize::packet::clientbound::*;
+    use crate::serialize::packet::deserialize_new;
+    use crate::serialize::position::Position;
+    use crate::serialize::protocol::State;
+    use crate::serialize::registry::{self, Direction};
+    use byteorder::WriteBytesExt;
+
+    fn join_game() -> ByteBuf {
+        let mut buf = ByteBuf::new();
+        buf.write_i32::<BigEndian>(1337).unwrap(); // Entity id
+        buf.write_bytes(&[0, 0, 0, 0, 0, 2, 20]);
+        buf
+    }
+
+    // A full column that is all air
+    fn chunk(x: i32, z: i32) -> Vec<u8> {
+        ChunkData {
+            x,
+            z,
+            full: true,
+            bit_mask: 0,
+            data: vec![1; 256], // Biomes
+            block_entity_count: 0,
+            block_entities: Vec::new(),
+        }
+        .body(&ProtocolVersion::V_1_12_2)
+    }
+
+    fn spawn_player(id: i32) -> Vec<u8> {
+        let spawn = SpawnPlayer {
+            entity_id: id,
+            y: 64.0,
+            ..SpawnPlayer::default()
+        };
+        let mut buf = ByteBuf::from(spawn.body(&ProtocolVersion::V_1_12_2).as_slice());
+        Metadata::default().write_to(&mut buf);
+        buf.as_slice().to_vec()
+    }
+
+    // With no player, entities or tab list beyond what's in `cache`
+    fn replayed(cache: &WorldCache) -> Vec<(i32, Vec<u8>)> {
+        cache.replay(None, &Entities::new(cache.ver), &TabList::new(cache.ver))
+    }
+
+    fn kinds(replay: &[(i32, Vec<u8>)]) -> Vec<PacketID> {
+        let ver = ProtocolVersion::V_1_12_2;
+        replay
+            .iter()
+            .map(|(id, _)| registry::lookup(&ver, &State::Play, &Direction::Clientbound, *id))
+            .collect()
+    }
+
+    #[test]
+    fn nothing_to_replay_before_join_game() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache.update(PacketID::TimeUpdate, &[0_u8; 16]).unwrap();
+        assert!(replayed(&cache).is_empty());
+    }
+
+    #[test]
+    fn replay_order_and_contents() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        cache.update(PacketID::UpdateHealth, &[1_u8; 9]).unwrap();
+        cache.update(PacketID::ChunkData, &chunk(0, 0)).unwrap();
+        cache.update(PacketID::TimeUpdate, &[1_u8; 16]).unwrap();
+        cache.update(PacketID::TimeUpdate, &[2_u8; 16]).unwrap();
+
+        assert_eq!(
+            vec![
+                PacketID::JoinGame,
+                PacketID::TimeUpdate,
+                PacketID::ChunkData,
+                PacketID::UpdateHealth
+            ],
+            kinds(&replayed(&cache))
+        );
+
+        // Bodies are replayed exactly as they were read, only the latest time is kept
+        let replay = replayed(&cache);
+        assert_eq!(join_game().as_slice(), replay[0].1.as_slice());
+        assert_eq!(vec![2_u8; 16], replay[1].1);
+        assert_eq!(chunk(0, 0).as_slice(), replay[2].1.as_slice());
+    }
+
+    #[test]
+    fn chunks_follow_loads_unloads_and_changes() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        cache.update(PacketID::ChunkData, &chunk(-1, 2)).unwrap();
+        cache.update(PacketID::ChunkData, &chunk(5, 5)).unwrap();
+        assert_eq!(2, cache.chunk_count());
+
+        // A block at x = -3, y = 64, z = 40 is in chunk (-1, 2)
+        let change = BlockChange {
+            location: Position {
+                x: -3,
+                y: 64,
+                z: 40,
+            },
+            block_state: 1 << 4,
+        };
+        for _ in 0..3 {
+            cache
+                .update(PacketID::BlockChange, &change.body(&cache.ver))
+                .unwrap();
+        }
+        let multi = MultiBlockChange {
+            chunk_x: -1,
+            chunk_z: 2,
+            records: vec![BlockChangeRecord {
+                x: 0,
+                y: 10,
+                z: 15,
+                block_state: 2 << 4,
+            }],
+        };
+        cache
+            .update(PacketID::MultiBlockChange, &multi.body(&cache.ver))
+            .unwrap();
+        // Changes to chunks we don't have are dropped
+        let elsewhere = BlockChange {
+            location: Position { x: 0, y: 0, z: 0 },
+            block_state: 1 << 4,
+        };
+        cache
+            .update(PacketID::BlockChange, &elsewhere.body(&cache.ver))
+            .unwrap();
+
+        let mut unload = ByteBuf::new();
+        unload.write_i32::<BigEndian>(5).unwrap();
+        unload.write_i32::<BigEndian>(5).unwrap();
+        cache
+            .update(PacketID::UnloadChunk, unload.as_slice())
+            .unwrap();
+        assert_eq!(1, cache.chunk_count());
+
+        // The changes are in the column rather than replayed after it
+        assert_eq!(
+            vec![PacketID::JoinGame, PacketID::ChunkData],
+            kinds(&replayed(&cache))
+        );
+        let replay = replayed(&cache);
+        let mut data = ByteBuf::from(replay[1].1.as_slice());
+        let packet = deserialize_new::<ChunkData>(&mut data, &cache.ver).unwrap();
+        let column = ChunkColumn::decode(&packet, true).unwrap();
+        assert_eq!((-1, 2), (column.x, column.z));
+        assert_eq!(Some(1 << 4), column.get_block(13, 64, 8));
+        assert_eq!(Some(2 << 4), column.get_block(0, 10, 15));
+
+        // A new full chunk replaces the column and its changes
+        cache.update(PacketID::ChunkData, &chunk(-1, 2)).unwrap();
+        assert_eq!(chunk(-1, 2).as_slice(), replayed(&cache)[1].1.as_slice());
+    }
+
+    #[test]
+    fn chunks_in_the_nether_have_no_sky_light() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        let mut respawn = ByteBuf::new();
+        respawn.write_i32::<BigEndian>(-1).unwrap(); // The nether
+        respawn.write_bytes(&[0, 0, 0]);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        cache.update(PacketID::Respawn, respawn.as_slice()).unwrap();
+        cache.update(PacketID::ChunkData, &chunk(0, 0)).unwrap();
+        let change = BlockChange {
+            location: Position { x: 1, y: 1, z: 1 },
+            block_state: 1 << 4,
+        };
+        cache
+            .update(PacketID::BlockChange, &change.body(&cache.ver))
+            .unwrap();
+
+        let replay = replayed(&cache);
+        let mut data = ByteBuf::from(replay[2].1.as_slice());
+        let packet = deserialize_new::<ChunkData>(&mut data, &cache.ver).unwrap();
+        let column = ChunkColumn::decode(&packet, false).unwrap();
+        assert_eq!(Some(1 << 4), column.get_block(1, 1, 1));
+        assert_eq!(packet.data, column.encode().data);
+    }
+
+    #[test]
+    fn entities_are_spawned_moved_and_destroyed() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        let mut entities = Entities::new(ProtocolVersion::V_1_12_2);
+        let update = |cache: &mut WorldCache, entities: &mut Entities, kind, data: &[u8]| {
+            cache.update(kind, data).unwrap();
+            entities.update(kind, data).unwrap();
+        };
+        update(
+            &mut cache,
+            &mut entities,
+            PacketID::JoinGame,
+            join_game().as_slice(),
+        );
+        update(
+            &mut cache,
+            &mut entities,
+            PacketID::SpawnPlayer,
+            &spawn_player(7),
+        );
+        update(
+            &mut cache,
+            &mut entities,
+            PacketID::SpawnPlayer,
+            &spawn_player(8),
+        );
+        let step = |id| {
+            EntityRelativeMove {
+                entity_id: id,
+                dx: 1.5,
+                dy: 0.0,
+                dz: -0.5,
+                on_ground: true,
+            }
+            .body(&ProtocolVersion::V_1_12_2)
+        };
+        for _ in 0..4 {
+            update(
+                &mut cache,
+                &mut entities,
+                PacketID::EntityRelativeMove,
+                &step(7),
+            );
+        }
+        // Moves for entities we never saw spawn are ignored
+        update(
+            &mut cache,
+            &mut entities,
+            PacketID::EntityRelativeMove,
+            &step(9),
+        );
+        assert_eq!(2, cache.entity_count());
+
+        let destroy = DestroyEntities {
+            entity_ids: vec![8],
+        };
+        let destroy = destroy.body(&cache.ver);
+        update(
+            &mut cache,
+            &mut entities,
+            PacketID::DestroyEntities,
+            &destroy,
+        );
+        assert_eq!(1, cache.entity_count());
+
+        // However many moves there were, they are replayed as one teleport
+        let replay = cache.replay(None, &entities, &TabList::new(cache.ver));
+        assert_eq!(
+            vec![
+                PacketID::JoinGame,
+                PacketID::SpawnPlayer,
+                PacketID::EntityTeleport
+            ],
+            kinds(&replay)
+        );
+        let mut data = ByteBuf::from(replay[2].1.as_slice());
+        let teleport = deserialize_new::<EntityTeleport>(&mut data, &cache.ver).unwrap();
+        assert_eq!(
+            (7, 6.0, 64.0, -2.0, true),
+            (
+                teleport.entity_id,
+                teleport.x,
+                teleport.y,
+                teleport.z,
+                teleport.on_ground
+            )
+        );
+    }
+
+    #[test]
+    fn metadata_updates_are_merged() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        cache
+            .update(PacketID::SpawnPlayer, &spawn_player(7))
+            .unwrap();
+        for (index, value) in [
+            (7, Value::Float(20.0)),
+            (0, Value::Byte(0x02)),
+            (7, Value::Float(12.5)),
+        ] {
+            let mut metadata = Metadata::default();
+            metadata.set(index, value);
+            let update = EntityMetadata {
+                entity_id: 7,
+                metadata,
+            };
+            cache
+                .update(PacketID::EntityMetadata, &update.body(&cache.ver))
+                .unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                PacketID::JoinGame,
+                PacketID::SpawnPlayer,
+                PacketID::EntityMetadata
+            ],
+            kinds(&replayed(&cache))
+        );
+        let replay = replayed(&cache);
+        let mut data = ByteBuf::from(replay[2].1.as_slice());
+        let update = deserialize_new::<EntityMetadata>(&mut data, &cache.ver).unwrap();
+        assert_eq!(Some(&Value::Float(12.5)), update.metadata.get(7));
+        assert_eq!(Some(&Value::Byte(0x02)), update.metadata.get(0));
+    }
+
+    #[test]
+    fn inventory_is_reset_by_window_items() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+
+        let set_slot = |window: i8, slot: i16| {
+            let mut buf = ByteBuf::new();
+            buf.write_i8(window).unwrap();
+            buf.write_i16::<BigEndian>(slot).unwrap();
+            buf.write_i16::<BigEndian>(-1).unwrap();
+            buf
+        };
+        cache
+            .update(PacketID::SetSlot, set_slot(0, 36).as_slice())
+            .unwrap();
+        cache
+            .update(PacketID::SetSlot, set_slot(0, 36).as_slice())
+            .unwrap();
+        // Other windows close when the client attaches, so they are not kept
+        cache
+            .update(PacketID::SetSlot, set_slot(2, 3).as_slice())
+            .unwrap();
+        assert_eq!(
+            vec![PacketID::JoinGame, PacketID::SetSlot],
+            kinds(&replayed(&cache))
+        );
+
+        let mut window_items = ByteBuf::new();
+        window_items.write_u8(0).unwrap();
+        window_items.write_i16::<BigEndian>(0).unwrap();
+        cache
+            .update(PacketID::WindowItems, window_items.as_slice())
+            .unwrap();
+        assert_eq!(
+            vec![PacketID::JoinGame, PacketID::WindowItems],
+            kinds(&replayed(&cache))
+        );
+    }
+
+    #[test]
+    fn player_is_put_where_it_is_now() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        // Where the server last teleported us is stale once we've walked off
+        let stale = PlayerPositionAndLook {
+            teleport_id: 1,
+            ..PlayerPositionAndLook::default()
+        };
+        cache
+            .update(PacketID::PlayerPositionAndLookCB, &stale.body(&cache.ver))
+            .unwrap();
+        assert_eq!(vec![PacketID::JoinGame], kinds(&replayed(&cache)));
+
+        let player = PlayerState {
+            x: 12.5,
+            y: 70.0,
+            z: -3.5,
+            yaw: 90.0,
+            teleport_id: 4,
+            ..PlayerState::default()
+        };
+        let replay = cache.replay(
+            Some(&player),
+            &Entities::new(cache.ver),
+            &TabList::new(cache.ver),
+        );
+        let mut data = ByteBuf::from(replay[1].1.as_slice());
+        let teleport = deserialize_new::<PlayerPositionAndLook>(&mut data, &cache.ver).unwrap();
+        let mut client = PlayerState::default();
+        client.teleport(&teleport);
+        assert_eq!(player, client);
+    }
+
+    #[test]
+    fn tab_list_is_replayed_as_one_add() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        let mut tab_list = TabList::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        for (uuid, name) in [(1, "Steve"), (2, "Alex"), (3, "Herobrine")] {
+            let add = PlayerListItem {
+                actions: ADD_PLAYER,
+                players: vec![PlayerListEntry {
+                    uuid,
+                    name: name.to_string(),
+                    ..PlayerListEntry::default()
+                }],
+            };
+            tab_list
+                .update(PacketID::PlayerListItem, &add.body(&cache.ver))
+                .unwrap();
+        }
+        let remove = PlayerListItem {
+            actions: REMOVE_PLAYER,
+            players: vec![PlayerListEntry {
+                uuid: 3,
+                ..PlayerListEntry::default()
+            }],
+        };
+        tab_list
+            .update(PacketID::PlayerListItem, &remove.body(&cache.ver))
+            .unwrap();
+
+        let replay = cache.replay(None, &Entities::new(cache.ver), &tab_list);
+        assert_eq!(
+            vec![PacketID::JoinGame, PacketID::PlayerListItem],
+            kinds(&replay)
+        );
+        let mut data = ByteBuf::from(replay[1].1.as_slice());
+        let item = deserialize_new::<PlayerListItem>(&mut data, &cache.ver).unwrap();
+        let names: Vec<_> = item.players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(vec!["Steve", "Alex"], names);
+    }
+
+    #[test]
+    fn respawn_clears_the_world() {
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        cache
+            .update(PacketID::JoinGame, join_game().as_slice())
+            .unwrap();
+        cache.update(PacketID::ChunkData, &chunk(0, 0)).unwrap();
+        cache
+            .update(PacketID::SpawnPlayer, &spawn_player(7))
+            .unwrap();
+        cache.update(PacketID::Respawn, &[0xFF_u8; 10]).unwrap();
+
+        assert_eq!(0, cache.chunk_count());
+        assert_eq!(0, cache.entity_count());
+        assert_eq!(
+            vec![PacketID::JoinGame, PacketID::Respawn],
+            kinds(&replayed(&cache))
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use serde_json::Value;
+
+// The 16 chat colours, or any colour since 1.16
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Color {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+    Rgb(u8, u8, u8),
+}
+
+use Color::*;
+
+// In the order of their legacy § codes, 0 to f
+const NAMED: [(&str, Color); 16] = [
+    ("black", Black),
+    ("dark_blue", DarkBlue),
+    ("dark_green", DarkGreen),
+    ("dark_aqua", DarkAqua),
+    ("dark_red", DarkRed),
+    ("dark_purple", DarkPurple),
+    ("gold", Gold),
+    ("gray", Gray),
+    ("dark_gray", DarkGray),
+    ("blue", Blue),
+    ("green", Green),
+    ("aqua", Aqua),
+    ("red", Red),
+    ("light_purple", LightPurple),
+    ("yellow", Yellow),
+    ("white", White),
+];
+
+impl Color {
+    // A colour name or "#RRGGBB", None for "reset" and anything unknown
+    pub fn parse(name: &str) -> Option<Color> {
+        if let Some(hex) = name.strip_prefix('#') {
+            let rgb = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)?;
+            return Some(Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+        }
+        NAMED.iter().find(|(n, _)| *n == name).map(|(_, c)| *c)
+    }
+
+    // The SGR parameters selecting this colour as the foreground
+    fn ansi(&self) -> String {
+        match self {
+            Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+            named => {
+                let code = NAMED.iter().position(|(_, c)| c == named).unwrap();
+                // Vanilla's palette maps onto the 8 normal and 8 bright colours
+                const SGR: [u8; 16] = [
+                    30, 34, 32, 36, 31, 35, 33, 37, 90, 94, 92, 96, 91, 95, 93, 97,
+                ];
+                SGR[code].to_string()
+            }
+        }
+    }
+}
+
+// Unset fields are inherited from the parent component
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+}
+
+impl Style {
+    // `self` with anything it doesn't set taken from `parent`
+    fn inherit(&self, parent: &Style) -> Style {
+        Style {
+            color: self.color.or(parent.color),
+            bold: self.bold.or(parent.bold),
+            italic: self.italic.or(parent.italic),
+            underlined: self.underlined.or(parent.underlined),
+            strikethrough: self.strikethrough.or(parent.strikethrough),
+            obfuscated: self.obfuscated.or(parent.obfuscated),
+        }
+    }
+
+    fn ansi(&self) -> String {
+        let mut codes = vec!["0".to_string()];
+        if let Some(color) = self.color {
+            codes.push(color.ansi());
+        }
+        let flags = [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underlined, "4"),
+            (self.obfuscated, "8"),
+            (self.strikethrough, "9"),
+        ];
+        for (flag, code) in flags {
+            if flag == Some(true) {
+                codes.push(code.to_string());
+            }
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+}
+
+// e.g. open_url with the URL, run_command with the command
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClickEvent {
+    pub action: String,
+    pub value: String,
+}
+
+// show_text has a component, show_item and show_entity their SNBT as text
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoverEvent {
+    pub action: String,
+    pub contents: Box<Component>,
+}
+
+// A JSON chat component, see https://wiki.vg/Chat
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Component {
+    pub text: String,
+    pub translate: Option<String>,
+    pub with: Vec<Component>, // Arguments to `translate`
+    pub extra: Vec<Component>,
+    pub style: Style,
+    pub click_event: Option<ClickEvent>,
+    pub hover_event: Option<HoverEvent>,
+}
+
+impl Component {
+    pub fn text(text: &str) -> Component {
+        Component {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Component, serde_json::Error> {
+        Ok(Component::from_value(&serde_json::from_str(json)?))
+    }
+
+    // Never fails, parts that aren't components are read as their text
+    pub fn from_value(value: &Value) -> Component {
+        match value {
+            Value::String(text) => Component::text(text),
+            // The first element is the parent of the rest
+            Value::Array(parts) => match parts.split_first() {
+                Some((first, rest)) => {
+                    let mut component = Component::from_value(first);
+                    component
+                        .extra
+                        .extend(rest.iter().map(Component::from_value));
+                    component
+                }
+                None => Component::default(),
+            },
+            Value::Object(fields) => {
+                let string = |key| fields.get(key).and_then(Value::as_str);
+                let flag = |key| fields.get(key).and_then(Value::as_bool);
+                let list = |key| match fields.get(key) {
+                    Some(Value::Array(parts)) => parts.iter().map(Component::from_value).collect(),
+                    _ => Vec::new(),
+                };
+
+                let click_event = fields.get("clickEvent").and_then(|event| {
+                    Some(ClickEvent {
+                        action: event.get("action")?.as_str()?.to_string(),
+                        value: event.get("value")?.as_str()?.to_string(),
+                    })
+                });
+                // "contents" replaced "value" in 1.16
+                let hover_event = fields.get("hoverEvent").and_then(|event| {
+                    Some(HoverEvent {
+                        action: event.get("action")?.as_str()?.to_string(),
+                        contents: Box::new(Component::from_value(
+                            event.get("contents").or_else(|| event.get("value"))?,
+                        )),
+                    })
+                });
+
+                Component {
+                    text: string("text").unwrap_or_default().to_string(),
+                    translate: string("translate").map(str::to_string),
+                    with: list("with"),
+                    extra: list("extra"),
+                    style: Style {
+                        color: string("color").and_then(Color::parse),
+                        bold: flag("bold"),
+                        italic: flag("italic"),
+                        underlined: flag("underlined"),
+                        strikethrough: flag("strikethrough"),
+                        obfuscated: flag("obfuscated"),
+                    },
+                    click_event,
+                    hover_event,
+                }
+            }
+            Value::Null => Component::default(),
+            other => Component::text(&other.to_string()),
+        }
+    }
+
+    // The text without any formatting, like a log file
+    pub fn to_plain(&self) -> String {
+        let mut out = String::new();
+        self.render(&Style::default(), false, &mut out);
+        out
+    }
+
+    // The text with ANSI escapes for the colours and styles
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        self.render(&Style::default(), true, &mut out);
+        if out.contains('\x1b') {
+            out.push_str("\x1b[0m");
+        }
+        out
+    }
+
+    fn render(&self, parent: &Style, ansi: bool, out: &mut String) {
+        let style = self.style.inherit(parent);
+        match &self.translate {
+            Some(key) => {
+                let args: Vec<String> = self
+                    .with
+                    .iter()
+                    .map(|arg| {
+                        let mut out = String::new();
+                        arg.render(&style, ansi, &mut out);
+                        out
+                    })
+                    .collect();
+                // Arguments end with their own style, the rest of the
+                // translation is in ours
+                let text = translate(key, &args, &|text| styled(text, &style, ansi));
+                out.push_str(&text);
+            }
+            None => out.push_str(&styled(&self.text, &style, ansi)),
+        }
+        for child in &self.extra {
+            child.render(&style, ansi, out);
+        }
+    }
+}
+
+// Renders `text` in `style`, following any legacy § codes in it. Older
+// servers and plugins still put them in plain text components.
+fn styled(text: &str, style: &Style, ansi: bool) -> String {
+    let mut out = String::new();
+    if text.is_empty() {
+        return out;
+    }
+    let mut style = style.clone();
+    if ansi {
+        out.push_str(&style.ansi());
+    }
+
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '§' {
+            out.push(c);
+            continue;
+        }
+        let code = match chars.next() {
+            Some(code) => code.to_ascii_lowercase(),
+            None => break,
+        };
+        match code {
+            '0'..='9' | 'a'..='f' => {
+                // A colour resets the other styles, like vanilla
+                let index = code.to_digit(16).unwrap() as usize;
+                style = Style {
+                    color: Some(NAMED[index].1),
+                    ..Default::default()
+                };
+            }
+            'k' => style.obfuscated = Some(true),
+            'l' => style.bold = Some(true),
+            'm' => style.strikethrough = Some(true),
+            'n' => style.underlined = Some(true),
+            'o' => style.italic = Some(true),
+            'r' => style = Style::default(),
+            _ => {}
+        }
+        if ansi {
+            out.push_str(&style.ansi());
+        }
+    }
+    out
+}
+
+// The English text of the translation keys chat is usually sent as, other
+// keys are shown as the key followed by their arguments
+fn translate(key: &str, args: &[String], styled: &dyn Fn(&str) -> String) -> String {
+    let format = match key {
+        "chat.type.text" => "<%s> %s",
+        "chat.type.emote" => "* %s %s",
+        "chat.type.announcement" => "[%s] %s",
+        "chat.type.admin" => "[%s: %s]",
+        "commands.message.display.incoming" => "%s whispers to you: %s",
+        "commands.message.display.outgoing" => "You whisper to %s: %s",
+        "multiplayer.player.joined" => "%s joined the game",
+        "multiplayer.player.left" => "%s left the game",
+        "chat.type.advancement.task" => "%s has made the advancement %s",
+        "death.attack.generic" => "%s died",
+        _ if args.is_empty() => return styled(key),
+        _ => return format!("{}{}", styled(&format!("{} ", key)), args.join(" ")),
+    };
+
+    // %s takes the next argument, %1$s a numbered one
+    let mut out = String::new();
+    let mut next = 0;
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&styled(&rest[..start]));
+        rest = &rest[start + 1..];
+        let index = match rest.find('s') {
+            Some(0) => {
+                next += 1;
+                next - 1
+            }
+            Some(end) => match rest[..end].strip_suffix('$').map(str::parse::<usize>) {
+                Some(Ok(n)) if n > 0 => n - 1,
+                _ => {
+                    out.push_str(&styled("%"));
+                    continue;
+                }
+            },
+            None => {
+                out.push_str(&styled("%"));
+                continue;
+            }
+        };
+        rest = &rest[rest.find('s').unwrap() + 1..];
+        if let Some(arg) = args.get(index) {
+            out.push_str(arg);
+        }
+    }
+    out.push_str(&styled(rest));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::*;
+
+    #[test]
+    fn parse_components() {
+        let json = r##"{
+            "translate": "chat.type.text",
+            "with": [
+                {
+                    "text": "Steve",
+                    "clickEvent": {"action": "suggest_command", "value": "/msg Steve "},
+                    "hoverEvent": {"action": "show_text", "value": {"text": "Steve"}}
+                },
+                "hi"
+            ],
+            "color": "gold",
+            "bold": true,
+            "extra": [{"text": "!", "color": "#ff8000", "bold": false}]
+        }"##;
+        let chat = Component::from_json(json).unwrap();
+        assert_eq!(Some("chat.type.text".to_string()), chat.translate);
+        assert_eq!(2, chat.with.len());
+        assert_eq!(Some(Gold), chat.style.color);
+        assert_eq!(Some(true), chat.style.bold);
+
+        let steve = &chat.with[0];
+        let click = steve.click_event.as_ref().unwrap();
+        assert_eq!(
+            ("suggest_command", "/msg Steve "),
+            (&click.action[..], &click.value[..])
+        );
+        let hover = steve.hover_event.as_ref().unwrap();
+        assert_eq!("show_text", hover.action);
+        assert_eq!(Component::text("Steve"), *hover.contents);
+
+        let extra = &chat.extra[0];
+        assert_eq!(Some(Rgb(0xff, 0x80, 0)), extra.style.color);
+        assert_eq!(Some(false), extra.style.bold);
+
+        // Strings and arrays are components too
+        assert_eq!(
+            Component::text("hi"),
+            Component::from_json("\"hi\"").unwrap()
+        );
+        let list = Component::from_json(r#"["a", {"text": "b"}, 3]"#).unwrap();
+        assert_eq!("ab3", list.to_plain());
+        assert!(Component::from_json("{").is_err());
+    }
+
+    #[test]
+    fn render_plain() {
+        let chat = Component::from_json(
+            r#"{"translate": "chat.type.text", "with": ["Steve", {"text": "hello", "extra": [" world"]}]}"#,
+        )
+        .unwrap();
+        assert_eq!("<Steve> hello world", chat.to_plain());
+
+        let numbered = Component::from_json(
+            r#"{"translate": "chat.type.announcement", "with": ["Server", "restarting"]}"#,
+        )
+        .unwrap();
+        assert_eq!("[Server] restarting", numbered.to_plain());
+
+        // Unknown keys keep their arguments
+        let unknown =
+            Component::from_json(r#"{"translate": "death.attack.lava", "with": ["Steve"]}"#)
+                .unwrap();
+        assert_eq!("death.attack.lava Steve", unknown.to_plain());
+
+        // Legacy codes are dropped
+        assert_eq!(
+            "Welcome back!",
+            Component::text("§aWelcome §lback§r!").to_plain()
+        );
+    }
+
+    #[test]
+    fn render_ansi() {
+        let chat = Component::from_json(
+            r##"{"text": "a", "color": "red", "extra": [{"text": "b", "bold": true}, {"text": "c", "color": "#010203"}]}"##,
+        )
+        .unwrap();
+        assert_eq!(
+            "\x1b[0;91ma\x1b[0;91;1mb\x1b[0;38;2;1;2;3mc\x1b[0m",
+            chat.to_ansi()
+        );
+
+        assert_eq!(
+            "\x1b[0mx\x1b[0;92my\x1b[0m",
+            Component::text("x§ay").to_ansi()
+        );
+        // Nothing to reset without any text
+        assert_eq!("", Component::default().to_ansi());
+    }
+}
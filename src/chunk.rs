@@ -0,0 +1,623 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::bytes::WriteBytes;
+use crate::serialize::packet::clientbound::{BlockChange, ChunkData, MultiBlockChange};
+use crate::serialize::var::{DeserializeError, VarIntReader, VarIntWriter};
+use byteorder::WriteBytesExt;
+use std::convert::TryInto;
+
+pub const SECTIONS: usize = 16;
+const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+const LIGHT_BYTES: usize = BLOCKS_PER_SECTION / 2; // Half a byte per block
+const BIOME_BYTES: usize = 16 * 16;
+
+// Fewer bits than this are padded to it, more than MAX_PALETTE_BITS means
+// the section stores global states instead of palette indices
+const MIN_PALETTE_BITS: u8 = 4;
+const MAX_PALETTE_BITS: u8 = 8;
+const GLOBAL_BITS: u8 = 13;
+
+// A 16x16x16 cube of blocks in the 1.12.2 Chunk Data format
+#[derive(Clone, Debug, PartialEq)]
+struct ChunkSection {
+    bits_per_block: u8,
+    palette: Vec<i32>, // Empty if states are global
+    data: Vec<u64>,    // Values can span two longs
+    block_light: Vec<u8>,
+    sky_light: Option<Vec<u8>>, // Only in dimensions with a sky
+}
+
+// Blocks are ordered by y, then z, then x
+fn block_index(x: usize, y: usize, z: usize) -> usize {
+    (y << 8) | (z << 4) | x
+}
+
+// The section and index of a block in the column, None if it's outside
+fn locate(x: usize, y: usize, z: usize) -> Option<(usize, usize)> {
+    if x < 16 && y < SECTIONS * 16 && z < 16 {
+        Some((y >> 4, block_index(x, y & 0xF, z)))
+    } else {
+        None
+    }
+}
+
+fn data_len(bits_per_block: u8) -> usize {
+    BLOCKS_PER_SECTION * bits_per_block as usize / 64
+}
+
+impl ChunkSection {
+    // All air, as vanilla creates them
+    fn empty(sky_light: bool) -> ChunkSection {
+        ChunkSection {
+            bits_per_block: MIN_PALETTE_BITS,
+            palette: vec![0],
+            data: vec![0; data_len(MIN_PALETTE_BITS)],
+            block_light: vec![0; LIGHT_BYTES],
+            sky_light: if sky_light {
+                Some(vec![0xFF; LIGHT_BYTES])
+            } else {
+                None
+            },
+        }
+    }
+
+    fn read(buf: &mut ByteBuf, sky_light: bool) -> Result<ChunkSection, DeserializeError> {
+        let bits_per_block = buf.read_byte().ok_or(DeserializeError::BufferTooSmall)?;
+        if bits_per_block == 0 || bits_per_block > 32 {
+            return Err(DeserializeError::InvalidLength);
+        }
+
+        let palette_len = buf.read_var_int()?;
+        if palette_len < 0 {
+            return Err(DeserializeError::InvalidLength);
+        }
+        let palette = (0..palette_len)
+            .map(|_| buf.read_var_int())
+            .collect::<Result<Vec<i32>, DeserializeError>>()?;
+
+        let len = buf.read_var_int()?;
+        if len < 0 || len as usize != data_len(bits_per_block) {
+            return Err(DeserializeError::InvalidLength);
+        }
+        let data = buf
+            .read_bytes(len as usize * 8)
+            .ok_or(DeserializeError::BufferTooSmall)?
+            .chunks_exact(8)
+            .map(|long| u64::from_be_bytes(long.try_into().unwrap()))
+            .collect();
+
+        let block_light = buf
+            .read_bytes(LIGHT_BYTES)
+            .ok_or(DeserializeError::BufferTooSmall)?;
+        let sky_light = if sky_light {
+            Some(
+                buf.read_bytes(LIGHT_BYTES)
+                    .ok_or(DeserializeError::BufferTooSmall)?,
+            )
+        } else {
+            None
+        };
+
+        Ok(ChunkSection {
+            bits_per_block,
+            palette,
+            data,
+            block_light,
+            sky_light,
+        })
+    }
+
+    fn write(&self, buf: &mut ByteBuf) {
+        buf.write_u8(self.bits_per_block).unwrap();
+        buf.write_var_int(self.palette.len() as i32);
+        for state in &self.palette {
+            buf.write_var_int(*state);
+        }
+        buf.write_var_int(self.data.len() as i32);
+        for long in &self.data {
+            buf.write_bytes(&long.to_be_bytes());
+        }
+        buf.write_bytes(&self.block_light);
+        if let Some(sky_light) = self.sky_light.as_ref() {
+            buf.write_bytes(sky_light);
+        }
+    }
+
+    fn global(&self) -> bool {
+        self.bits_per_block > MAX_PALETTE_BITS
+    }
+
+    fn get_raw(&self, i: usize) -> u64 {
+        let bits = self.bits_per_block as usize;
+        let mask = (1_u64 << bits) - 1;
+        let (long, offset) = (i * bits / 64, i * bits % 64);
+
+        let mut value = self.data[long] >> offset;
+        if offset + bits > 64 {
+            value |= self.data[long + 1] << (64 - offset);
+        }
+        value & mask
+    }
+
+    fn set_raw(&mut self, i: usize, value: u64) {
+        let bits = self.bits_per_block as usize;
+        let mask = (1_u64 << bits) - 1;
+        let value = value & mask;
+        let (long, offset) = (i * bits / 64, i * bits % 64);
+
+        self.data[long] = (self.data[long] & !(mask << offset)) | (value << offset);
+        if offset + bits > 64 {
+            let written = 64 - offset;
+            self.data[long + 1] = (self.data[long + 1] & !(mask >> written)) | (value >> written);
+        }
+    }
+
+    fn get(&self, i: usize) -> i32 {
+        let value = self.get_raw(i);
+        if self.global() {
+            value as i32
+        } else {
+            // Out of range indices are air, like the vanilla client does
+            self.palette.get(value as usize).copied().unwrap_or(0)
+        }
+    }
+
+    fn set(&mut self, i: usize, state: i32) {
+        if self.global() {
+            self.set_raw(i, state as u64);
+            return;
+        }
+
+        let value = match self.palette.iter().position(|s| *s == state) {
+            Some(value) => value,
+            None if self.palette.len() < 1 << self.bits_per_block => {
+                self.palette.push(state);
+                self.palette.len() - 1
+            }
+            None => {
+                self.resize(self.bits_per_block + 1);
+                return self.set(i, state);
+            }
+        };
+        self.set_raw(i, value as u64);
+    }
+
+    // Repacks every block with more bits, switching to global states once
+    // the palette would get too large
+    fn resize(&mut self, bits_per_block: u8) {
+        let raw: Vec<u64> = (0..BLOCKS_PER_SECTION).map(|i| self.get_raw(i)).collect();
+        let states: Vec<i32> = (0..BLOCKS_PER_SECTION).map(|i| self.get(i)).collect();
+
+        let global = bits_per_block > MAX_PALETTE_BITS;
+        self.bits_per_block = if global { GLOBAL_BITS } else { bits_per_block };
+        self.data = vec![0; data_len(self.bits_per_block)];
+
+        if global {
+            self.palette.clear();
+            for (i, state) in states.into_iter().enumerate() {
+                self.set_raw(i, state as u64);
+            }
+        } else {
+            for (i, value) in raw.into_iter().enumerate() {
+                self.set_raw(i, value);
+            }
+        }
+    }
+}
+
+// A 16x256x16 column of blocks built from Chunk Data. Sections that haven't
+// been changed are written back exactly as they were read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkColumn {
+    pub x: i32,
+    pub z: i32,
+    sections: Vec<Option<ChunkSection>>, // Bottom up, None is all air
+    biomes: Option<Vec<u8>>,             // Only sent with full columns
+    sky_light: bool,
+    block_entity_count: i32,
+    block_entities: Vec<u8>,
+}
+
+impl ChunkColumn {
+    // Whether sections carry sky light isn't in the packet, it depends on the
+    // dimension (only the overworld has it)
+    pub fn decode(packet: &ChunkData, sky_light: bool) -> Result<ChunkColumn, DeserializeError> {
+        let mut column = ChunkColumn {
+            x: packet.x,
+            z: packet.z,
+            sections: vec![None; SECTIONS],
+            biomes: None,
+            sky_light,
+            block_entity_count: 0,
+            block_entities: Vec::new(),
+        };
+        column.update(packet)?;
+        Ok(column)
+    }
+
+    // Applies another Chunk Data for this column. One that isn't full only
+    // replaces the sections it has.
+    pub fn update(&mut self, packet: &ChunkData) -> Result<(), DeserializeError> {
+        let mut buf = ByteBuf::from(&packet.data);
+        let mut sections = self.sections.clone();
+        for (y, section) in sections.iter_mut().enumerate() {
+            if packet.bit_mask & (1 << y) != 0 {
+                *section = Some(ChunkSection::read(&mut buf, self.sky_light)?);
+            } else if packet.full {
+                *section = None;
+            }
+        }
+
+        let biomes = if packet.full {
+            Some(
+                buf.read_bytes(BIOME_BYTES)
+                    .ok_or(DeserializeError::BufferTooSmall)?,
+            )
+        } else {
+            self.biomes.take()
+        };
+        if !buf.end() {
+            return Err(DeserializeError::InvalidLength);
+        }
+
+        self.sections = sections;
+        self.biomes = biomes;
+        if packet.full {
+            self.block_entity_count = packet.block_entity_count;
+            self.block_entities = packet.block_entities.clone();
+        } else {
+            self.block_entity_count += packet.block_entity_count;
+            self.block_entities
+                .extend_from_slice(&packet.block_entities);
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> ChunkData {
+        let mut buf = ByteBuf::new();
+        let mut bit_mask = 0;
+        for (y, section) in self.sections.iter().enumerate() {
+            if let Some(section) = section {
+                section.write(&mut buf);
+                bit_mask |= 1 << y;
+            }
+        }
+        if let Some(biomes) = self.biomes.as_ref() {
+            buf.write_bytes(biomes);
+        }
+
+        ChunkData {
+            x: self.x,
+            z: self.z,
+            full: self.biomes.is_some(),
+            bit_mask,
+            data: buf.as_slice().to_vec(),
+            block_entity_count: self.block_entity_count,
+            block_entities: self.block_entities.clone(),
+        }
+    }
+
+    // Coordinates are relative to the column, x and z are 0-15, y is 0-255.
+    // None outside the column.
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> Option<i32> {
+        let (y, i) = locate(x, y, z)?;
+        Some(
+            self.sections[y]
+                .as_ref()
+                .map_or(0, |section| section.get(i)),
+        )
+    }
+
+    // Fails with `InvalidValue` outside the column, changing nothing
+    pub fn set_block(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        state: i32,
+    ) -> Result<(), DeserializeError> {
+        let (y, i) = locate(x, y, z).ok_or(DeserializeError::InvalidValue)?;
+        let sky_light = self.sky_light;
+        let section = &mut self.sections[y];
+        if section.is_none() {
+            if state == 0 {
+                return Ok(());
+            }
+            *section = Some(ChunkSection::empty(sky_light));
+        }
+        section.as_mut().unwrap().set(i, state);
+        Ok(())
+    }
+
+    // Changes outside this column fail with `InvalidValue`
+    pub fn apply_block_change(&mut self, change: &BlockChange) -> Result<(), DeserializeError> {
+        let pos = &change.location;
+        if pos.chunk() != (self.x, self.z) || pos.y < 0 {
+            return Err(DeserializeError::InvalidValue);
+        }
+        self.set_block(
+            (pos.x & 0xF) as usize,
+            pos.y as usize,
+            (pos.z & 0xF) as usize,
+            change.block_state,
+        )
+    }
+
+    // Nothing is changed if any record is outside this column
+    pub fn apply_multi_block_change(
+        &mut self,
+        change: &MultiBlockChange,
+    ) -> Result<(), DeserializeError> {
+        let inside = (change.chunk_x, change.chunk_z) == (self.x, self.z)
+            && change.records.iter().all(|record| {
+                locate(record.x as usize, record.y as usize, record.z as usize).is_some()
+            });
+        if !inside {
+            return Err(DeserializeError::InvalidValue);
+        }
+        for record in &change.records {
+            self.set_block(
+                record.x as usize,
+                record.y as usize,
+                record.z as usize,
+                record.block_state,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::*;
+    use crate::serialize::packet::clientbound::BlockChangeRecord;
+    use crate::serialize::packet::{deserialize_new, PacketSerializer};
+    use crate::serialize::position::Position;
+    use crate::serialize::protocol::ProtocolVersion;
+
+    // Deterministic filler so sections aren't all zeroes
+    fn noise(seed: u64, n: usize) -> Vec<u64> {
+        let mut x = seed;
+        (0..n)
+            .map(|_| {
+                x = x
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                x
+            })
+            .collect()
+    }
+
+    fn write_section(buf: &mut ByteBuf, bits: u8, palette: &[i32], seed: u64, sky_light: bool) {
+        buf.write_u8(bits).unwrap();
+        buf.write_var_int(palette.len() as i32);
+        for state in palette {
+            buf.write_var_int(*state);
+        }
+        let data = noise(seed, data_len(bits));
+        buf.write_var_int(data.len() as i32);
+        for long in data {
+            buf.write_bytes(&long.to_be_bytes());
+        }
+        buf.write_bytes(&[seed as u8; LIGHT_BYTES]);
+        if sky_light {
+            buf.write_bytes(&[0xF0; LIGHT_BYTES]);
+        }
+    }
+
+    // Sections 0, 3 and 4 with 4 bit, 5 bit and global states
+    fn chunk_data(sky_light: bool) -> ChunkData {
+        let mut buf = ByteBuf::new();
+        let palette: Vec<i32> = (0..32).map(|i| i << 4).collect();
+        write_section(&mut buf, 4, &palette[..16], 1, sky_light);
+        write_section(&mut buf, 5, &palette, 2, sky_light);
+        write_section(&mut buf, 13, &[], 3, sky_light);
+        buf.write_bytes(&[1; BIOME_BYTES]);
+
+        ChunkData {
+            x: -3,
+            z: 7,
+            full: true,
+            bit_mask: 0b11001,
+            data: buf.as_slice().to_vec(),
+            block_entity_count: 1,
+            block_entities: vec![10, 0, 0, 0], // Empty compound, then TAG_End
+        }
+    }
+
+    fn wire(packet: &ChunkData) -> Vec<u8> {
+        let mut buf = ByteBuf::new();
+        packet.serialize(&mut buf, &ProtocolVersion::V_1_12_2);
+        buf.as_slice().to_vec()
+    }
+
+    #[test]
+    fn unmodified_column_round_trips() {
+        for sky_light in [true, false] {
+            let packet = chunk_data(sky_light);
+            let mut buf = ByteBuf::from(wire(&packet).as_slice());
+            let read = deserialize_new::<ChunkData>(&mut buf, &ProtocolVersion::V_1_12_2).unwrap();
+            assert!(buf.end());
+
+            let column = ChunkColumn::decode(&read, sky_light).unwrap();
+            assert_eq!(wire(&packet), wire(&column.encode()));
+        }
+    }
+
+    #[test]
+    fn reads_palette_and_global_states() {
+        let mut column = ChunkColumn::decode(&chunk_data(true), true).unwrap();
+
+        // The first long of section 0 holds blocks 0-15, one nibble each
+        let first = noise(1, 1)[0];
+        assert_eq!(Some((first & 0xF) as i32 * 16), column.get_block(0, 0, 0));
+        assert_eq!(
+            Some((first >> 4 & 0xF) as i32 * 16),
+            column.get_block(1, 0, 0)
+        );
+
+        // Block 12 of a 5 bit section spans the first two longs
+        let longs = noise(2, 2);
+        let value = (longs[0] >> 60 | longs[1] << 4) & 0x1F;
+        assert_eq!(Some(value as i32 * 16), column.get_block(12, 48, 0));
+
+        let value = noise(3, 1)[0] & 0x1FFF;
+        assert_eq!(Some(value as i32), column.get_block(0, 64, 0));
+
+        // Sections that weren't sent are air
+        assert_eq!(Some(0), column.get_block(5, 20, 5));
+
+        column.set_block(12, 48, 0, 31 << 4).unwrap();
+        assert_eq!(Some(31 << 4), column.get_block(12, 48, 0));
+        assert_eq!(
+            Some((longs[0] & 0x1F) as i32 * 16),
+            column.get_block(0, 48, 0)
+        );
+        assert_eq!(
+            Some((longs[1] >> 1 & 0x1F) as i32 * 16),
+            column.get_block(13, 48, 0)
+        );
+    }
+
+    #[test]
+    fn palette_grows_then_turns_global() {
+        let mut column = ChunkColumn::decode(&ChunkData::default(), true).unwrap();
+        assert_eq!(0, column.encode().bit_mask);
+
+        // 300 different states need 5 bits, then more than a palette allows
+        for i in 0..300 {
+            column
+                .set_block(i % 16, 200 + i / 256, i / 16 % 16, (i as i32 + 1) << 4)
+                .unwrap();
+            if i == 16 {
+                assert_eq!(5, column.sections[12].as_ref().unwrap().bits_per_block);
+            }
+        }
+        let section = column.sections[12].as_ref().unwrap();
+        assert_eq!(GLOBAL_BITS, section.bits_per_block);
+        assert!(section.palette.is_empty());
+
+        for i in 0..300 {
+            assert_eq!(
+                Some((i as i32 + 1) << 4),
+                column.get_block(i % 16, 200 + i / 256, i / 16 % 16)
+            );
+        }
+        assert_eq!(Some(0), column.get_block(0, 202, 0));
+        assert_eq!(1 << 12, column.encode().bit_mask);
+
+        // What we write reads back the same
+        let decoded = ChunkColumn::decode(&column.encode(), true).unwrap();
+        assert_eq!(column, decoded);
+    }
+
+    #[test]
+    fn partial_update_replaces_sections() {
+        let mut column = ChunkColumn::decode(&chunk_data(true), true).unwrap();
+
+        let mut buf = ByteBuf::new();
+        write_section(&mut buf, 4, &[0, 16], 9, true);
+        let partial = ChunkData {
+            x: -3,
+            z: 7,
+            full: false,
+            bit_mask: 0b1,
+            data: buf.as_slice().to_vec(),
+            block_entity_count: 0,
+            block_entities: Vec::new(),
+        };
+        column.update(&partial).unwrap();
+
+        let encoded = column.encode();
+        assert!(encoded.full);
+        assert_eq!(0b11001, encoded.bit_mask);
+        let value = noise(9, 1)[0] & 0x1;
+        assert_eq!(Some(value as i32 * 16), column.get_block(0, 0, 0));
+    }
+
+    #[test]
+    fn applies_block_changes() {
+        let mut column = ChunkColumn::decode(&chunk_data(false), false).unwrap();
+
+        column
+            .apply_block_change(&BlockChange {
+                location: Position {
+                    x: -45,
+                    y: 70,
+                    z: 120,
+                },
+                block_state: 1 << 4,
+            })
+            .unwrap();
+        assert_eq!(Some(1 << 4), column.get_block(3, 70, 8));
+
+        let mut change = MultiBlockChange {
+            chunk_x: -3,
+            chunk_z: 7,
+            records: vec![
+                BlockChangeRecord {
+                    x: 15,
+                    y: 255,
+                    z: 15,
+                    block_state: 2 << 4,
+                },
+                BlockChangeRecord {
+                    x: 0,
+                    y: 0,
+                    z: 0,
+                    block_state: 0,
+                },
+            ],
+        };
+        column.apply_multi_block_change(&change).unwrap();
+        assert_eq!(Some(2 << 4), column.get_block(15, 255, 15));
+        assert_eq!(Some(0), column.get_block(0, 0, 0));
+
+        // Anything outside the column is an error and changes nothing
+        let unchanged = column.clone();
+        for y in [-1, 256, 2047] {
+            let change = BlockChange {
+                location: Position { x: -45, y, z: 120 },
+                block_state: 1 << 4,
+            };
+            assert_eq!(
+                DeserializeError::InvalidValue,
+                column.apply_block_change(&change).unwrap_err()
+            );
+        }
+        let elsewhere = BlockChange {
+            location: Position { x: 0, y: 70, z: 0 },
+            block_state: 1 << 4,
+        };
+        assert!(column.apply_block_change(&elsewhere).is_err());
+        change.records[1].x = 16;
+        change.records[1].block_state = 3 << 4;
+        assert!(column.apply_multi_block_change(&change).is_err());
+        change.records[1].x = 0;
+        change.chunk_x = 0;
+        assert!(column.apply_multi_block_change(&change).is_err());
+        assert!(column.set_block(0, 256, 0, 1 << 4).is_err());
+        assert!(column.set_block(0, 0, 16, 1 << 4).is_err());
+        assert_eq!(None, column.get_block(16, 0, 0));
+        assert_eq!(unchanged, column);
+    }
+
+    #[test]
+    fn rejects_malformed_data() {
+        let mut packet = chunk_data(true);
+        packet.data.push(0);
+        assert_eq!(
+            DeserializeError::InvalidLength,
+            ChunkColumn::decode(&packet, true).unwrap_err()
+        );
+
+        // Reading without sky light runs past the sections into the biomes
+        assert!(ChunkColumn::decode(&chunk_data(true), false).is_err());
+
+        let mut packet = chunk_data(true);
+        packet.data.truncate(100);
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            ChunkColumn::decode(&packet, true).unwrap_err()
+        );
+    }
+}
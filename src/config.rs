@@ -0,0 +1,592 @@
+use crate::afk::{Action, Task};
+use crate::auth::{Profile, MOJANG_SESSION_SERVER};
+use crate::health::MAX_HEALTH;
+use crate::safety::SafetyPolicy;
+use crate::serialize::packet::serverbound::ChatMessage;
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::uuid;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(String),           // Not TOML, or not the fields we expect
+    Invalid(String, String), // Which setting and what's wrong with it
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "Could not read {}: {}", path.display(), e),
+            ConfigError::Parse(e) => write!(f, "Bad config: {}", e),
+            ConfigError::Invalid(setting, e) => write!(f, "Bad config: {}: {}", setting, e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn invalid<T>(setting: &str, e: String) -> Result<T, ConfigError> {
+    Err(ConfigError::Invalid(setting.to_string(), e))
+}
+
+// The config file as written. Anything left out has a default, and
+// anything we don't know is an error so typos don't go unnoticed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct File {
+    pub server: ServerFile,
+    pub account: AccountFile,
+    pub proxy: ProxyFile,
+    pub log: LogOptions,
+    pub afk: AfkFile,
+    pub safety: SafetyFile,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerFile {
+    pub address: String,
+    pub port: u16,
+    pub protocol: ProtocolFile,
+}
+
+impl Default for ServerFile {
+    fn default() -> Self {
+        ServerFile {
+            address: "localhost".to_string(),
+            port: 25565,
+            protocol: ProtocolFile::Name("auto".to_string()),
+        }
+    }
+}
+
+// "auto", a release like "1.12.2" or a protocol number like 340
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ProtocolFile {
+    Number(i32),
+    Name(String),
+}
+
+// Offline mode unless both `uuid` and `access_token` are given
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountFile {
+    pub username: String,
+    pub uuid: Option<String>,
+    pub access_token: Option<String>,
+    pub session_server: String,
+}
+
+impl Default for AccountFile {
+    fn default() -> Self {
+        AccountFile {
+            username: "test".to_string(),
+            uuid: None,
+            access_token: None,
+            session_server: MOJANG_SESSION_SERVER.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyFile {
+    pub listen: String,
+    pub compression_threshold: Option<i32>, // Left out to not compress
+}
+
+impl Default for ProxyFile {
+    fn default() -> Self {
+        ProxyFile {
+            listen: "127.0.0.1:25566".to_string(),
+            compression_threshold: None,
+        }
+    }
+}
+
+// What gets printed
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogOptions {
+    pub packets: bool, // Every packet read, keep alives and so on
+    pub chat: bool,
+    pub players: bool,      // Players joining and leaving
+    pub watch: Vec<String>, // Players to alert on when they come online
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            packets: false,
+            chat: true,
+            players: true,
+            watch: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AfkFile {
+    pub enabled: bool,
+    pub tasks: Option<Vec<TaskFile>>, // Left out for the defaults
+}
+
+impl Default for AfkFile {
+    fn default() -> Self {
+        AfkFile {
+            enabled: true,
+            tasks: None,
+        }
+    }
+}
+
+// Seconds between runs, picked at random from `min` to `max`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskFile {
+    pub action: String,
+    pub min: u64,
+    pub max: u64,
+    pub message: Option<String>, // Only for chat
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafetyFile {
+    pub min_health: Option<f32>,
+    pub player_range: Option<f64>,
+    pub whitelist: Vec<String>, // UUIDs
+}
+
+// Everything the binary needs, checked and with the defaults filled in
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub protocol: Option<ProtocolVersion>, // None to use whatever the server says
+    pub username: String,
+    pub uuid: Option<u128>,
+    pub profile: Option<Profile>, // Online mode
+    pub session_server: String,
+    pub listen: SocketAddr,
+    pub compression: Option<i32>,
+    pub log: LogOptions,
+    pub afk: Vec<Task>,
+    pub safety: SafetyPolicy,
+}
+
+impl Config {
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+// Run while nobody's attached unless the config says otherwise
+pub fn default_tasks() -> Vec<Task> {
+    let task = |action, min, max| Task {
+        action,
+        min: Duration::from_secs(min),
+        max: Duration::from_secs(max),
+    };
+    vec![
+        task(Action::SwingArm, 20, 40),
+        task(Action::RotateHead, 10, 30),
+        task(Action::Jump, 60, 120),
+    ]
+}
+
+fn valid_username(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl File {
+    pub fn parse(text: &str) -> Result<File, ConfigError> {
+        toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    pub fn read(path: &Path) -> Result<File, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        File::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(e) => ConfigError::Parse(format!("{}: {}", path.display(), e)),
+            e => e,
+        })
+    }
+
+    // Checks every setting, the first one that's wrong is the error
+    pub fn validate(self) -> Result<Config, ConfigError> {
+        let server = self.server;
+        if server.address.trim().is_empty() {
+            return invalid("server.address", "can't be empty".to_string());
+        }
+        if server.port == 0 {
+            return invalid("server.port", "0 is not a port".to_string());
+        }
+        let protocol = match &server.protocol {
+            ProtocolFile::Name(name) if name == "auto" => None,
+            ProtocolFile::Name(name) => match name.parse::<i32>() {
+                Ok(number) => Some(protocol_number(number)?),
+                Err(_) => Some(ProtocolVersion::from_name(name).ok_or_else(|| {
+                    ConfigError::Invalid(
+                        "server.protocol".to_string(),
+                        format!("{} is not supported, use {}", name, supported()),
+                    )
+                })?),
+            },
+            ProtocolFile::Number(number) => Some(protocol_number(*number)?),
+        };
+
+        let account = self.account;
+        if !valid_username(&account.username) {
+            return invalid(
+                "account.username",
+                format!(
+                    "{:?} is not a Minecraft name, they are 1 to 16 letters, digits and underscores",
+                    account.username
+                ),
+            );
+        }
+        let uuid = match &account.uuid {
+            Some(id) => Some(uuid::parse(id).ok_or_else(|| {
+                ConfigError::Invalid(
+                    "account.uuid".to_string(),
+                    format!("{:?} is not a UUID", id),
+                )
+            })?),
+            None => None,
+        };
+        let profile = match (uuid, account.access_token) {
+            (Some(uuid), Some(access_token)) => Some(Profile {
+                id: format!("{:032x}", uuid),
+                name: account.username.clone(),
+                access_token,
+            }),
+            (None, Some(_)) => {
+                return invalid(
+                    "account.uuid",
+                    "needed with access_token to log in online".to_string(),
+                )
+            }
+            _ => None,
+        };
+        if !account.session_server.starts_with("http://")
+            && !account.session_server.starts_with("https://")
+        {
+            return invalid(
+                "account.session_server",
+                format!("{:?} is not an http(s) URL", account.session_server),
+            );
+        }
+
+        let proxy = self.proxy;
+        let listen = proxy.listen.parse::<SocketAddr>().map_err(|_| {
+            ConfigError::Invalid(
+                "proxy.listen".to_string(),
+                format!(
+                    "{:?} is not an IP address and port, like 127.0.0.1:25566",
+                    proxy.listen
+                ),
+            )
+        })?;
+        if let Some(threshold) = proxy.compression_threshold {
+            if threshold < 0 {
+                return invalid(
+                    "proxy.compression_threshold",
+                    "can't be negative, leave it out to not compress".to_string(),
+                );
+            }
+        }
+
+        if let Some(name) = self.log.watch.iter().find(|name| !valid_username(name)) {
+            return invalid("log.watch", format!("{:?} is not a Minecraft name", name));
+        }
+
+        let afk = match (self.afk.enabled, self.afk.tasks) {
+            (false, _) => Vec::new(),
+            (true, None) => default_tasks(),
+            (true, Some(tasks)) => tasks
+                .into_iter()
+                .enumerate()
+                .map(|(i, task)| task.validate(&format!("afk.tasks[{}]", i), protocol))
+                .collect::<Result<_, _>>()?,
+        };
+
+        let safety = self.safety;
+        if let Some(min) = safety.min_health {
+            if !(min > 0.0 && min <= MAX_HEALTH) {
+                return invalid(
+                    "safety.min_health",
+                    format!("{} is not above 0 and at most {}", min, MAX_HEALTH),
+                );
+            }
+        }
+        if let Some(range) = safety.player_range {
+            if !(range > 0.0 && range.is_finite()) {
+                return invalid(
+                    "safety.player_range",
+                    format!("{} is not a distance in blocks", range),
+                );
+            }
+        }
+        let whitelist = safety
+            .whitelist
+            .iter()
+            .map(|id| {
+                uuid::parse(id).ok_or_else(|| {
+                    ConfigError::Invalid(
+                        "safety.whitelist".to_string(),
+                        format!("{:?} is not a UUID", id),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Config {
+            host: server.address,
+            port: server.port,
+            protocol,
+            username: account.username,
+            uuid,
+            profile,
+            session_server: account.session_server,
+            listen,
+            compression: proxy.compression_threshold,
+            log: self.log,
+            afk,
+            safety: SafetyPolicy {
+                min_health: safety.min_health,
+                player_range: safety.player_range,
+                whitelist,
+            },
+        })
+    }
+}
+
+impl TaskFile {
+    fn validate(
+        self,
+        setting: &str,
+        protocol: Option<ProtocolVersion>,
+    ) -> Result<Task, ConfigError> {
+        let action = match (self.action.as_str(), self.message) {
+            ("chat", Some(message)) => {
+                // Unless we know better, whatever the newest versions allow
+                let max = protocol
+                    .map_or(ChatMessage::max_length(&ProtocolVersion::V_1_20_1), |ver| {
+                        ChatMessage::max_length(&ver)
+                    });
+                if message.is_empty() || message.chars().count() > max {
+                    return invalid(
+                        setting,
+                        format!("chat messages are 1 to {} characters", max),
+                    );
+                }
+                Action::Chat(message)
+            }
+            ("chat", None) => return invalid(setting, "chat needs a message".to_string()),
+            (_, Some(_)) => return invalid(setting, "only chat has a message".to_string()),
+            ("swing_arm", None) => Action::SwingArm,
+            ("rotate_head", None) => Action::RotateHead,
+            ("jump", None) => Action::Jump,
+            ("sneak", None) => Action::Sneak,
+            (other, None) => {
+                return invalid(
+                    setting,
+                    format!(
+                        "{:?} is not an action, use swing_arm, rotate_head, jump, sneak or chat",
+                        other
+                    ),
+                )
+            }
+        };
+        if self.min == 0 {
+            return invalid(setting, "min must be at least 1 second".to_string());
+        }
+        if self.max < self.min {
+            return invalid(
+                setting,
+                format!("max ({}s) is less than min ({}s)", self.max, self.min),
+            );
+        }
+        Ok(Task {
+            action,
+            min: Duration::from_secs(self.min),
+            max: Duration::from_secs(self.max),
+        })
+    }
+}
+
+fn protocol_number(number: i32) -> Result<ProtocolVersion, ConfigError> {
+    ProtocolVersion::from_protocol(number).ok_or_else(|| {
+        ConfigError::Invalid(
+            "server.protocol".to_string(),
+            format!("{} is not supported, use {}", number, supported()),
+        )
+    })
+}
+
+// e.g. "auto, 1.8.9 (47), ..."
+fn supported() -> String {
+    let versions: Vec<_> = ProtocolVersion::ALL
+        .iter()
+        .map(|ver| format!("{} ({})", ver.name(), *ver as i32))
+        .collect();
+    format!("auto, {}", versions.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+
+    fn error(text: &str) -> String {
+        File::parse(text)
+            .and_then(File::validate)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn defaults_need_no_file() {
+        let config = File::parse("").unwrap().validate().unwrap();
+        assert_eq!("localhost:25565", config.address());
+        assert_eq!(None, config.protocol);
+        assert_eq!("test", config.username);
+        assert!(config.profile.is_none());
+        assert_eq!("127.0.0.1:25566", config.listen.to_string());
+        assert_eq!(None, config.compression);
+        assert_eq!(LogOptions::default(), config.log);
+        assert_eq!(default_tasks().len(), config.afk.len());
+        assert_eq!(None, config.safety.min_health);
+    }
+
+    #[test]
+    fn everything_can_be_set() {
+        let config = File::parse(
+            r#"
+            [server]
+            address = "mc.example.com"
+            port = 25570
+            protocol = "1.16.4"
+
+            [account]
+            username = "Idler_01"
+            uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5"
+            access_token = "token"
+
+            [proxy]
+            listen = "0.0.0.0:25577"
+            compression_threshold = 256
+
+            [log]
+            packets = true
+            watch = ["Notch"]
+
+            [[afk.tasks]]
+            action = "chat"
+            min = 300
+            max = 600
+            message = "/afk"
+
+            [[afk.tasks]]
+            action = "sneak"
+            min = 5
+            max = 5
+
+            [safety]
+            min_health = 8.5
+            player_range = 32
+            whitelist = ["069a79f444e94726a5befca90e38aaf5"]
+            "#,
+        )
+        .unwrap()
+        .validate()
+        .unwrap();
+
+        assert_eq!("mc.example.com:25570", config.address());
+        assert_eq!(Some(ProtocolVersion::V_1_16_5), config.protocol);
+        let profile = config.profile.unwrap();
+        assert_eq!("069a79f444e94726a5befca90e38aaf5", profile.id);
+        assert_eq!("Idler_01", profile.name);
+        assert_eq!(Some(0x069a79f4_44e9_4726_a5be_fca90e38aaf5), config.uuid);
+        assert_eq!(25577, config.listen.port());
+        assert_eq!(Some(256), config.compression);
+        assert!(config.log.packets && config.log.chat);
+        assert_eq!(vec!["Notch"], config.log.watch);
+        assert_eq!(Action::Chat("/afk".to_string()), config.afk[0].action);
+        assert_eq!(Duration::from_secs(600), config.afk[0].max);
+        assert_eq!(Action::Sneak, config.afk[1].action);
+        assert_eq!(Some(8.5), config.safety.min_health);
+        assert_eq!(Some(32.0), config.safety.player_range);
+        assert_eq!(
+            vec![profile.id],
+            config
+                .safety
+                .whitelist
+                .iter()
+                .map(|id| format!("{:032x}", id))
+                .collect::<Vec<_>>()
+        );
+
+        let off = File::parse("server.protocol = 47\nafk.enabled = false")
+            .unwrap()
+            .validate()
+            .unwrap();
+        assert_eq!(Some(ProtocolVersion::V_1_8_9), off.protocol);
+        assert!(off.afk.is_empty());
+    }
+
+    #[test]
+    fn errors_say_what_is_wrong() {
+        assert!(error("[server]\nadress = \"x\"").contains("unknown field `adress`"));
+        assert!(error("server.port = 70000").starts_with("Bad config: "));
+        assert_eq!(
+            "Bad config: server.protocol: 338 is not supported, use auto, 1.8.9 (47), 1.12.2 (340), 1.16.5 (754), 1.20.1 (763)",
+            error("server.protocol = 338")
+        );
+        assert!(error("server.protocol = \"1.19\"").contains("1.19 is not supported"));
+        assert_eq!(
+            "Bad config: account.username: \"a b\" is not a Minecraft name, they are 1 to 16 letters, digits and underscores",
+            error("account.username = \"a b\"")
+        );
+        assert_eq!(
+            "Bad config: account.uuid: needed with access_token to log in online",
+            error("account.access_token = \"token\"")
+        );
+        assert_eq!(
+            "Bad config: proxy.listen: \"localhost\" is not an IP address and port, like 127.0.0.1:25566",
+            error("proxy.listen = \"localhost\"")
+        );
+        assert_eq!(
+            "Bad config: afk.tasks[1]: max (10s) is less than min (20s)",
+            error(
+                "[[afk.tasks]]\naction = \"jump\"\nmin = 1\nmax = 2\n\
+                 [[afk.tasks]]\naction = \"jump\"\nmin = 20\nmax = 10"
+            )
+        );
+        assert!(error("[[afk.tasks]]\naction = \"dance\"\nmin = 1\nmax = 2")
+            .contains("\"dance\" is not an action"));
+        assert_eq!(
+            "Bad config: afk.tasks[0]: chat messages are 1 to 100 characters",
+            error(&format!(
+                "server.protocol = 47\n[[afk.tasks]]\naction = \"chat\"\nmin = 1\nmax = 2\nmessage = \"{}\"",
+                "a".repeat(101)
+            ))
+        );
+        assert_eq!(
+            "Bad config: safety.min_health: 25 is not above 0 and at most 20",
+            error("safety.min_health = 25.0")
+        );
+        assert_eq!(
+            "Bad config: safety.whitelist: \"Notch\" is not a UUID",
+            error("safety.whitelist = [\"Notch\"]")
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes128;
+use rand::RngCore;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
+
+#[derive(Debug, PartialEq)]
+pub enum CryptoError {
+    InvalidPublicKey, // Not a DER encoded X.509 SubjectPublicKeyInfo
+    EncryptionFailed, // Message too long for the key
+}
+
+// AES-128 in 8-bit cipher feedback mode. Every byte is XORed with the first
+// byte of the encrypted shift register, which is then shifted left by one
+// with the ciphertext byte appended, so it works on streams of any length.
+pub struct Cfb8 {
+    cipher: Aes128,
+    register: [u8; 16],
+}
+
+impl Cfb8 {
+    pub fn new(key: &[u8; 16], iv: &[u8; 16]) -> Cfb8 {
+        Cfb8 {
+            cipher: Aes128::new(GenericArray::from_slice(key)),
+            register: *iv,
+        }
+    }
+
+    fn next_mask(&self) -> u8 {
+        let mut block = GenericArray::clone_from_slice(&self.register);
+        self.cipher.encrypt_block(&mut block);
+        block[0]
+    }
+
+    fn shift_in(&mut self, ciphertext: u8) {
+        self.register.copy_within(1.., 0);
+        self.register[15] = ciphertext;
+    }
+
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut() {
+            *b ^= self.next_mask();
+            self.shift_in(*b);
+        }
+    }
+
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut() {
+            let ciphertext = *b;
+            *b ^= self.next_mask();
+            self.shift_in(ciphertext);
+        }
+    }
+}
+
+// Both directions of an encrypted connection. The protocol uses the shared
+// secret as the key and the IV, and each direction keeps its own register.
+pub struct StreamCipher {
+    encryptor: Cfb8,
+    decryptor: Cfb8,
+}
+
+impl StreamCipher {
+    pub fn new(shared_secret: &[u8; 16]) -> StreamCipher {
+        StreamCipher {
+            encryptor: Cfb8::new(shared_secret, shared_secret),
+            decryptor: Cfb8::new(shared_secret, shared_secret),
+        }
+    }
+
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.encryptor.encrypt(data)
+    }
+
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.decryptor.decrypt(data)
+    }
+}
+
+// The server's RSA public key from an Encryption Request
+pub struct PublicKey {
+    key: RsaPublicKey,
+}
+
+impl PublicKey {
+    pub fn from_der(der: &[u8]) -> Result<PublicKey, CryptoError> {
+        match RsaPublicKey::from_public_key_der(der) {
+            Ok(key) => Ok(PublicKey { key }),
+            Err(_) => Err(CryptoError::InvalidPublicKey),
+        }
+    }
+
+    // RSA with PKCS#1 v1.5 padding, as used for the shared secret and verify token
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.key
+            .encrypt(&mut rand::thread_rng(), Pkcs1v15Encrypt, data)
+            .map_err(|_| CryptoError::EncryptionFailed)
+    }
+}
+
+pub fn generate_shared_secret() -> [u8; 16] {
+    let mut secret = [0_u8; 16];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::*;
+
+    fn key(s: &str) -> [u8; 16] {
+        let mut k = [0_u8; 16];
+        k.copy_from_slice(hex::decode(s).unwrap().as_slice());
+        k
+    }
+
+    // NIST SP 800-38A, F.3.7 CFB8-AES128.Encrypt
+    const NIST_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const NIST_IV: &str = "000102030405060708090a0b0c0d0e0f";
+    const NIST_PLAINTEXT: &str = "6bc1bee22e409f96e93d7e117393172aae2d";
+    const NIST_CIPHERTEXT: &str = "3b79424c9c0dd436bace9e0ed4586a4f32b9";
+
+    #[test]
+    fn cfb8_known_answer_encrypt() {
+        let mut cfb8 = Cfb8::new(&key(NIST_KEY), &key(NIST_IV));
+        let mut data = hex::decode(NIST_PLAINTEXT).unwrap();
+        cfb8.encrypt(&mut data);
+        assert_eq!(NIST_CIPHERTEXT, hex::encode(&data));
+    }
+
+    #[test]
+    fn cfb8_known_answer_decrypt() {
+        let mut cfb8 = Cfb8::new(&key(NIST_KEY), &key(NIST_IV));
+        let mut data = hex::decode(NIST_CIPHERTEXT).unwrap();
+        cfb8.decrypt(&mut data);
+        assert_eq!(NIST_PLAINTEXT, hex::encode(&data));
+    }
+
+    #[test]
+    fn cfb8_is_a_stream() {
+        // Encrypting in pieces must give the same output as one call
+        let mut whole = Cfb8::new(&key(NIST_KEY), &key(NIST_IV));
+        let mut pieces = Cfb8::new(&key(NIST_KEY), &key(NIST_IV));
+        let mut a = hex::decode(NIST_PLAINTEXT).unwrap();
+        let mut b = a.clone();
+
+        whole.encrypt(&mut a);
+        let (first, rest) = b.split_at_mut(5);
+        pieces.encrypt(first);
+        pieces.encrypt(rest);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn stream_cipher_round_trip() {
+        // Shared secret is used as both the key and the IV
+        let secret = key("00112233445566778899aabbccddeeff");
+        let mut client = StreamCipher::new(&secret);
+        let mut server = StreamCipher::new(&secret);
+
+        let msg = b"\x10\x00\xd4\x02\x09localhost\x63\xdd\x02".to_vec();
+        let mut data = msg.clone();
+        client.encrypt(&mut data);
+        assert_ne!(msg, data);
+        server.decrypt(&mut data);
+        assert_eq!(msg, data);
+
+        // The reverse direction has an independent register
+        let mut data = msg.clone();
+        server.encrypt(&mut data);
+        client.decrypt(&mut data);
+        assert_eq!(msg, data);
+    }
+
+    #[test]
+    fn public_key_encrypt() {
+        use rsa::pkcs8::EncodePublicKey;
+        use rsa::RsaPrivateKey;
+
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let der = private_key.to_public_key().to_public_key_der().unwrap();
+        let public_key = PublicKey::from_der(der.as_bytes()).unwrap();
+
+        let secret = generate_shared_secret();
+        let encrypted = public_key.encrypt(&secret).unwrap();
+        assert_eq!(128, encrypted.len());
+        let decrypted = private_key.decrypt(Pkcs1v15Encrypt, &encrypted).unwrap();
+        assert_eq!(secret.to_vec(), decrypted);
+    }
+
+    #[test]
+    fn invalid_public_key() {
+        assert_eq!(
+            CryptoError::InvalidPublicKey,
+            PublicKey::from_der(&[0x30, 0x03, 0x02, 0x01, 0x00])
+                .err()
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::field::Field;
+use crate::serialize::metadata::Metadata;
+use crate::serialize::packet::clientbound::{
+    DestroyEntities, EntityLook, EntityLookAndRelativeMove, EntityMetadata, EntityRelativeMove,
+    EntityTeleport, SpawnMob, SpawnObject, SpawnPlayer,
+};
+use crate::serialize::packet::{deserialize_new, PacketID};
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::var::DeserializeError;
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EntityKind {
+    Player,
+    Mob,
+    Object, // Items, arrows, minecarts...
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    pub id: i32,
+    pub uuid: u128,
+    pub kind: EntityKind,
+    pub type_id: i32, // Of mob or object, 0 for players
+    pub x: f64,
+    pub y: f64, // Feet
+    pub z: f64,
+    pub yaw: u8, // 256ths of a turn
+    pub pitch: u8,
+    pub on_ground: bool,
+    pub metadata: Metadata, // Only decoded in 1.12.2
+}
+
+impl Entity {
+    pub fn distance_to(&self, x: f64, y: f64, z: f64) -> f64 {
+        ((self.x - x).powi(2) + (self.y - y).powi(2) + (self.z - z).powi(2)).sqrt()
+    }
+}
+
+// The entities around us, by entity id. Players are tracked in every
+// version; mobs, objects and metadata only in 1.12.2, the other versions'
+// packets for them look up as `PacketID::Unknown`.
+#[derive(Debug)]
+pub struct Entities {
+    ver: ProtocolVersion,
+    entities: HashMap<i32, Entity>,
+}
+
+impl Entities {
+    pub fn new(ver: ProtocolVersion) -> Entities {
+        Entities {
+            ver,
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, id: i32) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    // Entities within `range` blocks of a point, closest first
+    pub fn nearby(&self, x: f64, y: f64, z: f64, range: f64) -> Vec<&Entity> {
+        let mut nearby: Vec<(f64, &Entity)> = self
+            .entities
+            .values()
+            .map(|entity| (entity.distance_to(x, y, z), entity))
+            .filter(|(distance, _)| *distance <= range)
+            .collect();
+        nearby.sort_by(|a, b| a.0.total_cmp(&b.0));
+        nearby.into_iter().map(|(_, entity)| entity).collect()
+    }
+
+    pub fn players_near(&self, x: f64, y: f64, z: f64, range: f64) -> Vec<&Entity> {
+        self.nearby_of(EntityKind::Player, x, y, z, range)
+    }
+
+    pub fn mobs_near(&self, x: f64, y: f64, z: f64, range: f64) -> Vec<&Entity> {
+        self.nearby_of(EntityKind::Mob, x, y, z, range)
+    }
+
+    fn nearby_of(&self, kind: EntityKind, x: f64, y: f64, z: f64, range: f64) -> Vec<&Entity> {
+        let mut nearby = self.nearby(x, y, z, range);
+        nearby.retain(|entity| entity.kind == kind);
+        nearby
+    }
+
+    // Entities we never saw spawn are ignored until they do
+    pub fn update(&mut self, kind: PacketID, data: &[u8]) -> Result<(), DeserializeError> {
+        let mut fields = ByteBuf::from(data);
+        let ver = &self.ver;
+        match kind {
+            // Everything is sent again in the new world
+            PacketID::JoinGame | PacketID::Respawn => self.entities.clear(),
+            PacketID::SpawnPlayer => {
+                let spawn = deserialize_new::<SpawnPlayer>(&mut fields, ver)?;
+                let metadata = if *ver == ProtocolVersion::V_1_12_2 {
+                    Metadata::read_from(&mut fields)?
+                } else {
+                    Metadata::default()
+                };
+                self.spawn(Entity {
+                    id: spawn.entity_id,
+                    uuid: spawn.uuid,
+                    kind: EntityKind::Player,
+                    type_id: 0,
+                    x: spawn.x,
+                    y: spawn.y,
+                    z: spawn.z,
+                    yaw: spawn.yaw,
+                    pitch: spawn.pitch,
+                    on_ground: false,
+                    metadata,
+                });
+            }
+            PacketID::SpawnMob => {
+                let spawn = deserialize_new::<SpawnMob>(&mut fields, ver)?;
+                self.spawn(Entity {
+                    id: spawn.entity_id,
+                    uuid: spawn.uuid,
+                    kind: EntityKind::Mob,
+                    type_id: spawn.kind,
+                    x: spawn.x,
+                    y: spawn.y,
+                    z: spawn.z,
+                    yaw: spawn.yaw,
+                    pitch: spawn.pitch,
+                    on_ground: false,
+                    metadata: spawn.metadata,
+                });
+            }
+            PacketID::SpawnObject => {
+                let spawn = deserialize_new::<SpawnObject>(&mut fields, ver)?;
+                self.spawn(Entity {
+                    id: spawn.entity_id,
+                    uuid: spawn.uuid,
+                    kind: EntityKind::Object,
+                    type_id: spawn.kind as i32,
+                    x: spawn.x,
+                    y: spawn.y,
+                    z: spawn.z,
+                    yaw: spawn.yaw,
+                    pitch: spawn.pitch,
+                    on_ground: false,
+                    metadata: Metadata::default(),
+                });
+            }
+            PacketID::EntityRelativeMove => {
+                let moved = deserialize_new::<EntityRelativeMove>(&mut fields, ver)?;
+                if let Some(entity) = self.entities.get_mut(&moved.entity_id) {
+                    entity.x += moved.dx;
+                    entity.y += moved.dy;
+                    entity.z += moved.dz;
+                    entity.on_ground = moved.on_ground;
+                }
+            }
+            PacketID::EntityLookAndRelativeMove => {
+                let moved = deserialize_new::<EntityLookAndRelativeMove>(&mut fields, ver)?;
+                if let Some(entity) = self.entities.get_mut(&moved.entity_id) {
+                    entity.x += moved.dx;
+                    entity.y += moved.dy;
+                    entity.z += moved.dz;
+                    entity.yaw = moved.yaw;
+                    entity.pitch = moved.pitch;
+                    entity.on_ground = moved.on_ground;
+                }
+            }
+            PacketID::EntityLook => {
+                let look = deserialize_new::<EntityLook>(&mut fields, ver)?;
+                if let Some(entity) = self.entities.get_mut(&look.entity_id) {
+                    entity.yaw = look.yaw;
+                    entity.pitch = look.pitch;
+                    entity.on_ground = look.on_ground;
+                }
+            }
+            PacketID::EntityTeleport => {
+                let teleport = deserialize_new::<EntityTeleport>(&mut fields, ver)?;
+                if let Some(entity) = self.entities.get_mut(&teleport.entity_id) {
+                    entity.x = teleport.x;
+                    entity.y = teleport.y;
+                    entity.z = teleport.z;
+                    entity.yaw = teleport.yaw;
+                    entity.pitch = teleport.pitch;
+                    entity.on_ground = teleport.on_ground;
+                }
+            }
+            PacketID::EntityMetadata => {
+                let update = deserialize_new::<EntityMetadata>(&mut fields, ver)?;
+                if let Some(entity) = self.entities.get_mut(&update.entity_id) {
+                    entity.metadata.merge(update.metadata);
+                }
+            }
+            PacketID::DestroyEntities => {
+                for id in deserialize_new::<DestroyEntities>(&mut fields, ver)?.entity_ids {
+                    self.entities.remove(&id);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // An id spawned again replaces what had it
+    fn spawn(&mut self, entity: Entity) {
+        self.entities.insert(entity.id, entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::*;
+    use crate::serialize::metadata::Value;
+    use crate::serialize::packet::Packet;
+
+    fn spawn_player(id: i32, x: f64) -> SpawnPlayer {
+        SpawnPlayer {
+            entity_id: id,
+            uuid: id as u128,
+            x,
+            y: 64.0,
+            z: 0.0,
+            ..SpawnPlayer::default()
+        }
+    }
+
+    #[test]
+    fn players_move_in_every_version() {
+        for &ver in ProtocolVersion::ALL {
+            let mut entities = Entities::new(ver);
+            entities
+                .update(
+                    PacketID::SpawnPlayer,
+                    &spawn_player(1, 100.0).body(&entities.ver),
+                )
+                .unwrap();
+            entities
+                .update(
+                    PacketID::SpawnPlayer,
+                    &spawn_player(2, 10.5).body(&entities.ver),
+                )
+                .unwrap();
+            assert_eq!(2, entities.len());
+
+            // Walks 3 blocks closer, then is teleported right next to us
+            for _ in 0..2 {
+                entities
+                    .update(
+                        PacketID::EntityRelativeMove,
+                        &EntityRelativeMove {
+                            entity_id: 1,
+                            dx: -1.5,
+                            dy: 0.25,
+                            dz: 0.0,
+                            on_ground: true,
+                        }
+                        .body(&entities.ver),
+                    )
+                    .unwrap();
+            }
+            let player = entities.get(1).unwrap();
+            assert_eq!((97.0, 64.5, true), (player.x, player.y, player.on_ground));
+            entities
+                .update(
+                    PacketID::EntityTeleport,
+                    &EntityTeleport {
+                        entity_id: 1,
+                        x: 2.0,
+                        y: 64.0,
+                        z: 1.0,
+                        yaw: 128,
+                        ..EntityTeleport::default()
+                    }
+                    .body(&entities.ver),
+                )
+                .unwrap();
+            entities
+                .update(
+                    PacketID::EntityLook,
+                    &EntityLook {
+                        entity_id: 2,
+                        yaw: 64,
+                        pitch: 10,
+                        on_ground: true,
+                    }
+                    .body(&entities.ver),
+                )
+                .unwrap();
+
+            let near: Vec<i32> = entities
+                .players_near(0.0, 64.0, 0.0, 16.0)
+                .iter()
+                .map(|entity| entity.id)
+                .collect();
+            assert_eq!(vec![1, 2], near, "{:?}", ver);
+            assert_eq!(128, entities.get(1).unwrap().yaw);
+            assert_eq!((64, 10), {
+                let player = entities.get(2).unwrap();
+                (player.yaw, player.pitch)
+            });
+
+            entities
+                .update(
+                    PacketID::DestroyEntities,
+                    &DestroyEntities {
+                        entity_ids: vec![1, 3],
+                    }
+                    .body(&entities.ver),
+                )
+                .unwrap();
+            assert_eq!(1, entities.len());
+            entities.update(PacketID::Respawn, &[]).unwrap();
+            assert!(entities.is_empty());
+        }
+    }
+
+    #[test]
+    fn mobs_and_metadata() {
+        let mut entities = Entities::new(ProtocolVersion::V_1_12_2);
+        let mut metadata = Metadata::default();
+        metadata.set(7, Value::Float(20.0)); // Health
+        entities
+            .update(
+                PacketID::SpawnMob,
+                &SpawnMob {
+                    entity_id: 5,
+                    kind: 54, // Zombie
+                    x: 3.0,
+                    y: 64.0,
+                    z: 4.0,
+                    metadata,
+                    ..SpawnMob::default()
+                }
+                .body(&entities.ver),
+            )
+            .unwrap();
+        entities
+            .update(
+                PacketID::SpawnObject,
+                &SpawnObject {
+                    entity_id: 6,
+                    kind: 2, // Item
+                    x: 1.0,
+                    y: 64.0,
+                    ..SpawnObject::default()
+                }
+                .body(&entities.ver),
+            )
+            .unwrap();
+        entities
+            .update(
+                PacketID::EntityLookAndRelativeMove,
+                &EntityLookAndRelativeMove {
+                    entity_id: 5,
+                    dx: 0.5,
+                    dz: -0.125,
+                    yaw: 32,
+                    ..EntityLookAndRelativeMove::default()
+                }
+                .body(&entities.ver),
+            )
+            .unwrap();
+        let mut update = Metadata::default();
+        update.set(7, Value::Float(15.5));
+        entities
+            .update(
+                PacketID::EntityMetadata,
+                &EntityMetadata {
+                    entity_id: 5,
+                    metadata: update,
+                }
+                .body(&entities.ver),
+            )
+            .unwrap();
+
+        let zombie = entities.get(5).unwrap();
+        assert_eq!((3.5, 3.875, 32), (zombie.x, zombie.z, zombie.yaw));
+        assert_eq!(Some(&Value::Float(15.5)), zombie.metadata.get(7));
+        assert_eq!(
+            vec![5],
+            entities
+                .mobs_near(0.0, 64.0, 0.0, 8.0)
+                .iter()
+                .map(|entity| entity.id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, entities.nearby(0.0, 64.0, 0.0, 8.0).len());
+        assert!(entities.nearby(0.0, 64.0, 0.0, 0.5).is_empty());
+        assert!(entities.players_near(0.0, 64.0, 0.0, 100.0).is_empty());
+
+        // Player spawns carry metadata in 1.12.2
+        let spawn = spawn_player(9, 0.0).body(&entities.ver);
+        entities.update(PacketID::SpawnPlayer, &spawn).unwrap();
+        assert_eq!(EntityKind::Player, entities.get(9).unwrap().kind);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::auth::AuthError;
+use crate::crypto::CryptoError;
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::var::DeserializeError;
+use std::fmt;
+use std::io;
+
+// Everything that can go wrong talking to a server. A connection that
+// returned one of these can't be used any more, except after `Io` timeouts.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Framing(DeserializeError),     // A frame's length couldn't be read
+    Compression(DeserializeError), // A frame couldn't be inflated
+    Decode(DeserializeError),      // A packet's fields aren't what we expected
+    Protocol(String),              // The server did something the protocol doesn't allow
+    Disconnected(String),          // Kicked, with the reason as a JSON chat component
+    Closed,                        // The other end closed the connection
+    Unsafe(String),                // Left because the safety policy said to
+    Auth(AuthError),
+    Crypto(CryptoError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Framing(e) => write!(f, "Bad frame: {:?}", e),
+            Error::Compression(e) => write!(f, "Bad compressed packet: {:?}", e),
+            Error::Decode(e) => write!(f, "Bad packet: {:?}", e),
+            Error::Protocol(e) => write!(f, "Protocol error: {}", e),
+            Error::Disconnected(reason) => write!(f, "Disconnected: {}", reason),
+            Error::Closed => write!(f, "Connection closed"),
+            Error::Unsafe(danger) => write!(f, "Left to be safe: {}", danger),
+            Error::Auth(e) => write!(f, "Authentication failed: {:?}", e),
+            Error::Crypto(e) => write!(f, "Encryption failed: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+// Errors from reading a packet's fields, framing and compression errors are
+// wrapped where they happen
+impl From<DeserializeError> for Error {
+    fn from(e: DeserializeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<AuthError> for Error {
+    fn from(e: AuthError) -> Self {
+        Error::Auth(e)
+    }
+}
+
+impl From<CryptoError> for Error {
+    fn from(e: CryptoError) -> Self {
+        Error::Crypto(e)
+    }
+}
+
+impl Error {
+    // Sending a `P` on a connection speaking `ver`, which doesn't have it
+    pub fn unsupported<P>(ver: &ProtocolVersion) -> Error {
+        let name = std::any::type_name::<P>();
+        let name = name.rsplit("::").next().unwrap_or(name);
+        Error::Protocol(format!("{} does not exist in {}", name, ver.name()))
+    }
+
+    // True for read timeouts, which just mean nothing arrived in time
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Error::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::*;
+
+    #[test]
+    fn errors_convert_and_display() {
+        let e: Error = DeserializeError::InvalidString.into();
+        assert!(matches!(e, Error::Decode(DeserializeError::InvalidString)));
+        assert_eq!("Bad packet: InvalidString", e.to_string());
+
+        let e: Error = io::Error::new(io::ErrorKind::TimedOut, "timed out").into();
+        assert!(e.is_timeout());
+        assert!(!Error::Closed.is_timeout());
+
+        let e = Error::unsupported::<CryptoError>(&ProtocolVersion::V_1_8_9);
+        assert_eq!(
+            "Protocol error: CryptoError does not exist in 1.8.9",
+            e.to_string()
+        );
+
+        let e: Error = CryptoError::InvalidPublicKey.into();
+        assert_eq!("Encryption failed: InvalidPublicKey", e.to_string());
+    }
+}
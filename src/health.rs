@@ -0,0 +1,160 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::packet::clientbound::{CombatEvent, UpdateHealth, ENTITY_DEAD};
+use crate::serialize::packet::{deserialize_new, PacketID};
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::var::DeserializeError;
+
+pub const MAX_HEALTH: f32 = 20.0;
+
+// Our health and hunger as the server last said, and whether we're dead
+#[derive(Debug)]
+pub struct Health {
+    ver: ProtocolVersion,
+    health: f32,
+    food: i32,
+    saturation: f32,
+    dead: bool,
+    respawn_requested: bool,
+    death_message: Option<String>, // JSON chat component
+}
+
+impl Health {
+    // Full until the server says otherwise
+    pub fn new(ver: ProtocolVersion) -> Health {
+        Health {
+            ver,
+            health: MAX_HEALTH,
+            food: 20,
+            saturation: 5.0,
+            dead: false,
+            respawn_requested: false,
+            death_message: None,
+        }
+    }
+
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    pub fn food(&self) -> i32 {
+        self.food
+    }
+
+    pub fn saturation(&self) -> f32 {
+        self.saturation
+    }
+
+    pub fn dead(&self) -> bool {
+        self.dead
+    }
+
+    // How we last died, if we're dead and the server said
+    pub fn death_message(&self) -> Option<&str> {
+        self.death_message.as_deref()
+    }
+
+    // True once per death, when a respawn should be asked for
+    pub fn take_respawn(&mut self) -> bool {
+        let respawn = self.dead && !self.respawn_requested;
+        self.respawn_requested |= respawn;
+        respawn
+    }
+
+    // Follows a clientbound play packet's body
+    pub fn update(&mut self, kind: PacketID, data: &[u8]) -> Result<(), DeserializeError> {
+        let mut fields = ByteBuf::from(data);
+        match kind {
+            PacketID::UpdateHealth => {
+                let update = deserialize_new::<UpdateHealth>(&mut fields, &self.ver)?;
+                self.health = update.health;
+                self.food = update.food;
+                self.saturation = update.saturation;
+                if update.health <= 0.0 {
+                    self.dead = true;
+                } else {
+                    self.alive();
+                }
+            }
+            // Only sent to whoever died
+            PacketID::CombatEvent => {
+                let combat = deserialize_new::<CombatEvent>(&mut fields, &self.ver)?;
+                if combat.event == ENTITY_DEAD {
+                    self.dead = true;
+                    self.death_message = Some(combat.message);
+                }
+            }
+            PacketID::Respawn => self.alive(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn alive(&mut self) {
+        self.dead = false;
+        self.respawn_requested = false;
+        self.death_message = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::*;
+    use crate::serialize::packet::Packet;
+
+    fn update_health(health: f32) -> UpdateHealth {
+        UpdateHealth {
+            health,
+            food: 17,
+            saturation: 0.0,
+        }
+    }
+
+    #[test]
+    fn respawns_once_per_death() {
+        for &ver in ProtocolVersion::ALL {
+            let mut health = Health::new(ver);
+            assert!(!health.take_respawn());
+
+            health
+                .update(PacketID::UpdateHealth, &update_health(6.5).body(&ver))
+                .unwrap();
+            assert_eq!(
+                (6.5, 17, false),
+                (health.health(), health.food(), health.dead())
+            );
+
+            health
+                .update(
+                    PacketID::CombatEvent,
+                    &CombatEvent {
+                        event: ENTITY_DEAD,
+                        player_id: 1,
+                        message: r#"{"text":"idler fell"}"#.to_string(),
+                        ..CombatEvent::default()
+                    }
+                    .body(&ver),
+                )
+                .unwrap();
+            health
+                .update(PacketID::UpdateHealth, &update_health(0.0).body(&ver))
+                .unwrap();
+            assert!(health.dead());
+            assert_eq!(Some(r#"{"text":"idler fell"}"#), health.death_message());
+            assert!(health.take_respawn());
+            assert!(!health.take_respawn());
+
+            // Back with full health
+            health.update(PacketID::Respawn, &[]).unwrap();
+            health
+                .update(PacketID::UpdateHealth, &update_health(20.0).body(&ver))
+                .unwrap();
+            assert!(!health.dead() && health.death_message().is_none());
+
+            // Dying again asks again
+            health
+                .update(PacketID::UpdateHealth, &update_health(-1.0).body(&ver))
+                .unwrap();
+            assert!(health.take_respawn());
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::packet::clientbound::{HeldItemChange, SetSlot, WindowItems};
+use crate::serialize::packet::{deserialize_new, serverbound, PacketID};
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::slot::Slot;
+use crate::serialize::var::DeserializeError;
+
+// Window 0 slot numbers
+pub const CRAFTING_OUTPUT: usize = 0;
+pub const CRAFTING_INPUT: usize = 1; // 2x2, to 4
+pub const ARMOR: usize = 5; // Helmet to boots, to 8
+pub const MAIN: usize = 9; // 3 rows, to 35
+pub const HOTBAR: usize = 36; // To 44
+pub const OFFHAND: usize = 45;
+pub const SLOTS: usize = 46;
+
+const PLAYER_WINDOW: i8 = 0;
+const CURSOR_WINDOW: i8 = -1;
+
+// Slots are only read in the 1.12.2 format, the other versions' window
+// packets look up as `PacketID::Unknown`
+const VER: ProtocolVersion = ProtocolVersion::V_1_12_2;
+
+// The player's own inventory as the server last told us. Other windows
+// (chests, furnaces) aren't tracked.
+#[derive(Debug)]
+pub struct Inventory {
+    slots: Vec<Option<Slot>>,
+    held: usize, // Hotbar slot, 0-8
+    cursor: Option<Slot>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory::new()
+    }
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory {
+            slots: vec![None; SLOTS],
+            held: 0,
+            cursor: None,
+        }
+    }
+
+    // Only the player's own window, the cursor and the held slot are kept
+    pub fn update(&mut self, kind: PacketID, data: &[u8]) -> Result<(), DeserializeError> {
+        let mut fields = ByteBuf::from(data);
+        match kind {
+            PacketID::WindowItems => {
+                let items = deserialize_new::<WindowItems>(&mut fields, &VER)?;
+                if items.window_id == PLAYER_WINDOW as u8 {
+                    for (i, slot) in items.slots.into_iter().take(SLOTS).enumerate() {
+                        self.slots[i] = slot;
+                    }
+                }
+            }
+            PacketID::SetSlot => {
+                let set = deserialize_new::<SetSlot>(&mut fields, &VER)?;
+                match set.window_id {
+                    CURSOR_WINDOW => self.cursor = set.data,
+                    PLAYER_WINDOW if (0..SLOTS as i16).contains(&set.slot) => {
+                        self.slots[set.slot as usize] = set.data;
+                    }
+                    _ => {}
+                }
+            }
+            PacketID::HeldItemChangeCB => {
+                let change = deserialize_new::<HeldItemChange>(&mut fields, &VER)?;
+                if (0..9).contains(&change.slot) {
+                    self.held = change.slot as usize;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn slots(&self) -> &[Option<Slot>] {
+        &self.slots
+    }
+
+    pub fn slot(&self, slot: usize) -> Option<&Slot> {
+        self.slots.get(slot).and_then(|s| s.as_ref())
+    }
+
+    pub fn hotbar(&self) -> &[Option<Slot>] {
+        &self.slots[HOTBAR..HOTBAR + 9]
+    }
+
+    pub fn held_slot(&self) -> usize {
+        self.held
+    }
+
+    pub fn held_item(&self) -> Option<&Slot> {
+        self.slot(HOTBAR + self.held)
+    }
+
+    // The item being moved around with the mouse
+    pub fn cursor(&self) -> Option<&Slot> {
+        self.cursor.as_ref()
+    }
+
+    // The first slot holding `item_id`
+    pub fn find(&self, item_id: i16) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|s| s.as_ref().map(|s| s.item_id) == Some(item_id))
+    }
+
+    // Switches to a hotbar slot, returning the packet to tell the server
+    pub fn select(&mut self, hotbar_slot: usize) -> serverbound::HeldItemChange {
+        assert!(hotbar_slot < 9, "hotbar slot {} out of range", hotbar_slot);
+        self.held = hotbar_slot;
+        serverbound::HeldItemChange {
+            slot: hotbar_slot as i16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::*;
+    use crate::serialize::packet::Packet;
+
+    fn item(item_id: i16, count: i8) -> Option<Slot> {
+        Some(Slot {
+            item_id,
+            count,
+            damage: 0,
+            nbt: None,
+        })
+    }
+
+    #[test]
+    fn window_items_fill_the_inventory() {
+        let mut inventory = Inventory::new();
+        let mut slots = vec![None; SLOTS];
+        slots[HOTBAR + 2] = item(276, 1);
+        slots[MAIN] = item(1, 64);
+        inventory
+            .update(
+                PacketID::WindowItems,
+                &WindowItems {
+                    window_id: 0,
+                    slots,
+                }
+                .body(&VER),
+            )
+            .unwrap();
+        assert_eq!(Some(HOTBAR + 2), inventory.find(276));
+        assert_eq!(64, inventory.slot(MAIN).unwrap().count);
+        assert_eq!(None, inventory.held_item());
+
+        inventory
+            .update(
+                PacketID::HeldItemChangeCB,
+                &HeldItemChange { slot: 2 }.body(&VER),
+            )
+            .unwrap();
+        assert_eq!(2, inventory.held_slot());
+        assert_eq!(276, inventory.held_item().unwrap().item_id);
+        assert_eq!(276, inventory.hotbar()[2].as_ref().unwrap().item_id);
+
+        // Chest contents aren't ours
+        inventory
+            .update(
+                PacketID::WindowItems,
+                &WindowItems {
+                    window_id: 3,
+                    slots: vec![None; 63],
+                }
+                .body(&VER),
+            )
+            .unwrap();
+        assert_eq!(Some(HOTBAR + 2), inventory.find(276));
+    }
+
+    #[test]
+    fn set_slot_and_cursor() {
+        let mut inventory = Inventory::new();
+        inventory
+            .update(
+                PacketID::SetSlot,
+                &SetSlot {
+                    window_id: 0,
+                    slot: OFFHAND as i16,
+                    data: item(442, 1),
+                }
+                .body(&VER),
+            )
+            .unwrap();
+        assert_eq!(442, inventory.slot(OFFHAND).unwrap().item_id);
+
+        inventory
+            .update(
+                PacketID::SetSlot,
+                &SetSlot {
+                    window_id: -1,
+                    slot: -1,
+                    data: item(3, 12),
+                }
+                .body(&VER),
+            )
+            .unwrap();
+        assert_eq!(12, inventory.cursor().unwrap().count);
+
+        inventory
+            .update(
+                PacketID::SetSlot,
+                &SetSlot {
+                    window_id: 0,
+                    slot: OFFHAND as i16,
+                    data: None,
+                }
+                .body(&VER),
+            )
+            .unwrap();
+        assert_eq!(None, inventory.slot(OFFHAND));
+        assert_eq!(None, inventory.find(442));
+    }
+
+    #[test]
+    fn select_held_slot() {
+        let mut inventory = Inventory::new();
+        let packet = inventory.select(8);
+        assert_eq!(8, packet.slot);
+        assert_eq!(8, inventory.held_slot());
+
+        // Out of range slots from the server are ignored
+        inventory
+            .update(
+                PacketID::HeldItemChangeCB,
+                &HeldItemChange { slot: 9 }.body(&VER),
+            )
+            .unwrap();
+        assert_eq!(8, inventory.held_slot());
+    }
+}
@@ -0,0 +1,20 @@
+pub mod afk;
+pub mod async_mc;
+pub mod auth;
+pub mod cache;
+pub mod chat;
+pub mod chunk;
+pub mod config;
+pub mod crypto;
+pub mod entity;
+pub mod error;
+pub mod health;
+pub mod inventory;
+pub mod mc;
+pub mod player;
+pub mod proxy;
+pub mod safety;
+pub mod serialize;
+pub mod status;
+pub mod supervisor;
+pub mod tablist;
@@ -1,56 +1,413 @@
-mod serialize;
-use crate::serialize::packet;
-use crate::serialize::packet::serverbound::*;
-use crate::serialize::protocol::ProtocolVersion;
+use clap::Parser;
+use mcidle_rs::afk::AntiAfk;
+use mcidle_rs::auth::SessionServer;
+use mcidle_rs::cache::WorldCache;
+use mcidle_rs::chat::Component;
+use mcidle_rs::config::{Config, ConfigError, File, ProtocolFile};
+use mcidle_rs::entity::Entities;
+use mcidle_rs::error::{Error, Result};
+use mcidle_rs::health::Health;
+use mcidle_rs::inventory::Inventory;
+use mcidle_rs::mc;
+use mcidle_rs::proxy::{Proxy, ProxyEvent};
+use mcidle_rs::serialize::packet;
+use mcidle_rs::serialize::packet::serverbound::*;
+use mcidle_rs::serialize::protocol::ProtocolVersion;
+use mcidle_rs::supervisor::{Event, Supervisor};
+use mcidle_rs::tablist::{Change, TabList};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-mod mc;
+// A server that takes longer to answer a status ping is logged in to anyway
+const STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Vanilla clients send their position at least once a second
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
+// Read when no config is given, if it's there
+const DEFAULT_CONFIG: &str = "mcidle.toml";
+
+/// Keeps a Minecraft account online and lets a vanilla client take over
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// TOML config, see mcidle.example.toml [default: mcidle.toml if there is one]
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Server to connect to, overrides server.address
+    #[arg(short, long)]
+    server: Option<String>,
+    /// Overrides server.port
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// Account to log in as, overrides account.username
+    #[arg(short, long)]
+    username: Option<String>,
+    /// auto, a release like 1.12.2 or a protocol number, overrides server.protocol
+    #[arg(long)]
+    protocol: Option<String>,
+    /// Address for clients to attach on, overrides proxy.listen
+    #[arg(short, long)]
+    listen: Option<String>,
+    /// Print every packet read
+    #[arg(short, long)]
+    verbose: bool,
+    /// Check the config and exit
+    #[arg(long)]
+    check: bool,
+}
+
+impl Cli {
+    // The config file with the command line on top
+    fn config(self) -> std::result::Result<Config, ConfigError> {
+        let mut file = match &self.config {
+            Some(path) => File::read(path)?,
+            None if Path::new(DEFAULT_CONFIG).exists() => File::read(Path::new(DEFAULT_CONFIG))?,
+            None => File::default(),
+        };
+        if let Some(server) = self.server {
+            file.server.address = server;
+        }
+        if let Some(port) = self.port {
+            file.server.port = port;
+        }
+        if let Some(username) = self.username {
+            file.account.username = username;
+        }
+        if let Some(protocol) = self.protocol {
+            file.server.protocol = ProtocolFile::Name(protocol);
+        }
+        if let Some(listen) = self.listen {
+            file.proxy.listen = listen;
+        }
+        file.log.packets |= self.verbose;
+        file.validate()
+    }
+}
 
 fn main() {
+    let cli = Cli::parse();
+    let check = cli.check;
+    let config = match cli.config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    if check {
+        println!("Config is valid");
+        return;
+    }
+    if let Err(e) = run(config) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Chat components coloured for the terminal, anything else as it is
+fn render(json: &str) -> String {
+    Component::from_json(json).map_or_else(|_| json.to_string(), |chat| chat.to_ansi())
+}
+
+fn run(config: Config) -> Result<()> {
+    // Unless told otherwise, speak whatever version the server advertises,
+    // 1.12.2 if it won't say
+    let mut protocol = config.protocol.unwrap_or(ProtocolVersion::V_1_12_2);
+    // A server that's down is waited for by the supervisor below
+    let ping = mc::Connection::new(config.address(), protocol, mc::BufferSize::Medium)
+        .and_then(|mut status| status.ping_status(&config.host, config.port, STATUS_TIMEOUT));
+    match ping {
+        Ok(ping) => {
+            println!(
+                "Server is running {} with {}/{} players online ({}ms)",
+                ping.status.version.name,
+                ping.status.players.online,
+                ping.status.players.max,
+                ping.latency.as_millis()
+            );
+            if config.protocol.is_none() {
+                protocol = ProtocolVersion::from_protocol(ping.status.version.protocol)
+                    .ok_or_else(|| {
+                        Error::Protocol(format!(
+                            "{} (protocol {}) is not supported",
+                            ping.status.version.name, ping.status.version.protocol
+                        ))
+                    })?;
+            }
+        }
+        Err(e) => println!("Status ping failed: {}", e),
+    }
+    println!("Using protocol {:?}", protocol);
+
     let handshake = Handshake {
-        protocol_version: 340,
-        address: "localhost".to_string(),
-        port: 25565,
+        protocol_version: protocol as i32,
+        address: config.host.clone(),
+        port: config.port,
         next_state: LoginState::Login,
     };
-    let protocol = ProtocolVersion::V_1_12_2;
-    let mut c = mc::Connection::new(
-        "localhost:25565".to_string(),
-        protocol,
-        mc::BufferSize::Medium,
-    );
-    println!("Sent {} bytes!", c.send_packet(&handshake));
-
     let login_start = LoginStart {
-        username: "test".to_string(),
+        username: config.username.clone(),
+        uuid: config.uuid,
     };
-    println!("Sent {} bytes!", c.send_packet(&login_start));
+    let mut upstream = Supervisor::new(config.address(), protocol, handshake, login_start);
+    if let Some(profile) = config.profile.clone() {
+        upstream.set_authentication(SessionServer::new(&config.session_server), profile);
+    }
+    let events = upstream.subscribe();
+    let success = upstream.connect()?;
+
+    let mut cache = WorldCache::new(protocol);
+    let mut inventory = Inventory::new();
+    let mut health = Health::new(protocol);
+    let mut entities = Entities::new(protocol);
+    let mut tab_list = TabList::new(protocol);
+    let safety = &config.safety;
+    let log = &config.log;
+    let mut proxy = Proxy::bind(
+        &config.listen.to_string(),
+        protocol,
+        success,
+        config.compression,
+    )?;
+    let client_events = proxy.subscribe();
+    println!("Proxy listening on {}", proxy.local_addr());
 
+    // Lines typed into the terminal are sent as chat
+    let (chat_tx, chat_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(|line| line.ok()) {
+            if chat_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut last_position = Instant::now();
+    let started = Instant::now();
+    let mut afk = AntiAfk::new(protocol, config.afk.clone());
     loop {
-        let mut pkts = c.read_packets();
+        // Only fails if reconnecting is given up on
+        let mut pkts = upstream.read_packets()?;
+
+        for event in events.try_iter() {
+            match &event {
+                Event::Connected(success) => {
+                    println!("Logged in as {} ({})!", success.username, success.uuid);
+                    // The server sends everything again
+                    cache = WorldCache::new(protocol);
+                    inventory = Inventory::new();
+                    health = Health::new(protocol);
+                    entities = Entities::new(protocol);
+                    tab_list = TabList::new(protocol);
+                    afk = AntiAfk::new(protocol, config.afk.clone());
+                }
+                Event::Disconnected(reason) => println!("Disconnected: {}", render(reason)),
+                Event::Reconnecting(attempt, delay) => {
+                    println!("Reconnecting in {:?} (attempt {})", delay, attempt)
+                }
+            }
+            proxy.notify(&event);
+        }
+
+        for message in chat_rx.try_iter() {
+            if message.chars().count() > ChatMessage::max_length(&protocol) {
+                println!(
+                    "Not sent, chat messages are at most {} characters",
+                    ChatMessage::max_length(&protocol)
+                );
+            } else if let Err(e) = upstream.send_packet(&ChatMessage { message }) {
+                println!("Could not send chat: {}", e);
+            }
+        }
+
+        // The attached client sends its own
+        if !proxy.attached() && last_position.elapsed() >= POSITION_INTERVAL {
+            last_position = Instant::now();
+            let player = upstream
+                .connection()
+                .and_then(|conn| conn.player().cloned());
+            if let Some(player) = player {
+                if let Err(e) = upstream.send_packet(&player.position()) {
+                    println!("Could not send position: {}", e);
+                }
+            }
+        }
+
+        if !proxy.attached() {
+            if let Some(conn) = upstream.connection() {
+                if let Some(player) = conn.player().cloned() {
+                    let entity_id = conn.entity_id().unwrap_or(0);
+                    let mut rng = rand::thread_rng();
+                    for emit in afk.poll(started.elapsed(), &player, entity_id, &mut rng) {
+                        if let Err(e) = emit.send(conn) {
+                            println!("Could not send {:?}: {}", emit, e);
+                        }
+                    }
+                }
+            }
+        }
+
+        // A failed send shows up as a lost connection on the next read
+        if let Some(conn) = upstream.connection() {
+            if let Err(e) = proxy.forward_to_server(conn, &cache, &entities, &tab_list) {
+                println!("Could not relay to the server: {}", e);
+            }
+        }
+        for event in client_events.try_iter() {
+            match event {
+                ProxyEvent::Attached(username) => println!("{} attached to the proxy", username),
+                ProxyEvent::Detached => println!("Client detached from the proxy"),
+                ProxyEvent::Lost(reason) => {
+                    println!("Lost connection to the attached client: {}", reason)
+                }
+            }
+        }
+
         let len = pkts.len();
         if len == 0 {
-            break;
+            continue;
+        }
+        if log.packets {
+            println!("Read {} packets!", len);
         }
-        println!("Read {} packets!", len);
         for (id, buf) in pkts.iter_mut() {
-            match packet::to_packet_id(*id) {
-                packet::PacketID::SetCompression => {
-                    let set_compression =
-                        packet::deserialize_new::<packet::clientbound::SetCompression>(buf);
-                    c.set_compression_threshold(set_compression.threshold);
-                    println!("Compression threshold is {}!", set_compression.threshold);
-                }
-                packet::PacketID::KeepAliveCB => {
-                    let keep_alive = packet::deserialize_new::<packet::clientbound::KeepAlive>(buf);
-                    println!("Got keep alive id {}!", keep_alive.id);
-                    let keep_alive_sb = packet::serverbound::KeepAlive { id: keep_alive.id };
-                    c.send_packet(&keep_alive_sb);
-                }
-                _ => {
+            let kind = upstream.packet_id(*id);
+            let data = buf.remaining_slice();
+            // A packet we can't make sense of isn't cached, but isn't fatal
+            if let Err(e) = cache
+                .update(kind, data)
+                .and(inventory.update(kind, data))
+                .and(health.update(kind, data))
+                .and(entities.update(kind, data))
+            {
+                println!("Bad {:?} packet: {:?}", kind, e);
+            }
+            match tab_list.update(kind, data) {
+                Ok(changes) => {
+                    for change in changes {
+                        match change {
+                            Change::Joined(player) => {
+                                if log
+                                    .watch
+                                    .iter()
+                                    .any(|name| name.eq_ignore_ascii_case(&player.name))
+                                {
+                                    println!("[Alert] {} is online", player.name);
+                                }
+                                if log.players {
+                                    println!("{} joined ({} online)", player.name, tab_list.len());
+                                }
+                            }
+                            Change::Left(player) if log.players => {
+                                println!("{} left ({} online)", player.name, tab_list.len())
+                            }
+                            Change::Left(_) => {}
+                        }
+                    }
+                }
+                Err(e) => println!("Bad {:?} packet: {:?}", kind, e),
+            }
+            proxy.forward_to_client(*id, data);
+
+            match kind {
+                packet::PacketID::KeepAliveCB if !proxy.attached() => {
+                    if let Ok(keep_alive) =
+                        packet::deserialize_new::<packet::clientbound::KeepAlive>(buf, &protocol)
+                    {
+                        if log.packets {
+                            println!("Got keep alive id {}!", keep_alive.id);
+                        }
+                        let keep_alive_sb = packet::serverbound::KeepAlive { id: keep_alive.id };
+                        if let Err(e) = upstream.send_packet(&keep_alive_sb) {
+                            println!("Could not answer keep alive: {}", e);
+                        }
+                    }
+                }
+                packet::PacketID::PlayerPositionAndLookCB if !proxy.attached() => {
+                    if let Ok(teleport) = packet::deserialize_new::<
+                        packet::clientbound::PlayerPositionAndLook,
+                    >(buf, &protocol)
+                    {
+                        // The connection has already moved us, accept it like a
+                        // vanilla client. 1.8 has nothing to confirm.
+                        afk.interrupt();
+                        if protocol > ProtocolVersion::V_1_8_9 {
+                            let confirm = TeleportConfirm {
+                                teleport_id: teleport.teleport_id,
+                            };
+                            if let Err(e) = upstream.send_packet(&confirm) {
+                                println!("Could not confirm teleport: {}", e);
+                            }
+                        }
+                        let player = upstream
+                            .connection()
+                            .and_then(|conn| conn.player().cloned());
+                        if let Some(player) = player {
+                            println!(
+                                "Teleported to {:.1} {:.1} {:.1}",
+                                player.x, player.y, player.z
+                            );
+                            if let Err(e) = upstream.send_packet(&player.position_and_look()) {
+                                println!("Could not send position: {}", e);
+                            }
+                        }
+                    }
+                }
+                packet::PacketID::ChatMessageCB if log.chat => {
+                    if let Ok(chat) =
+                        packet::deserialize_new::<packet::clientbound::ChatMessage>(buf, &protocol)
+                    {
+                        // Action bar text is sent over and over
+                        if chat.position != packet::clientbound::GAME_INFO {
+                            println!("[Chat] {}", render(&chat.json));
+                        }
+                    }
+                }
+                packet::PacketID::UpdateHealth if log.packets => {
+                    println!("Health {:.1}, food {}", health.health(), health.food());
+                }
+                packet::PacketID::HeldItemChangeCB if log.packets => {
+                    println!("Holding {:?}", inventory.held_item());
+                }
+                packet::PacketID::Unknown(id) if log.packets => {
                     println!("Unknown packet id {:x}", id);
                 }
+                other if log.packets => {
+                    println!("Unhandled packet {:?}", other);
+                }
+                _ => {}
+            }
+        }
+
+        // An attached client looks after itself
+        if !proxy.attached() {
+            let player = upstream
+                .connection()
+                .and_then(|conn| conn.player().cloned());
+            let nearby = player.and_then(|player| safety.check_nearby(&entities, &player));
+            if let Some(danger) = safety.check_health(&health).or(nearby) {
+                return Err(Error::Unsafe(danger.to_string()));
             }
+            if health.take_respawn() {
+                match health.death_message() {
+                    Some(message) => println!("Died: {}", render(message)),
+                    None => println!("Died"),
+                }
+                let respawn = ClientStatus {
+                    action: PERFORM_RESPAWN,
+                };
+                if let Err(e) = upstream.send_packet(&respawn) {
+                    println!("Could not respawn: {}", e);
+                }
+            }
+        }
+        if log.packets {
+            println!("done read call");
         }
-        println!("done read call");
     }
 }
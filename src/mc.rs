@@ -1,10 +1,27 @@
+use crate::auth::{server_hash, Profile, SessionServer};
+use crate::crypto::{generate_shared_secret, PublicKey, StreamCipher};
+use crate::error::{Error, Result};
 use crate::serialize::buffer::*;
-use std::net::TcpStream;
+use crate::serialize::compression::Compression;
+use crate::serialize::field::Field;
+use crate::serialize::frame::FrameDecoder;
+use std::net::{Shutdown, TcpStream};
 
-use crate::serialize::packet::Packet;
-use crate::serialize::protocol::ProtocolVersion;
+use crate::player::PlayerState;
+use crate::serialize::packet::clientbound::{
+    EncryptionRequest, LoginDisconnect, LoginSuccess, PlayerPositionAndLook, Pong, SetCompression,
+    StatusResponse,
+};
+use crate::serialize::packet::serverbound::{
+    EncryptionResponse, Handshake, LoginStart, LoginState, Ping, StatusRequest,
+};
+use crate::serialize::packet::{deserialize_new, Packet, PacketID};
+use crate::serialize::protocol::{ProtocolVersion, State};
+use crate::serialize::registry::{self, Direction};
 use crate::serialize::var::*;
-use std::io::{Read, Write};
+use crate::status::{ServerStatus, StatusPing};
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Copy, Clone)]
 #[repr(u32)]
@@ -15,50 +32,367 @@ pub enum BufferSize {
 pub struct Connection {
     stream: TcpStream,
     ver: ProtocolVersion,
-    compression: Option<i32>, // compression threshold
+    state: State,
+    compression: Option<Compression>,
+    cipher: Option<StreamCipher>,
+    auth: Option<(SessionServer, Profile)>, // None in offline mode
+    pending: Vec<(i32, ByteBuf)>,           // Read during login but not returned yet
+    error: Option<Error>,                   // Hit after packets that were still returned
+    frames: FrameDecoder,
+    closed: bool,
     chunk_size: BufferSize,
+    client: bool,                // Logged in with `login`, the other end is a server
+    player: Option<PlayerState>, // Where we are once the server has said
+    entity_id: Option<i32>,      // Ours, from Join Game
+}
+
+// Prepends the packet buffer with its length, compressing it first once
+// compression is on
+pub(crate) fn frame(buf: ByteBuf, compression: Option<Compression>) -> ByteBuf {
+    let contents = match compression {
+        Some(compression) => compression.encode(buf.as_slice()),
+        None => buf.as_slice().to_vec(),
+    };
+
+    let mut final_buf = ByteBuf::new();
+    final_buf.write_var_int(contents.len() as i32);
+    final_buf.write_all(&contents).unwrap();
+    final_buf
+}
+
+// Reads a packet's id and body out of a frame
+pub(crate) fn read_packet(
+    frame: &[u8],
+    compression: Option<Compression>,
+) -> Result<(i32, ByteBuf)> {
+    let mut buf = match compression {
+        Some(compression) => {
+            let packet = compression.decode(frame).map_err(Error::Compression)?;
+            ByteBuf::from(packet.as_slice())
+        }
+        None => ByteBuf::from(frame),
+    };
+    let id = buf.read_var_int()?;
+    Ok((id, buf))
 }
 
 impl Connection {
-    pub fn new(addr: String, ver: ProtocolVersion, chunk_size: BufferSize) -> Connection {
+    pub fn new(addr: String, ver: ProtocolVersion, chunk_size: BufferSize) -> Result<Connection> {
+        Ok(Connection::from_stream(
+            TcpStream::connect(addr)?,
+            ver,
+            chunk_size,
+        ))
+    }
+
+    // Wrap an already connected stream, e.g. one accepted by a listener
+    pub fn from_stream(
+        stream: TcpStream,
+        ver: ProtocolVersion,
+        chunk_size: BufferSize,
+    ) -> Connection {
         Connection {
-            stream: TcpStream::connect(addr).unwrap(),
+            stream,
             ver,
+            state: State::Handshaking,
             compression: None,
+            cipher: None,
+            auth: None,
+            pending: Vec::new(),
+            error: None,
+            frames: FrameDecoder::new(),
+            closed: false,
             chunk_size,
+            client: false,
+            player: None,
+            entity_id: None,
         }
     }
 
-    pub fn send_packet(&mut self, packet: &impl Packet) -> usize {
-        // Write and prepend packet buffer with its length
-        let mut buf = packet.serialize_with_id(&self.ver);
+    // Join through `session` as `profile` when the server asks for encryption
+    pub fn set_authentication(&mut self, session: SessionServer, profile: Profile) {
+        self.auth = Some((session, profile));
+    }
+
+    pub fn version(&self) -> ProtocolVersion {
+        self.ver
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
 
-        let mut final_buf = ByteBuf::new();
-        let mut total_len = 0;
-        let mut uncompressed_len = 0;
+    // None until the server first teleports us. Kept up to date with every
+    // teleport read and movement packet sent, if this end logged in.
+    pub fn player(&self) -> Option<&PlayerState> {
+        self.player.as_ref()
+    }
+
+    // None until Join Game, if this end logged in
+    pub fn entity_id(&self) -> Option<i32> {
+        self.entity_id
+    }
+
+    // What a clientbound packet read in the current state is
+    pub fn packet_id(&self, id: i32) -> PacketID {
+        registry::lookup(&self.ver, &self.state, &Direction::Clientbound, id)
+    }
 
-        if self.compression_enabled() {
-            // Compress the buffer and move it
-            if buf.len() >= self.compression.unwrap() as usize {
-                uncompressed_len = buf.len() as i32;
-                let mut out = vec![0_u8];
-                let mut compressor = flate2::Compress::new(flate2::Compression::fast(), true);
-                let _ =
-                    compressor.compress_vec(buf.as_slice(), &mut out, flate2::FlushCompress::None);
-                *buf = ByteBuf::from(out.as_slice());
+    // Sends Handshake and Login Start then answers the server's login packets.
+    // Play packets read together with Login Success are returned by the next
+    // `read_packets` call. Fails with Disconnected if the server kicks us.
+    pub fn login(
+        &mut self,
+        handshake: &Handshake,
+        login_start: &LoginStart,
+    ) -> Result<LoginSuccess> {
+        self.login_until(handshake, login_start, None)
+    }
+
+    // Like `login`, but fails with a timed out `Error::Io` if the server
+    // hasn't let us in after `timeout`
+    pub fn login_within(
+        &mut self,
+        handshake: &Handshake,
+        login_start: &LoginStart,
+        timeout: Duration,
+    ) -> Result<LoginSuccess> {
+        let deadline = Instant::now() + timeout;
+        let read_timeout = self.stream.read_timeout()?;
+        let success = self.login_until(handshake, login_start, Some(deadline));
+        self.stream.set_read_timeout(read_timeout)?;
+        success
+    }
+
+    fn login_until(
+        &mut self,
+        handshake: &Handshake,
+        login_start: &LoginStart,
+        deadline: Option<Instant>,
+    ) -> Result<LoginSuccess> {
+        self.client = true;
+        self.send_packet(handshake)?;
+        self.set_state(State::Login);
+        self.send_packet(login_start)?;
+
+        loop {
+            if let Some(deadline) = deadline {
+                self.wait_until(deadline, PacketID::LoginSuccess)?;
+            }
+            let pkts = self.read_packets()?;
+            if self.is_closed() {
+                return Err(Error::Closed);
+            }
+
+            let mut pkts = pkts.into_iter();
+            while let Some((id, mut buf)) = pkts.next() {
+                match registry::lookup(&self.ver, &State::Login, &Direction::Clientbound, id) {
+                    PacketID::EncryptionRequest => {
+                        let req = deserialize_new::<EncryptionRequest>(&mut buf, &self.ver)?;
+                        self.handle_encryption_request(&req)?;
+                    }
+                    PacketID::LoginSuccess => {
+                        self.pending = pkts.collect();
+                        return Ok(*deserialize_new::<LoginSuccess>(&mut buf, &self.ver)?);
+                    }
+                    PacketID::LoginDisconnect => {
+                        let disconnect = deserialize_new::<LoginDisconnect>(&mut buf, &self.ver)?;
+                        return Err(Error::Disconnected(disconnect.reason));
+                    }
+                    _ => {}
+                }
             }
-            total_len += len_varint(uncompressed_len);
         }
+    }
+
+    // Server list ping, the server closes the connection after the Pong.
+    // Fails with a timed out `Error::Io` if it takes longer than `timeout`.
+    pub fn ping_status(
+        &mut self,
+        address: &str,
+        port: u16,
+        timeout: Duration,
+    ) -> Result<StatusPing> {
+        let deadline = Instant::now() + timeout;
+        let read_timeout = self.stream.read_timeout()?;
+        let ping = self.ping_status_until(address, port, deadline);
+        self.stream.set_read_timeout(read_timeout)?;
+        ping
+    }
+
+    fn ping_status_until(
+        &mut self,
+        address: &str,
+        port: u16,
+        deadline: Instant,
+    ) -> Result<StatusPing> {
+        let handshake = Handshake {
+            protocol_version: self.ver as i32,
+            address: address.to_string(),
+            port,
+            next_state: LoginState::Status,
+        };
+        self.send_packet(&handshake)?;
+        self.set_state(State::Status);
+        self.send_packet(&StatusRequest {})?;
 
-        total_len += buf.len() as i32;
+        let mut buf = self.read_until(PacketID::StatusResponse, deadline)?;
+        let response = deserialize_new::<StatusResponse>(&mut buf, &self.ver)?;
+        let status = ServerStatus::from_json(&response.json)
+            .map_err(|e| Error::Protocol(format!("Bad status response: {}", e)))?;
 
-        final_buf.write_var_int(total_len);
-        if self.compression_enabled() {
-            final_buf.write_var_int(uncompressed_len);
+        let payload = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        let start = Instant::now();
+        self.send_packet(&Ping { payload })?;
+
+        let mut buf = self.read_until(PacketID::Pong, deadline)?;
+        let latency = start.elapsed();
+        if deserialize_new::<Pong>(&mut buf, &self.ver)?.payload != payload {
+            return Err(Error::Protocol(
+                "Pong payload does not match the Ping".to_string(),
+            ));
         }
-        final_buf.write_all(buf.as_slice()).unwrap();
 
-        self.send_buffer(&final_buf)
+        Ok(StatusPing { status, latency })
+    }
+
+    // Reads until a `kind` packet arrives, dropping everything else, or
+    // until `deadline` passes
+    fn read_until(&mut self, kind: PacketID, deadline: Instant) -> Result<ByteBuf> {
+        loop {
+            self.wait_until(deadline, kind)?;
+            let pkts = self.read_packets()?;
+            if self.is_closed() {
+                return Err(Error::Closed);
+            }
+
+            for (id, buf) in pkts {
+                if self.packet_id(id) == kind {
+                    return Ok(buf);
+                }
+            }
+        }
+    }
+
+    // Makes the next read give up at `deadline`, failing once it has passed
+    // without `kind` arriving
+    fn wait_until(&mut self, deadline: Instant, kind: PacketID) -> Result<()> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(Error::Io(io::Error::new(
+                ErrorKind::TimedOut,
+                format!("no {:?} in time", kind),
+            )));
+        }
+        // A zero timeout isn't allowed, it would mean blocking forever
+        self.set_read_timeout(Some(left.max(Duration::from_millis(1))))?;
+        Ok(())
+    }
+
+    // Login packets that change how the rest of the stream is read take
+    // effect immediately, they may share a read with the packets after them
+    fn update_state(&mut self, id: i32, buf: &ByteBuf) -> Result<()> {
+        if self.state == State::Play && self.client {
+            // Only our own bookkeeping, a packet it can't read is still
+            // returned for the caller to make sense of
+            let _ = self.track_received(id, buf);
+        }
+        if self.state != State::Login {
+            return Ok(());
+        }
+
+        match self.packet_id(id) {
+            PacketID::SetCompression => {
+                let set_compression =
+                    deserialize_new::<SetCompression>(&mut buf.clone(), &self.ver)?;
+                self.set_compression_threshold(set_compression.threshold);
+            }
+            PacketID::LoginSuccess => self.set_state(State::Play),
+            PacketID::LoginDisconnect => self.set_state(State::Disconnected),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_encryption_request(&mut self, req: &EncryptionRequest) -> Result<()> {
+        let public_key = PublicKey::from_der(&req.public_key)?;
+        let shared_secret = generate_shared_secret();
+
+        if let Some((session, profile)) = self.auth.as_ref() {
+            let hash = server_hash(&req.server_id, &shared_secret, &req.public_key);
+            session.join(profile, &hash)?;
+        }
+
+        let response = EncryptionResponse {
+            shared_secret: public_key.encrypt(&shared_secret)?,
+            verify_token: public_key.encrypt(&req.verify_token)?,
+        };
+        self.send_packet(&response)?;
+
+        // The server encrypts everything after it reads the response
+        self.enable_encryption(&shared_secret);
+        Ok(())
+    }
+
+    // Fails with `Error::Protocol` if the version we speak doesn't have the packet
+    pub fn send_packet<P: Packet>(&mut self, packet: &P) -> Result<usize> {
+        let buf = packet
+            .serialize_with_id(&self.ver)
+            .ok_or_else(|| Error::unsupported::<P>(&self.ver))?;
+        self.track_sent(&buf);
+        let frame = frame(*buf, self.compression);
+        self.send_buffer(&frame)
+    }
+
+    // Sends a packet we only have the id and body of, e.g. one read from
+    // another connection
+    pub fn send_raw(&mut self, id: i32, data: &[u8]) -> Result<usize> {
+        let mut buf = ByteBuf::new();
+        buf.write_var_int(id);
+        buf.write_all(data)?;
+        self.track_sent(&buf);
+        let frame = frame(buf, self.compression);
+        self.send_buffer(&frame)
+    }
+
+    fn track_received(&mut self, id: i32, buf: &ByteBuf) -> Result<()> {
+        match self.packet_id(id) {
+            PacketID::PlayerPositionAndLookCB => {
+                let teleport =
+                    deserialize_new::<PlayerPositionAndLook>(&mut buf.clone(), &self.ver)?;
+                self.player
+                    .get_or_insert_with(PlayerState::default)
+                    .teleport(&teleport);
+            }
+            // Every version starts it with our entity id
+            PacketID::JoinGame => {
+                self.entity_id = Some(i32::read_from(&mut buf.clone())?);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Keeps `player` where our movement packets say we are, `buf` starts
+    // with the packet id. A movement packet we can't read is still sent,
+    // the server decides what to make of it.
+    fn track_sent(&mut self, buf: &ByteBuf) {
+        if !self.client || self.state != State::Play {
+            return;
+        }
+        if let Some(player) = self.player.as_mut() {
+            let mut buf = buf.clone();
+            if let Ok(id) = buf.read_var_int() {
+                let kind = registry::lookup(&self.ver, &State::Play, &Direction::Serverbound, id);
+                let _ = player.update_sent(kind, &buf, &self.ver);
+            }
+        }
     }
 
     pub fn compression_enabled(&self) -> bool {
@@ -66,69 +400,551 @@ impl Connection {
     }
 
     pub fn set_compression_threshold(&mut self, threshold: i32) {
-        self.compression = Some(threshold);
+        self.compression = Compression::new(threshold);
     }
 
-    pub fn send_buffer(&mut self, buf: &ByteBuf) -> usize {
-        self.stream.write(buf.as_slice()).unwrap()
+    pub fn encryption_enabled(&self) -> bool {
+        self.cipher.is_some()
     }
 
-    fn read_packet(&self, len: i32, buf: &mut ByteBuf) -> (i32, ByteBuf) {
-        let mut compressed_len = 0;
-        let mut compressed_len_len = 0;
+    // Everything sent or read after this call is encrypted with the shared secret
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.cipher = Some(StreamCipher::new(shared_secret));
+    }
 
-        // Optionally read a compression value
-        if self.compression_enabled() {
-            compressed_len = buf.read_var_int().unwrap();
-            compressed_len_len = len_varint(compressed_len);
+    pub fn send_buffer(&mut self, buf: &ByteBuf) -> Result<usize> {
+        match self.cipher.as_mut() {
+            Some(cipher) => {
+                // A short write would desync the cipher, so write everything
+                let mut out = buf.as_slice().to_vec();
+                cipher.encrypt(&mut out);
+                self.stream.write_all(&out)?;
+                Ok(out.len())
+            }
+            None => {
+                self.stream.write_all(buf.as_slice())?;
+                Ok(buf.len())
+            }
         }
+    }
 
-        // Read off everything except the compressed length VarInt (if it's there)
-        let vec = buf.read_bytes((len - compressed_len_len) as usize).unwrap();
-
-        // This buffer contains PacketID + Data
-        let mut tmp_buf = ByteBuf::from(vec.as_slice());
+    // With a timeout `read_packets` returns nothing instead of blocking when
+    // no packet starts arriving in time, use `is_closed` to tell the two apart
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        Ok(self.stream.set_read_timeout(timeout)?)
+    }
 
-        if compressed_len > 0 {
-            let mut out = vec![0_u8; compressed_len as usize];
-            let mut decompressor = flate2::Decompress::new(true);
+    // Closes the connection for every clone of the stream too
+    pub fn shutdown(&self) -> Result<()> {
+        Ok(self.stream.shutdown(Shutdown::Both)?)
+    }
 
-            // zlib inflate into another slice
-            decompressor
-                .decompress_vec(vec.as_slice(), &mut out, flate2::FlushDecompress::None)
-                .unwrap();
+    // True once the other end has closed the connection
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
 
-            // Replace the compressed `tmp_buf` with its uncompressed counterpart
-            tmp_buf = ByteBuf::from(out.as_slice());
+    fn read_stream(&mut self, slice: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.stream.read(slice)?;
+        if let Some(cipher) = self.cipher.as_mut() {
+            cipher.decrypt(&mut slice[..n]);
         }
-
-        let id: i32 = tmp_buf.read_var_int().unwrap();
-        (id, tmp_buf)
+        Ok(n)
     }
 
-    pub fn read_packets(&mut self) -> Vec<(i32, ByteBuf)> {
+    // Reads whatever has arrived. Errors other than timeouts leave the stream
+    // unreadable, the connection should be dropped.
+    pub fn read_packets(&mut self) -> Result<Vec<(i32, ByteBuf)>> {
+        if !self.pending.is_empty() {
+            return Ok(std::mem::take(&mut self.pending));
+        }
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
         let mut slice = vec![0_u8; self.chunk_size as usize];
+        match self.read_stream(&mut slice) {
+            Ok(0) => self.closed = true,
+            Ok(n) => self.frames.push(&slice[..n]),
+            Err(e) => match e.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut => {}
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => self.closed = true,
+                _ => return Err(e.into()),
+            },
+        }
+
+        // Packets can be split over any number of reads, the rest of one
+        // that's still arriving is returned by a later call. Whatever came
+        // before a bad frame is returned first, the error with the next call.
         let mut packets = Vec::new();
-        match self.stream.read(&mut slice) {
-            Ok(n) => {
-                let mut buf = ByteBuf::from(&slice[..n]);
+        loop {
+            match self.next_packet() {
+                Ok(Some(packet)) => packets.push(packet),
+                Ok(None) => break,
+                Err(e) if packets.is_empty() => return Err(e),
+                Err(e) => {
+                    self.error = Some(e);
+                    break;
+                }
+            }
+        }
+        Ok(packets)
+    }
 
-                while !buf.end() {
-                    let len = buf.read_var_int().unwrap(); // total packet length
+    fn next_packet(&mut self) -> Result<Option<(i32, ByteBuf)>> {
+        let frame = match self.frames.next_frame().map_err(Error::Framing)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let (id, packet) = read_packet(&frame, self.compression)?;
+        self.update_state(id, &packet)?;
+        Ok(Some((id, packet)))
+    }
+}
 
-                    if !buf.has_readable_bytes(len as usize) {
-                        let mut rest = vec![0_u8; (len as usize) - buf.remaining()];
-                        self.stream.read_exact(rest.as_mut_slice()).unwrap();
-                        buf.write_all(rest.as_mut_slice()).unwrap();
-                    }
+#[cfg(test)]
+mod tests {
+    use crate::mc::*;
+    use crate::serialize::packet::clientbound::KeepAlive;
+    use crate::serialize::packet::serverbound::TeleportConfirm;
+    use crate::serialize::string::VarIntString;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
 
-                    packets.push(self.read_packet(len, &mut buf));
-                }
+    // Reads packets until at least `n` have arrived
+    fn read_n(conn: &mut Connection, n: usize) -> Vec<(i32, ByteBuf)> {
+        let mut pkts = Vec::new();
+        while pkts.len() < n {
+            pkts.append(&mut conn.read_packets().unwrap());
+        }
+        pkts
+    }
+
+    // Accepts one client and reads its Handshake and Login Start
+    fn accept_login(listener: TcpListener) -> Connection {
+        let (stream, _) = listener.accept().unwrap();
+        let mut conn =
+            Connection::from_stream(stream, ProtocolVersion::V_1_12_2, BufferSize::Medium);
+
+        let mut pkts = read_n(&mut conn, 2);
+        assert_eq!(PacketID::Handshake.id(&conn.ver).unwrap(), pkts[0].0);
+        assert_eq!(PacketID::LoginStart.id(&conn.ver).unwrap(), pkts[1].0);
+        let login_start =
+            deserialize_new::<LoginStart>(&mut pkts[1].1, &ProtocolVersion::V_1_12_2).unwrap();
+        assert_eq!("idler", login_start.username);
+        conn
+    }
+
+    fn login(addr: SocketAddr) -> (Connection, Result<LoginSuccess>) {
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        let handshake = Handshake {
+            protocol_version: 340,
+            address: "127.0.0.1".to_string(),
+            port: addr.port(),
+            next_state: LoginState::Login,
+        };
+        let login_start = LoginStart {
+            username: "idler".to_string(),
+            uuid: None,
+        };
+
+        let res = conn.login(&handshake, &login_start);
+        (conn, res)
+    }
+
+    fn login_success() -> LoginSuccess {
+        LoginSuccess {
+            uuid: "c0ffee00-0000-4000-8000-000000000000".to_string(),
+            username: "idler".to_string(),
+            properties: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn encrypted_login() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Fake server, validates the response and answers with Login Success
+        let server = thread::spawn(move || {
+            let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+            let der = private_key.to_public_key().to_public_key_der().unwrap();
+            let mut conn = accept_login(listener);
+
+            let req = EncryptionRequest {
+                server_id: "".to_string(),
+                public_key: der.as_bytes().to_vec(),
+                verify_token: vec![1, 2, 3, 4],
+            };
+            conn.send_packet(&req).unwrap();
+
+            let mut pkts = read_n(&mut conn, 1);
+            assert_eq!(
+                PacketID::EncryptionResponse.id(&conn.ver).unwrap(),
+                pkts[0].0
+            );
+            let response =
+                deserialize_new::<EncryptionResponse>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2)
+                    .unwrap();
+            let token = private_key
+                .decrypt(Pkcs1v15Encrypt, &response.verify_token)
+                .unwrap();
+            assert_eq!(req.verify_token, token);
+
+            let secret = private_key
+                .decrypt(Pkcs1v15Encrypt, &response.shared_secret)
+                .unwrap();
+            let mut shared_secret = [0_u8; 16];
+            shared_secret.copy_from_slice(&secret);
+            conn.enable_encryption(&shared_secret);
+            conn.send_packet(&login_success()).unwrap();
+        });
+
+        let (conn, res) = login(addr);
+        server.join().unwrap();
+
+        assert!(conn.encryption_enabled());
+        assert_eq!(State::Play, conn.state());
+        let success = res.unwrap();
+        assert_eq!(login_success().uuid, success.uuid);
+        assert_eq!(login_success().username, success.username);
+    }
+
+    #[test]
+    fn compressed_login_then_play() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut conn = accept_login(listener);
+            conn.send_packet(&SetCompression { threshold: 256 })
+                .unwrap();
+            conn.set_compression_threshold(256);
+            conn.send_packet(&login_success()).unwrap();
+            conn.send_packet(&KeepAlive { id: 1337 }).unwrap();
+        });
+
+        let (mut conn, res) = login(addr);
+        assert!(res.is_ok());
+        assert!(conn.compression_enabled());
+        assert_eq!(State::Play, conn.state());
+
+        // The keep alive is read as a play packet, even if it came with Login Success
+        let mut pkts = read_n(&mut conn, 1);
+        server.join().unwrap();
+        assert_eq!(PacketID::KeepAliveCB, conn.packet_id(pkts[0].0));
+        assert_eq!(
+            1337,
+            deserialize_new::<KeepAlive>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2)
+                .unwrap()
+                .id
+        );
+    }
+
+    #[test]
+    fn bad_compressed_frame_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
 
-                println!("size: {}, data: {}", n, hex::encode(&slice[..n]));
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Claims 300 bytes compressed, but isn't zlib
+            stream.write_all(&[6, 0xAC, 0x02, 1, 2, 3, 4]).unwrap();
+        });
+
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        conn.set_compression_threshold(256);
+        server.join().unwrap();
+
+        let res = (0..10).find_map(|_| conn.read_packets().err());
+        assert!(matches!(
+            res,
+            Some(Error::Compression(DeserializeError::Decompression))
+        ));
+    }
+
+    #[test]
+    fn packets_before_a_bad_frame_are_returned() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // An uncompressed keep alive, then a frame that isn't zlib
+            let mut keep_alive = vec![10, 0, 0x1F];
+            keep_alive.extend_from_slice(&7_i64.to_be_bytes());
+            stream.write_all(&keep_alive).unwrap();
+            stream.write_all(&[6, 0xAC, 0x02, 1, 2, 3, 4]).unwrap();
+        });
+
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        conn.set_compression_threshold(256);
+        conn.set_state(State::Play);
+        server.join().unwrap();
+
+        let mut pkts = read_n(&mut conn, 1);
+        assert_eq!(PacketID::KeepAliveCB, conn.packet_id(pkts[0].0));
+        assert_eq!(
+            7,
+            deserialize_new::<KeepAlive>(&mut pkts[0].1, &conn.ver)
+                .unwrap()
+                .id
+        );
+        assert!(matches!(
+            conn.read_packets(),
+            Err(Error::Compression(DeserializeError::Decompression))
+        ));
+    }
+
+    #[test]
+    fn tracked_packets_that_dont_decode_are_still_returned() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let ver = ProtocolVersion::V_1_12_2;
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = Connection::from_stream(stream, ver, BufferSize::Medium);
+            server.set_state(State::Play);
+            // A teleport cut short, then a keep alive
+            let teleport = PacketID::PlayerPositionAndLookCB.id(&ver).unwrap();
+            server.send_raw(teleport, &[0; 4]).unwrap();
+            server.send_packet(&KeepAlive { id: 1 }).unwrap();
+        });
+
+        let mut conn = Connection::new(addr.to_string(), ver, BufferSize::Medium).unwrap();
+        conn.client = true;
+        conn.set_state(State::Play);
+        server.join().unwrap();
+
+        let pkts = read_n(&mut conn, 2);
+        assert_eq!(PacketID::PlayerPositionAndLookCB, conn.packet_id(pkts[0].0));
+        assert_eq!(PacketID::KeepAliveCB, conn.packet_id(pkts[1].0));
+        assert_eq!(None, conn.player());
+    }
+
+    #[test]
+    fn frames_split_across_writes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Two frames, the first with a 2 byte length
+        let mut stream = ByteBuf::new();
+        for (id, len) in [(0x1F, 200), (0x1F, 8)] {
+            stream.write_var_int(len + 1);
+            stream.write_var_int(id);
+            stream.extend_from_slice(&vec![7; len as usize]);
+        }
+        let bytes = stream.as_slice().to_vec();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Split inside the first length, then inside the second frame
+            for piece in [&bytes[..1], &bytes[1..150], &bytes[150..205], &bytes[205..]] {
+                stream.write_all(piece).unwrap();
+                stream.flush().unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        let pkts = read_n(&mut conn, 2);
+        server.join().unwrap();
+        assert_eq!(2, pkts.len());
+        assert_eq!(200, pkts[0].1.remaining_slice().len());
+        assert_eq!(&[7; 8], pkts[1].1.remaining_slice());
+    }
+
+    #[test]
+    fn login_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut conn = accept_login(listener);
+            conn.send_packet(&LoginDisconnect {
+                reason: "{\"text\":\"Server is full\"}".to_string(),
+            })
+            .unwrap();
+        });
+
+        let (conn, res) = login(addr);
+        server.join().unwrap();
+        match res {
+            Err(Error::Disconnected(reason)) => {
+                assert_eq!("{\"text\":\"Server is full\"}", reason)
             }
-            Err(e) => std::panic::panic_any(e),
+            other => panic!("expected a disconnect, got {:?}", other.map(|_| ())),
         }
-        packets
+        assert_eq!(State::Disconnected, conn.state());
+    }
+
+    #[test]
+    fn status_ping() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn =
+                Connection::from_stream(stream, ProtocolVersion::V_1_12_2, BufferSize::Medium);
+
+            let mut pkts = read_n(&mut conn, 2);
+            let handshake =
+                deserialize_new::<Handshake>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2).unwrap();
+            assert_eq!(340, handshake.protocol_version);
+            assert_eq!(LoginState::Status, handshake.next_state);
+            assert_eq!(PacketID::StatusRequest.id(&conn.ver).unwrap(), pkts[1].0);
+
+            // Big enough to need more than one read
+            let favicon = format!("data:image/png;base64,{}", "A".repeat(8192));
+            let json = serde_json::json!({
+                "version": {"name": "1.12.2", "protocol": 340},
+                "players": {"max": 20, "online": 1, "sample": [
+                    {"name": "idler", "id": "c0ffee00-0000-4000-8000-000000000000"}
+                ]},
+                "description": {"text": "A Minecraft Server"},
+                "favicon": favicon,
+            });
+            conn.send_packet(&StatusResponse {
+                json: json.to_string(),
+            })
+            .unwrap();
+
+            let mut pkts = read_n(&mut conn, 1);
+            assert_eq!(PacketID::Ping.id(&conn.ver).unwrap(), pkts[0].0);
+            let ping = deserialize_new::<Ping>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2).unwrap();
+            conn.send_packet(&Pong {
+                payload: ping.payload,
+            })
+            .unwrap();
+        });
+
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        let ping = conn
+            .ping_status("127.0.0.1", addr.port(), Duration::from_secs(5))
+            .unwrap();
+        server.join().unwrap();
+
+        assert_eq!(State::Status, conn.state());
+        assert_eq!(340, ping.status.version.protocol);
+        assert_eq!(1, ping.status.players.online);
+        assert_eq!(20, ping.status.players.max);
+        assert_eq!("idler", ping.status.players.sample[0].name);
+        assert_eq!("A Minecraft Server", ping.status.description["text"]);
+        assert_eq!(8214, ping.status.favicon.unwrap().len());
+    }
+
+    #[test]
+    fn silent_servers_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        // Accepted, but never answered
+        let (_stream, _) = listener.accept().unwrap();
+
+        let start = Instant::now();
+        let e = conn
+            .ping_status("127.0.0.1", addr.port(), Duration::from_millis(100))
+            .unwrap_err();
+        assert!(e.is_timeout(), "{}", e);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn login_with_each_version() {
+        for ver in ProtocolVersion::ALL.iter().copied() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut conn = Connection::from_stream(stream, ver, BufferSize::Medium);
+                let mut pkts = read_n(&mut conn, 2);
+                let handshake = deserialize_new::<Handshake>(&mut pkts[0].1, &ver).unwrap();
+                assert_eq!(ver as i32, handshake.protocol_version);
+                assert_eq!(PacketID::LoginStart.id(&ver).unwrap(), pkts[1].0);
+                let login_start = deserialize_new::<LoginStart>(&mut pkts[1].1, &ver).unwrap();
+                assert_eq!("idler", login_start.username);
+
+                conn.send_packet(&login_success()).unwrap();
+                conn.send_packet(&KeepAlive { id: 99 }).unwrap();
+            });
+
+            let mut conn = Connection::new(addr.to_string(), ver, BufferSize::Medium).unwrap();
+            let handshake = Handshake {
+                protocol_version: ver as i32,
+                address: "127.0.0.1".to_string(),
+                port: addr.port(),
+                next_state: LoginState::Login,
+            };
+            let login_start = LoginStart {
+                username: "idler".to_string(),
+                uuid: Some(1),
+            };
+            let success = conn.login(&handshake, &login_start).unwrap();
+            assert_eq!(login_success().uuid, success.uuid);
+
+            let mut pkts = read_n(&mut conn, 1);
+            assert_eq!(PacketID::KeepAliveCB, conn.packet_id(pkts[0].0));
+            assert_eq!(
+                99,
+                deserialize_new::<KeepAlive>(&mut pkts[0].1, &ver)
+                    .unwrap()
+                    .id
+            );
+            server.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn missing_packets_are_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut conn = Connection::new(
+            addr.to_string(),
+            ProtocolVersion::V_1_8_9,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        // 1.8 has no teleport ids to confirm
+        let e = conn
+            .send_packet(&TeleportConfirm { teleport_id: 1 })
+            .unwrap_err();
+        assert_eq!(
+            "Protocol error: TeleportConfirm does not exist in 1.8.9",
+            e.to_string()
+        );
     }
 }
@@ -0,0 +1,179 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::packet::clientbound::{self, RELATIVE_PITCH, RELATIVE_X, RELATIVE_Y};
+use crate::serialize::packet::clientbound::{RELATIVE_YAW, RELATIVE_Z};
+use crate::serialize::packet::{deserialize_new, serverbound, PacketID};
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::var::DeserializeError;
+
+// Where the server last put us or we last said we moved to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerState {
+    pub x: f64,
+    pub y: f64, // Feet
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+    pub teleport_id: i32, // Of the last teleport, 0 in 1.8
+}
+
+impl PlayerState {
+    // Moves to where a Player Position And Look says, fields with their flag
+    // set are relative to where we are
+    pub fn teleport(&mut self, teleport: &clientbound::PlayerPositionAndLook) {
+        let base = |flag, current| {
+            if teleport.flags & flag != 0 {
+                current
+            } else {
+                0.0
+            }
+        };
+        self.x = base(RELATIVE_X, self.x) + teleport.x;
+        self.y = base(RELATIVE_Y, self.y) + teleport.y;
+        self.z = base(RELATIVE_Z, self.z) + teleport.z;
+        self.yaw = base(RELATIVE_YAW, self.yaw as f64) as f32 + teleport.yaw;
+        self.pitch = base(RELATIVE_PITCH, self.pitch as f64) as f32 + teleport.pitch;
+        self.teleport_id = teleport.teleport_id;
+    }
+
+    // Follows a serverbound movement packet, whoever sent it
+    pub fn update_sent(
+        &mut self,
+        kind: PacketID,
+        buf: &ByteBuf,
+        ver: &ProtocolVersion,
+    ) -> Result<(), DeserializeError> {
+        let mut fields = ByteBuf::from(buf.remaining_slice());
+        match kind {
+            PacketID::PlayerPosition => {
+                let moved = deserialize_new::<serverbound::PlayerPosition>(&mut fields, ver)?;
+                self.x = moved.x;
+                self.y = moved.y;
+                self.z = moved.z;
+                self.on_ground = moved.on_ground;
+            }
+            PacketID::PlayerPositionAndLookSB => {
+                let moved =
+                    deserialize_new::<serverbound::PlayerPositionAndLook>(&mut fields, ver)?;
+                self.x = moved.x;
+                self.y = moved.y;
+                self.z = moved.z;
+                self.yaw = moved.yaw;
+                self.pitch = moved.pitch;
+                self.on_ground = moved.on_ground;
+            }
+            PacketID::PlayerLook => {
+                let look = deserialize_new::<serverbound::PlayerLook>(&mut fields, ver)?;
+                self.yaw = look.yaw;
+                self.pitch = look.pitch;
+                self.on_ground = look.on_ground;
+            }
+            PacketID::Player => {
+                self.on_ground =
+                    deserialize_new::<serverbound::Player>(&mut fields, ver)?.on_ground;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn position(&self) -> serverbound::PlayerPosition {
+        serverbound::PlayerPosition {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            on_ground: self.on_ground,
+        }
+    }
+
+    pub fn position_and_look(&self) -> serverbound::PlayerPositionAndLook {
+        serverbound::PlayerPositionAndLook {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            on_ground: self.on_ground,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::*;
+    use crate::serialize::packet::PacketSerializer;
+
+    #[test]
+    fn relative_teleports() {
+        let mut player = PlayerState::default();
+        player.teleport(&clientbound::PlayerPositionAndLook {
+            x: 10.5,
+            y: 64.0,
+            z: -3.5,
+            yaw: 90.0,
+            pitch: 10.0,
+            flags: 0,
+            teleport_id: 1,
+        });
+        assert_eq!((10.5, 64.0, -3.5), (player.x, player.y, player.z));
+
+        // Only y and yaw are relative
+        player.teleport(&clientbound::PlayerPositionAndLook {
+            x: 0.0,
+            y: 1.5,
+            z: 2.0,
+            yaw: -45.0,
+            pitch: 0.0,
+            flags: RELATIVE_Y | RELATIVE_YAW,
+            teleport_id: 2,
+        });
+        assert_eq!((0.0, 65.5, 2.0), (player.x, player.y, player.z));
+        assert_eq!((45.0, 0.0), (player.yaw, player.pitch));
+        assert_eq!(2, player.teleport_id);
+    }
+
+    #[test]
+    fn follows_sent_movement() {
+        let ver = ProtocolVersion::V_1_12_2;
+        let mut player = PlayerState::default();
+        let mut buf = ByteBuf::new();
+        serverbound::PlayerLook {
+            yaw: 180.0,
+            pitch: -90.0,
+            on_ground: true,
+        }
+        .serialize(&mut buf, &ver);
+        player
+            .update_sent(PacketID::PlayerLook, &buf, &ver)
+            .unwrap();
+        assert_eq!(
+            (180.0, -90.0, true),
+            (player.yaw, player.pitch, player.on_ground)
+        );
+
+        let mut buf = ByteBuf::new();
+        serverbound::PlayerPosition {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+            on_ground: false,
+        }
+        .serialize(&mut buf, &ver);
+        player
+            .update_sent(PacketID::PlayerPosition, &buf, &ver)
+            .unwrap();
+        assert_eq!((1.0, 2.0, 3.0), (player.x, player.y, player.z));
+        assert_eq!(180.0, player.position_and_look().yaw);
+
+        // Other packets are ignored, broken movement isn't
+        player
+            .update_sent(PacketID::ChatMessageSB, &buf, &ver)
+            .unwrap();
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            player
+                .update_sent(PacketID::PlayerPositionAndLookSB, &buf, &ver)
+                .unwrap_err()
+        );
+    }
+}
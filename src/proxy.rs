@@ -0,0 +1,691 @@
+use crate::cache::WorldCache;
+use crate::entity::Entities;
+use crate::error::Result;
+use crate::mc::{BufferSize, Connection};
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::packet::clientbound::{
+    Disconnect, LoginDisconnect, LoginSuccess, SetCompression,
+};
+use crate::serialize::packet::serverbound::{Handshake, LoginStart, LoginState, TeleportConfirm};
+use crate::serialize::packet::{deserialize_new, PacketID};
+use crate::serialize::protocol::{ProtocolVersion, State};
+use crate::serialize::registry::{self, Direction};
+use crate::supervisor::Event;
+use crate::tablist::TabList;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+// What happened on the client side of the proxy
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProxyEvent {
+    Attached(String), // The client's username
+    Detached,
+    Lost(String), // Why the client was dropped
+}
+
+enum ClientEvent {
+    Attached(Box<Connection>, String), // Writing half and the client's username
+    Packet(i32, ByteBuf),
+    Detached,
+}
+
+// Lets a vanilla client take over the idling session. The client logs in to
+// the proxy in offline mode, compressed if a threshold is given, and is then handed every
+// clientbound packet, while its own packets are sent upstream. Only one
+// client can be attached at a time.
+pub struct Proxy {
+    addr: SocketAddr,
+    ver: ProtocolVersion,
+    events: Receiver<ClientEvent>,
+    client: Option<Connection>,
+    // The teleport the client was put in place with, the server already had
+    // it confirmed
+    replayed_teleport: Option<i32>,
+    subscribers: Vec<Sender<ProxyEvent>>,
+}
+
+impl Proxy {
+    // `profile` is the upstream Login Success, the attaching client is told
+    // it logged in as that player. Packets at least `compression` bytes long
+    // are compressed, None leaves them all uncompressed.
+    pub fn bind(
+        addr: &str,
+        ver: ProtocolVersion,
+        profile: LoginSuccess,
+        compression: Option<i32>,
+    ) -> Result<Proxy> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            let attached = Arc::new(AtomicBool::new(false));
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                let attached = attached.clone();
+                let profile = profile.clone();
+                thread::spawn(move || {
+                    serve_client(stream, ver, profile, compression, attached, tx)
+                });
+            }
+        });
+
+        Ok(Proxy {
+            addr,
+            ver,
+            events: rx,
+            client: None,
+            replayed_teleport: None,
+            subscribers: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // While a client is attached it answers keep alives, not the idler
+    pub fn attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<ProxyEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    // Relays a clientbound packet to the attached client, if there is one
+    pub fn forward_to_client(&mut self, id: i32, data: &[u8]) {
+        if let Some(client) = self.client.as_mut() {
+            if let Err(e) = client.send_raw(id, data) {
+                self.drop_client(e.to_string());
+            }
+        }
+    }
+
+    // Tells the attached client what happened upstream. Losing the server
+    // kicks the client, the world it was sent is gone.
+    pub fn notify(&mut self, event: &Event) {
+        if let Event::Disconnected(reason) = event {
+            if let Some(mut client) = self.client.take() {
+                let text = format!("Lost connection to the server: {}", reason);
+                let _ = client.send_packet(&Disconnect {
+                    reason: serde_json::json!({ "text": text }).to_string(),
+                });
+                let _ = client.shutdown();
+            }
+        }
+    }
+
+    // The client's thread sees the connection close and lets the next
+    // client attach
+    fn drop_client(&mut self, reason: String) {
+        if let Some(client) = self.client.take() {
+            let _ = client.shutdown();
+            self.emit(ProxyEvent::Lost(reason));
+        }
+    }
+
+    fn emit(&mut self, event: ProxyEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    // Handles everything the client side did since the last call, sending
+    // the client's packets to `upstream`. A newly attached client is sent
+    // the cached world first, with `entities` and `tab_list` as
+    // `WorldCache::replay` takes them. Errors are from sending to `upstream`.
+    pub fn forward_to_server(
+        &mut self,
+        upstream: &mut Connection,
+        cache: &WorldCache,
+        entities: &Entities,
+        tab_list: &TabList,
+    ) -> Result<()> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                ClientEvent::Attached(mut client, username) => {
+                    let player = upstream.player();
+                    let replayed = cache
+                        .replay(player, entities, tab_list)
+                        .iter()
+                        .try_for_each(|(id, data)| client.send_raw(*id, data).map(|_| ()));
+                    self.client = Some(*client);
+                    match replayed {
+                        Ok(()) => {
+                            self.replayed_teleport = player.map(|player| player.teleport_id);
+                            self.emit(ProxyEvent::Attached(username));
+                        }
+                        Err(e) => self.drop_client(e.to_string()),
+                    }
+                }
+                ClientEvent::Packet(id, buf) => {
+                    if self.client.is_some() && !self.is_replayed_confirm(id, &buf) {
+                        upstream.send_raw(id, buf.remaining_slice())?;
+                    }
+                }
+                ClientEvent::Detached => {
+                    if self.client.take().is_some() {
+                        self.emit(ProxyEvent::Detached);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Drops the client's confirm of the replayed teleport, once
+    fn is_replayed_confirm(&mut self, id: i32, buf: &ByteBuf) -> bool {
+        if registry::lookup(&self.ver, &State::Play, &Direction::Serverbound, id)
+            != PacketID::TeleportConfirm
+        {
+            return false;
+        }
+        let confirm = deserialize_new::<TeleportConfirm>(&mut buf.clone(), &self.ver);
+        match confirm {
+            Ok(confirm) if Some(confirm.teleport_id) == self.replayed_teleport => {
+                self.replayed_teleport = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn serve_client(
+    stream: TcpStream,
+    ver: ProtocolVersion,
+    profile: LoginSuccess,
+    compression: Option<i32>,
+    attached: Arc<AtomicBool>,
+    tx: Sender<ClientEvent>,
+) {
+    let writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let mut conn = Connection::from_stream(stream, ver, BufferSize::Medium);
+    let mut writer = Connection::from_stream(writer, ver, BufferSize::Medium);
+
+    let (protocol, username) = match read_login(&mut conn, &ver) {
+        Some(login) => login,
+        None => return,
+    };
+
+    // Packets are relayed as they are, both ends must speak the same version
+    if protocol != ver as i32 {
+        let text = format!(
+            "This session is on {}, connect with that version",
+            ver.name()
+        );
+        let _ = writer.send_packet(&LoginDisconnect {
+            reason: serde_json::json!({ "text": text }).to_string(),
+        });
+        return;
+    }
+
+    if attached.swap(true, Ordering::SeqCst) {
+        let _ = writer.send_packet(&LoginDisconnect {
+            reason: "{\"text\":\"Someone is already attached to this session\"}".to_string(),
+        });
+        return;
+    }
+
+    if let Some(threshold) = compression {
+        if writer.send_packet(&SetCompression { threshold }).is_err() {
+            attached.store(false, Ordering::SeqCst);
+            return;
+        }
+        conn.set_compression_threshold(threshold);
+        writer.set_compression_threshold(threshold);
+    }
+    if writer.send_packet(&profile).is_err() {
+        attached.store(false, Ordering::SeqCst);
+        return;
+    }
+    conn.set_state(State::Play);
+    writer.set_state(State::Play);
+    if tx
+        .send(ClientEvent::Attached(Box::new(writer), username))
+        .is_err()
+    {
+        return;
+    }
+
+    // A client that sends garbage is treated as gone
+    while let Ok(pkts) = conn.read_packets() {
+        if conn.is_closed() {
+            break;
+        }
+        for (id, buf) in pkts {
+            if tx.send(ClientEvent::Packet(id, buf)).is_err() {
+                return;
+            }
+        }
+    }
+
+    // Released after Detached is queued, so the next client's Attached
+    // can't arrive before it
+    let _ = tx.send(ClientEvent::Detached);
+    attached.store(false, Ordering::SeqCst);
+}
+
+// Reads the client's Handshake and Login Start, returning its protocol
+// version and username. Anything but a login attempt is dropped.
+fn read_login(conn: &mut Connection, ver: &ProtocolVersion) -> Option<(i32, String)> {
+    let mut protocol = 0;
+    let mut pkts = Vec::new();
+    while pkts.len() < 2 {
+        pkts.append(&mut conn.read_packets().ok()?);
+        if conn.is_closed() {
+            return None;
+        }
+
+        // A client pinging the proxy sends Handshake then Request
+        if let Some((id, buf)) = pkts.first_mut() {
+            if registry::lookup(ver, &State::Handshaking, &Direction::Serverbound, *id)
+                != PacketID::Handshake
+            {
+                return None;
+            }
+            let handshake = deserialize_new::<Handshake>(&mut buf.clone(), ver).ok()?;
+            if handshake.next_state != LoginState::Login {
+                return None;
+            }
+            protocol = handshake.protocol_version;
+        }
+    }
+
+    let (id, buf) = &mut pkts[1];
+    if registry::lookup(ver, &State::Login, &Direction::Serverbound, *id) != PacketID::LoginStart {
+        return None;
+    }
+    let username = deserialize_new::<LoginStart>(buf, ver).ok()?.username;
+    Some((protocol, username))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proxy::*;
+    use crate::serialize::packet::clientbound::{KeepAlive, PlayerPositionAndLook};
+    use crate::serialize::packet::serverbound;
+    use crate::serialize::string::WriteString;
+    use std::time::Duration;
+
+    fn profile() -> LoginSuccess {
+        LoginSuccess {
+            uuid: "c0ffee00-0000-4000-8000-000000000000".to_string(),
+            username: "idler".to_string(),
+            properties: Vec::new(),
+        }
+    }
+
+    fn connect_client(proxy: &Proxy, username: &str) -> (Connection, Result<LoginSuccess>) {
+        let mut client = Connection::new(
+            proxy.local_addr().to_string(),
+            ProtocolVersion::V_1_12_2,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        let handshake = Handshake {
+            protocol_version: 340,
+            address: "127.0.0.1".to_string(),
+            port: proxy.local_addr().port(),
+            next_state: LoginState::Login,
+        };
+        let login_start = LoginStart {
+            username: username.to_string(),
+            uuid: None,
+        };
+        let res = client.login(&handshake, &login_start);
+        (client, res)
+    }
+
+    // Fake upstream server with `upstream` being our connection to it
+    fn upstream_pair() -> (Connection, Connection) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let ver = ProtocolVersion::V_1_12_2;
+        let upstream = Connection::new(
+            listener.local_addr().unwrap().to_string(),
+            ver,
+            BufferSize::Medium,
+        )
+        .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut server = Connection::from_stream(stream, ver, BufferSize::Medium);
+        server.set_state(State::Play);
+        (upstream, server)
+    }
+
+    // With no entities or tab list beyond what's in `cache`
+    fn forward(proxy: &mut Proxy, upstream: &mut Connection, cache: &WorldCache) {
+        let ver = ProtocolVersion::V_1_12_2;
+        proxy
+            .forward_to_server(upstream, cache, &Entities::new(ver), &TabList::new(ver))
+            .unwrap();
+    }
+
+    fn wait_for_attach(proxy: &mut Proxy, upstream: &mut Connection, cache: &WorldCache) {
+        while !proxy.attached() {
+            forward(proxy, upstream, cache);
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn relays_both_directions() {
+        let mut proxy =
+            Proxy::bind("127.0.0.1:0", ProtocolVersion::V_1_12_2, profile(), None).unwrap();
+        let (mut upstream, mut server) = upstream_pair();
+        let cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        let events = proxy.subscribe();
+        assert!(!proxy.attached());
+
+        let (mut client, res) = connect_client(&proxy, "player");
+        let success = res.unwrap();
+        assert_eq!(profile().uuid, success.uuid);
+        assert_eq!(profile().username, success.username);
+        assert_eq!(State::Play, client.state());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+
+        // Server -> proxy -> client
+        server.send_packet(&KeepAlive { id: 42 }).unwrap();
+        let mut pkts = upstream.read_packets().unwrap();
+        proxy.forward_to_client(pkts[0].0, pkts[0].1.remaining_slice());
+        let mut relayed = client.read_packets().unwrap();
+        assert_eq!(PacketID::KeepAliveCB, client.packet_id(relayed[0].0));
+        assert_eq!(
+            42,
+            deserialize_new::<KeepAlive>(&mut relayed[0].1, &ProtocolVersion::V_1_12_2)
+                .unwrap()
+                .id
+        );
+        assert_eq!(
+            42,
+            deserialize_new::<KeepAlive>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2)
+                .unwrap()
+                .id
+        );
+
+        // Client -> proxy -> server
+        client
+            .send_packet(&serverbound::KeepAlive { id: 42 })
+            .unwrap();
+        let mut pkts = Vec::new();
+        while pkts.is_empty() {
+            forward(&mut proxy, &mut upstream, &cache);
+            server
+                .set_read_timeout(Some(Duration::from_millis(5)))
+                .unwrap();
+            pkts = server.read_packets().unwrap();
+        }
+        assert_eq!(
+            PacketID::KeepAliveSB,
+            registry::lookup(
+                &ProtocolVersion::V_1_12_2,
+                &State::Play,
+                &Direction::Serverbound,
+                pkts[0].0
+            )
+        );
+        assert_eq!(
+            42,
+            deserialize_new::<serverbound::KeepAlive>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2)
+                .unwrap()
+                .id
+        );
+
+        // Detaching hands keep alives back to the idler
+        drop(client);
+        while proxy.attached() {
+            forward(&mut proxy, &mut upstream, &cache);
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            vec![
+                ProxyEvent::Attached("player".to_string()),
+                ProxyEvent::Detached
+            ],
+            events.try_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn clients_can_be_compressed() {
+        let mut proxy = Proxy::bind(
+            "127.0.0.1:0",
+            ProtocolVersion::V_1_12_2,
+            profile(),
+            Some(64),
+        )
+        .unwrap();
+        let (mut upstream, _server) = upstream_pair();
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        let join_game = ByteBuf::from(
+            &[
+                0, 0, 5, 57, 0, 0, 0, 0, 0, 2, 20, 7, 100, 101, 102, 97, 117, 108, 116, 0,
+            ][..],
+        );
+        cache
+            .update(PacketID::JoinGame, join_game.as_slice())
+            .unwrap();
+        // Over the threshold, Join Game isn't
+        let mut header = ByteBuf::new();
+        let text = serde_json::json!({ "text": "x".repeat(200) }).to_string();
+        header.write_string(&text);
+        header.write_string(&text);
+        cache
+            .update(PacketID::PlayerListHeaderAndFooter, header.as_slice())
+            .unwrap();
+
+        let (mut client, res) = connect_client(&proxy, "player");
+        assert!(res.is_ok());
+        assert!(client.compression_enabled());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+
+        let mut pkts = Vec::new();
+        while pkts.len() < 2 {
+            pkts.append(&mut client.read_packets().unwrap());
+        }
+        assert_eq!(join_game.as_slice(), pkts[0].1.remaining_slice());
+        assert_eq!(
+            PacketID::PlayerListHeaderAndFooter,
+            client.packet_id(pkts[1].0)
+        );
+        assert_eq!(header.as_slice(), pkts[1].1.remaining_slice());
+    }
+
+    #[test]
+    fn one_client_at_a_time() {
+        let mut proxy =
+            Proxy::bind("127.0.0.1:0", ProtocolVersion::V_1_12_2, profile(), None).unwrap();
+        let (mut upstream, _server) = upstream_pair();
+        let cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+
+        let (_first, res) = connect_client(&proxy, "first");
+        assert!(res.is_ok());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+
+        let (second, res) = connect_client(&proxy, "second");
+        assert!(res.unwrap_err().to_string().contains("already attached"));
+        assert_eq!(State::Disconnected, second.state());
+    }
+
+    #[test]
+    fn attaching_client_gets_the_cached_world() {
+        let mut proxy =
+            Proxy::bind("127.0.0.1:0", ProtocolVersion::V_1_12_2, profile(), None).unwrap();
+        let (mut upstream, _server) = upstream_pair();
+
+        let mut cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+        let join_game = ByteBuf::from(
+            &[
+                0, 0, 5, 57, 0, 0, 0, 0, 0, 2, 20, 7, 100, 101, 102, 97, 117, 108, 116, 0,
+            ][..],
+        );
+        cache
+            .update(PacketID::JoinGame, join_game.as_slice())
+            .unwrap();
+        cache.update(PacketID::TimeUpdate, &[0_u8; 16]).unwrap();
+
+        let (mut client, res) = connect_client(&proxy, "player");
+        assert!(res.is_ok());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+
+        let mut pkts = Vec::new();
+        while pkts.len() < 2 {
+            pkts.append(&mut client.read_packets().unwrap());
+        }
+        assert_eq!(PacketID::JoinGame, client.packet_id(pkts[0].0));
+        assert_eq!(join_game.as_slice(), pkts[0].1.remaining_slice());
+        assert_eq!(PacketID::TimeUpdate, client.packet_id(pkts[1].0));
+    }
+
+    #[test]
+    fn replayed_teleport_is_not_confirmed_upstream() {
+        let ver = ProtocolVersion::V_1_12_2;
+        let mut proxy = Proxy::bind("127.0.0.1:0", ver, profile(), None).unwrap();
+        let mut cache = WorldCache::new(ver);
+        let join_game = ByteBuf::from(
+            &[
+                0, 0, 5, 57, 0, 0, 0, 0, 0, 2, 20, 7, 100, 101, 102, 97, 117, 108, 116, 0,
+            ][..],
+        );
+        cache
+            .update(PacketID::JoinGame, join_game.as_slice())
+            .unwrap();
+
+        // Our position is only tracked on a connection that logged in
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let login = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = Connection::from_stream(stream, ver, BufferSize::Medium);
+            let mut pkts = Vec::new();
+            while pkts.len() < 2 {
+                pkts.append(&mut server.read_packets().unwrap());
+            }
+            server.send_packet(&profile()).unwrap();
+            server.set_state(State::Play);
+            let teleport = PlayerPositionAndLook {
+                x: 8.5,
+                y: 65.0,
+                teleport_id: 3,
+                ..PlayerPositionAndLook::default()
+            };
+            server.send_packet(&teleport).unwrap();
+            server
+        });
+        let mut upstream = Connection::new(addr.to_string(), ver, BufferSize::Medium).unwrap();
+        let handshake = Handshake {
+            protocol_version: 340,
+            address: "127.0.0.1".to_string(),
+            port: addr.port(),
+            next_state: LoginState::Login,
+        };
+        let login_start = LoginStart {
+            username: "idler".to_string(),
+            uuid: None,
+        };
+        upstream.login(&handshake, &login_start).unwrap();
+        let mut server = login.join().unwrap();
+        while upstream.player().is_none() {
+            upstream.read_packets().unwrap();
+        }
+
+        let (mut client, res) = connect_client(&proxy, "player");
+        assert!(res.is_ok());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+        let mut pkts = Vec::new();
+        while pkts.len() < 2 {
+            pkts.append(&mut client.read_packets().unwrap());
+        }
+        assert_eq!(
+            PacketID::PlayerPositionAndLookCB,
+            client.packet_id(pkts[1].0)
+        );
+        let teleport = deserialize_new::<PlayerPositionAndLook>(&mut pkts[1].1, &ver).unwrap();
+        assert_eq!(
+            (8.5, 65.0, 3),
+            (teleport.x, teleport.y, teleport.teleport_id)
+        );
+
+        // The client's confirm stays with the proxy, what follows goes through
+        for teleport_id in [3, 3] {
+            client
+                .send_packet(&TeleportConfirm { teleport_id })
+                .unwrap();
+        }
+        let mut pkts = Vec::new();
+        while pkts.is_empty() {
+            forward(&mut proxy, &mut upstream, &cache);
+            server
+                .set_read_timeout(Some(Duration::from_millis(5)))
+                .unwrap();
+            pkts = server.read_packets().unwrap();
+        }
+        assert_eq!(1, pkts.len());
+        let confirm = deserialize_new::<TeleportConfirm>(&mut pkts[0].1, &ver).unwrap();
+        assert_eq!(3, confirm.teleport_id);
+    }
+
+    #[test]
+    fn client_is_kicked_when_upstream_is_lost() {
+        let mut proxy =
+            Proxy::bind("127.0.0.1:0", ProtocolVersion::V_1_12_2, profile(), None).unwrap();
+        let (mut upstream, _server) = upstream_pair();
+        let cache = WorldCache::new(ProtocolVersion::V_1_12_2);
+
+        let (mut client, res) = connect_client(&proxy, "player");
+        assert!(res.is_ok());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+
+        proxy.notify(&Event::Reconnecting(1, Duration::from_secs(1)));
+        assert!(proxy.attached());
+        proxy.notify(&Event::Disconnected("Server restarting".to_string()));
+        assert!(!proxy.attached());
+
+        let mut pkts = Vec::new();
+        while pkts.is_empty() {
+            pkts = client.read_packets().unwrap();
+        }
+        assert_eq!(PacketID::Disconnect, client.packet_id(pkts[0].0));
+        let kick =
+            deserialize_new::<Disconnect>(&mut pkts[0].1, &ProtocolVersion::V_1_12_2).unwrap();
+        assert!(kick.reason.contains("Server restarting"));
+
+        // The kicked client's connection is closed, so someone else can
+        // attach while it's still around
+        while !client.is_closed() {
+            client.read_packets().unwrap();
+        }
+        let (_next, res) = connect_client(&proxy, "next");
+        assert!(res.is_ok());
+        wait_for_attach(&mut proxy, &mut upstream, &cache);
+    }
+
+    #[test]
+    fn clients_on_another_version_are_kicked() {
+        let proxy = Proxy::bind("127.0.0.1:0", ProtocolVersion::V_1_12_2, profile(), None).unwrap();
+        let ver = ProtocolVersion::V_1_8_9;
+        let mut client =
+            Connection::new(proxy.local_addr().to_string(), ver, BufferSize::Medium).unwrap();
+        let handshake = Handshake {
+            protocol_version: ver as i32,
+            address: "127.0.0.1".to_string(),
+            port: proxy.local_addr().port(),
+            next_state: LoginState::Login,
+        };
+        let login_start = LoginStart {
+            username: "player".to_string(),
+            uuid: None,
+        };
+        let err = client.login(&handshake, &login_start).unwrap_err();
+        assert!(err.to_string().contains("This session is on 1.12.2"));
+        assert!(!proxy.attached());
+    }
+}
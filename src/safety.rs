@@ -0,0 +1,137 @@
+use crate::entity::Entities;
+use crate::health::Health;
+use crate::player::PlayerState;
+use crate::serialize::uuid;
+use std::fmt;
+
+// When to leave the server rather than risk the account while nobody's
+// watching. Everything is off by default.
+#[derive(Clone, Debug, Default)]
+pub struct SafetyPolicy {
+    pub min_health: Option<f32>,   // Leave when health drops below this
+    pub player_range: Option<f64>, // Leave when a player not on the whitelist is this close
+    pub whitelist: Vec<u128>,      // UUIDs of players that can come close
+}
+
+// Why the policy says to leave
+#[derive(Clone, Debug, PartialEq)]
+pub enum Danger {
+    LowHealth(f32),
+    Player(u128, f64), // Who and how many blocks away
+}
+
+impl fmt::Display for Danger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Danger::LowHealth(health) => write!(f, "Health dropped to {}", health),
+            Danger::Player(id, distance) => write!(
+                f,
+                "Player {} came within {:.1} blocks",
+                uuid::to_string(*id),
+                distance
+            ),
+        }
+    }
+}
+
+impl SafetyPolicy {
+    pub fn check_health(&self, health: &Health) -> Option<Danger> {
+        match self.min_health {
+            Some(min) if health.health() < min => Some(Danger::LowHealth(health.health())),
+            _ => None,
+        }
+    }
+
+    // Another player `distance` blocks away
+    pub fn check_player(&self, id: u128, distance: f64) -> Option<Danger> {
+        match self.player_range {
+            Some(range) if distance <= range && !self.whitelist.contains(&id) => {
+                Some(Danger::Player(id, distance))
+            }
+            _ => None,
+        }
+    }
+
+    // The closest player too close to `player`, if any
+    pub fn check_nearby(&self, entities: &Entities, player: &PlayerState) -> Option<Danger> {
+        let range = self.player_range?;
+        entities
+            .players_near(player.x, player.y, player.z, range)
+            .into_iter()
+            .find_map(|other| {
+                self.check_player(other.uuid, other.distance_to(player.x, player.y, player.z))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::safety::*;
+    use crate::serialize::packet::clientbound::{EntityTeleport, SpawnPlayer, UpdateHealth};
+    use crate::serialize::packet::{Packet, PacketID};
+    use crate::serialize::protocol::ProtocolVersion;
+
+    const FRIEND: u128 = 0xc0ffee00_0000_4000_8000_000000000001;
+    const STRANGER: u128 = 0xc0ffee00_0000_4000_8000_000000000002;
+
+    #[test]
+    fn danger_needs_a_limit() {
+        let ver = ProtocolVersion::V_1_12_2;
+        let mut health = Health::new(ver);
+        let update = UpdateHealth {
+            health: 5.0,
+            food: 20,
+            saturation: 0.0,
+        };
+        health
+            .update(PacketID::UpdateHealth, &update.body(&ver))
+            .unwrap();
+
+        let off = SafetyPolicy::default();
+        assert_eq!(None, off.check_health(&health));
+        assert_eq!(None, off.check_player(STRANGER, 0.0));
+
+        let policy = SafetyPolicy {
+            min_health: Some(6.0),
+            player_range: Some(16.0),
+            whitelist: vec![FRIEND],
+        };
+        assert_eq!(Some(Danger::LowHealth(5.0)), policy.check_health(&health));
+        assert_eq!(None, policy.check_player(FRIEND, 2.0));
+        assert_eq!(None, policy.check_player(STRANGER, 16.5));
+        let danger = policy.check_player(STRANGER, 16.0).unwrap();
+        assert_eq!(
+            "Player c0ffee00-0000-4000-8000-000000000002 came within 16.0 blocks",
+            danger.to_string()
+        );
+
+        // Only the stranger is a danger, once they're close enough
+        let mut entities = Entities::new(ver);
+        let me = PlayerState::default();
+        for (id, uuid, x) in [(1, FRIEND, 1.0), (2, STRANGER, 20.0)] {
+            let spawn = SpawnPlayer {
+                entity_id: id,
+                uuid,
+                x,
+                ..SpawnPlayer::default()
+            };
+            entities
+                .update(PacketID::SpawnPlayer, &spawn.body(&ver))
+                .unwrap();
+        }
+        assert_eq!(None, policy.check_nearby(&entities, &me));
+        let teleport = EntityTeleport {
+            entity_id: 2,
+            x: 3.0,
+            ..EntityTeleport::default()
+        };
+        entities
+            .update(PacketID::EntityTeleport, &teleport.body(&ver))
+            .unwrap();
+        assert_eq!(
+            Some(Danger::Player(STRANGER, 3.0)),
+            policy.check_nearby(&entities, &me)
+        );
+        assert_eq!(None, off.check_nearby(&entities, &me));
+    }
+}
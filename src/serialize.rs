@@ -1,6 +1,15 @@
 pub mod buffer;
 pub mod bytes;
+pub mod compression;
+pub mod field;
+pub mod frame;
+pub mod metadata;
+pub mod nbt;
 pub mod packet;
+pub mod position;
 pub mod protocol;
+pub mod registry;
+pub mod slot;
 pub mod string;
+pub mod uuid;
 pub mod var;
@@ -2,7 +2,7 @@ use crate::serialize::string::VarIntString;
 use crate::serialize::var::{DeserializeError, VarIntReader, VarIntWriter};
 use std::io::{Read, Write};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ByteBuf {
     vec: Vec<u8>,
     read_idx: usize,
@@ -10,8 +10,7 @@ pub struct ByteBuf {
 
 impl From<&[u8]> for ByteBuf {
     fn from(slice: &[u8]) -> Self {
-        let mut v = Vec::new();
-        v.resize(slice.len(), 0_u8);
+        let mut v = vec![0_u8; slice.len()];
         v.copy_from_slice(slice);
         ByteBuf {
             vec: v,
@@ -65,14 +64,18 @@ impl ByteBuf {
         if self.read_idx + len > self.len() {
             None
         } else {
-            let mut dest = Vec::new();
-            dest.resize(len, 0);
+            let mut dest = vec![0_u8; len];
             dest.copy_from_slice(&self.vec.as_slice()[self.read_idx..self.read_idx + len]);
             self.read_idx += len;
             Some(dest)
         }
     }
 
+    // Everything that hasn't been read yet
+    pub fn remaining_slice(&self) -> &[u8] {
+        &self.vec.as_slice()[self.read_idx..]
+    }
+
     pub fn end(&self) -> bool {
         self.remaining() == 0
     }
@@ -84,6 +87,10 @@ impl ByteBuf {
     pub fn len(&self) -> usize {
         self.vec.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
 }
 
 impl VarIntString for ByteBuf {
@@ -108,10 +115,13 @@ impl Write for ByteBuf {
 }
 
 impl Read for ByteBuf {
+    // Reads what's left if that's less than `buf`, so read_exact fails
+    // instead of panicking on a short buffer
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        buf.copy_from_slice(&self.vec.as_slice()[self.read_idx..self.read_idx + buf.len()]);
-        self.read_idx += buf.len();
-        Ok(buf.len())
+        let len = buf.len().min(self.remaining());
+        buf[..len].copy_from_slice(&self.vec.as_slice()[self.read_idx..self.read_idx + len]);
+        self.read_idx += len;
+        Ok(len)
     }
 }
 
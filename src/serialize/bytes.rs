@@ -1,5 +1,6 @@
 use crate::serialize::buffer::*;
 use crate::serialize::string::VarIntString;
+use crate::serialize::var::{DeserializeError, VarIntReader};
 
 pub trait WriteBytes {
     fn write_bytes(&mut self, value: &[u8]);
@@ -10,3 +11,33 @@ impl WriteBytes for ByteBuf {
         self.extend_from_slice(value);
     }
 }
+
+// Byte arrays prefixed with their length as a VarInt
+pub trait WriteByteArray: VarIntString {
+    fn write_byte_array(&mut self, value: &[u8]);
+}
+
+pub trait ReadByteArray: VarIntString {
+    fn read_byte_array(&mut self) -> Result<Vec<u8>, DeserializeError>;
+}
+
+impl<T> WriteByteArray for T
+where
+    T: VarIntString,
+{
+    fn write_byte_array(&mut self, value: &[u8]) {
+        self.write_var_int(value.len() as i32);
+        self.extend_from_slice(value);
+    }
+}
+
+impl ReadByteArray for ByteBuf {
+    fn read_byte_array(&mut self) -> Result<Vec<u8>, DeserializeError> {
+        let len = self.read_var_int()?;
+        if len < 0 {
+            return Err(DeserializeError::InvalidLength);
+        }
+        self.read_bytes(len as usize)
+            .ok_or(DeserializeError::BufferTooSmall)
+    }
+}
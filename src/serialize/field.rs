@@ -0,0 +1,165 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::position::Position;
+use crate::serialize::slot::{ReadSlot, Slot, WriteSlot};
+use crate::serialize::string::{ReadString, WriteString};
+use crate::serialize::var::{DeserializeError, VarIntReader, VarIntWriter};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+// A packet field with a single wire format, see `#[derive(Packet)]`
+pub trait Field: Sized {
+    fn write_to(&self, buf: &mut ByteBuf);
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError>;
+}
+
+// Big endian numbers
+macro_rules! number_field {
+    ($ty:ty, $write:ident, $read:ident) => {
+        impl Field for $ty {
+            fn write_to(&self, buf: &mut ByteBuf) {
+                buf.$write::<BigEndian>(*self).unwrap();
+            }
+
+            fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+                Ok(buf.$read::<BigEndian>()?)
+            }
+        }
+    };
+}
+
+number_field!(u16, write_u16, read_u16);
+number_field!(i16, write_i16, read_i16);
+number_field!(i32, write_i32, read_i32);
+number_field!(i64, write_i64, read_i64);
+number_field!(u128, write_u128, read_u128); // UUIDs
+number_field!(f32, write_f32, read_f32);
+number_field!(f64, write_f64, read_f64);
+
+impl Field for u8 {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        buf.write_u8(*self).unwrap();
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        Ok(buf.read_u8()?)
+    }
+}
+
+impl Field for i8 {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        buf.write_i8(*self).unwrap();
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        Ok(buf.read_i8()?)
+    }
+}
+
+// Anything but 0 is true, like vanilla
+impl Field for bool {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        buf.write_u8(*self as u8).unwrap();
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        Ok(buf.read_u8()? != 0)
+    }
+}
+
+impl Field for String {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        buf.write_string(self);
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        buf.read_string()
+    }
+}
+
+impl Field for Position {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        buf.write_i64::<BigEndian>(self.packed()).unwrap();
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        Ok(Position::from_packed(buf.read_i64::<BigEndian>()?))
+    }
+}
+
+impl Field for Option<Slot> {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        buf.write_slot(self.as_ref());
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        buf.read_slot()
+    }
+}
+
+// A VarInt count then the elements, `#[prefixed]`
+pub fn write_prefixed<T: Field>(values: &[T], buf: &mut ByteBuf) {
+    buf.write_var_int(values.len() as i32);
+    for value in values {
+        value.write_to(buf);
+    }
+}
+
+pub fn read_prefixed<T: Field>(buf: &mut ByteBuf) -> Result<Vec<T>, DeserializeError> {
+    let count = buf.read_var_int()?;
+    if count < 0 {
+        return Err(DeserializeError::InvalidLength);
+    }
+    // Not preallocated, the count can be anything
+    let mut values = Vec::new();
+    for _ in 0..count {
+        values.push(T::read_from(buf)?);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::field::*;
+
+    #[test]
+    fn fields_round_trip() {
+        let mut buf = ByteBuf::new();
+        true.write_to(&mut buf);
+        (-2_i8).write_to(&mut buf);
+        25565_u16.write_to(&mut buf);
+        1.5_f32.write_to(&mut buf);
+        "hi".to_string().write_to(&mut buf);
+        Position { x: -1, y: 64, z: 2 }.write_to(&mut buf);
+        write_prefixed(&[1_i16, -1], &mut buf);
+        assert_eq!(1 + 1 + 2 + 4 + 3 + 8 + 5, buf.len());
+
+        assert!(bool::read_from(&mut buf).unwrap());
+        assert_eq!(-2, i8::read_from(&mut buf).unwrap());
+        assert_eq!(25565, u16::read_from(&mut buf).unwrap());
+        assert_eq!(1.5, f32::read_from(&mut buf).unwrap());
+        assert_eq!("hi", String::read_from(&mut buf).unwrap());
+        assert_eq!(
+            Position { x: -1, y: 64, z: 2 },
+            Position::read_from(&mut buf).unwrap()
+        );
+        assert_eq!(vec![1_i16, -1], read_prefixed::<i16>(&mut buf).unwrap());
+        assert!(buf.end());
+    }
+
+    #[test]
+    fn bad_prefixes_are_errors() {
+        let mut buf = ByteBuf::new();
+        buf.write_var_int(-1);
+        assert_eq!(
+            DeserializeError::InvalidLength,
+            read_prefixed::<u8>(&mut buf).unwrap_err()
+        );
+
+        let mut buf = ByteBuf::new();
+        buf.write_var_int(i32::MAX);
+        buf.write_u8(1).unwrap();
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            read_prefixed::<u8>(&mut buf).unwrap_err()
+        );
+    }
+}
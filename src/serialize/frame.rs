@@ -0,0 +1,190 @@
+use crate::serialize::var::DeserializeError;
+
+// Vanilla's frame lengths are VarInts of at most 3 bytes
+const MAX_LENGTH_BYTES: usize = 3;
+
+// Splits a stream of length prefixed frames back into frames, whatever
+// pieces it arrives in. Bytes that aren't a whole frame yet are kept for
+// the next call.
+#[derive(Default)]
+pub struct FrameDecoder {
+    received: Vec<u8>,
+    start: usize, // Everything before this has been returned
+}
+
+impl FrameDecoder {
+    pub fn new() -> FrameDecoder {
+        FrameDecoder {
+            received: Vec::new(),
+            start: 0,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        // Drop what's been returned once it's most of the buffer
+        if self.start > 0 && self.start >= self.received.len() / 2 {
+            self.received.drain(..self.start);
+            self.start = 0;
+        }
+        self.received.extend_from_slice(data);
+    }
+
+    // Bytes waiting to become a frame
+    pub fn buffered(&self) -> usize {
+        self.received.len() - self.start
+    }
+
+    // The next complete frame without its length, None until all of it has
+    // been pushed. Errors mean the stream can't be read any further.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, DeserializeError> {
+        loop {
+            let (len, header) = match self.frame_len()? {
+                Some(len) => len,
+                None => return Ok(None),
+            };
+            if self.buffered() < header + len {
+                return Ok(None);
+            }
+
+            let start = self.start + header;
+            self.start = start + len;
+            // Vanilla ignores empty frames
+            if len > 0 {
+                return Ok(Some(self.received[start..start + len].to_vec()));
+            }
+        }
+    }
+
+    // Length of the next frame and of the VarInt in front of it
+    fn frame_len(&self) -> Result<Option<(usize, usize)>, DeserializeError> {
+        let mut len = 0;
+        for (i, b) in self.received[self.start..].iter().enumerate() {
+            if i == MAX_LENGTH_BYTES {
+                return Err(DeserializeError::VarIntTooBig);
+            }
+            len |= ((b & 0x7F) as usize) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(Some((len, i + 1)));
+            }
+        }
+        match self.buffered() {
+            MAX_LENGTH_BYTES => Err(DeserializeError::VarIntTooBig),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::buffer::ByteBuf;
+    use crate::serialize::frame::*;
+    use crate::serialize::string::VarIntString;
+    use crate::serialize::var::VarIntWriter;
+
+    // Frames with 1, 2 and 3 byte lengths, and an empty one
+    fn frames() -> Vec<Vec<u8>> {
+        [1, 5, 127, 0, 128, 300, 16384, 2]
+            .iter()
+            .enumerate()
+            .map(|(i, len)| (0..*len).map(|b| (b * 7 + i) as u8).collect())
+            .collect()
+    }
+
+    fn stream(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = ByteBuf::new();
+        for frame in frames {
+            buf.write_var_int(frame.len() as i32);
+            buf.extend_from_slice(frame);
+        }
+        buf.as_slice().to_vec()
+    }
+
+    fn drain(decoder: &mut FrameDecoder, out: &mut Vec<Vec<u8>>) {
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            out.push(frame);
+        }
+    }
+
+    fn non_empty(frames: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        frames.into_iter().filter(|f| !f.is_empty()).collect()
+    }
+
+    #[test]
+    fn split_at_every_offset() {
+        let frames = frames();
+        let stream = stream(&frames);
+
+        for split in 0..=stream.len() {
+            let mut decoder = FrameDecoder::new();
+            let mut out = Vec::new();
+            decoder.push(&stream[..split]);
+            drain(&mut decoder, &mut out);
+            decoder.push(&stream[split..]);
+            drain(&mut decoder, &mut out);
+
+            assert_eq!(non_empty(frames.clone()), out, "split at {}", split);
+            assert_eq!(0, decoder.buffered());
+        }
+    }
+
+    #[test]
+    fn split_at_every_pair_of_offsets() {
+        // Small frames so every pair can be tried
+        let frames: Vec<Vec<u8>> = vec![vec![1], vec![2; 130], vec![], vec![3, 4, 5]];
+        let stream = stream(&frames);
+
+        for a in 0..=stream.len() {
+            for b in a..=stream.len() {
+                let mut decoder = FrameDecoder::new();
+                let mut out = Vec::new();
+                for piece in [&stream[..a], &stream[a..b], &stream[b..]] {
+                    decoder.push(piece);
+                    drain(&mut decoder, &mut out);
+                }
+                assert_eq!(non_empty(frames.clone()), out, "split at {} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        let frames = frames();
+        let mut decoder = FrameDecoder::new();
+        let mut out = Vec::new();
+        for b in stream(&frames) {
+            decoder.push(&[b]);
+            drain(&mut decoder, &mut out);
+        }
+        assert_eq!(non_empty(frames), out);
+    }
+
+    #[test]
+    fn partial_frame_is_kept() {
+        let stream = stream(&[vec![9; 10], vec![8; 10]]);
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&stream[..15]);
+        assert_eq!(Some(vec![9; 10]), decoder.next_frame().unwrap());
+        assert_eq!(None, decoder.next_frame().unwrap());
+        assert_eq!(4, decoder.buffered());
+
+        decoder.push(&stream[15..]);
+        assert_eq!(Some(vec![8; 10]), decoder.next_frame().unwrap());
+    }
+
+    #[test]
+    fn rejects_lengths_over_21_bits() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&[0xFF, 0xFF]);
+        assert_eq!(None, decoder.next_frame().unwrap());
+        decoder.push(&[0xFF]);
+        assert_eq!(
+            DeserializeError::VarIntTooBig,
+            decoder.next_frame().unwrap_err()
+        );
+
+        // The largest length is fine, it just hasn't all arrived
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&[0xFF, 0xFF, 0x7F]);
+        assert_eq!(None, decoder.next_frame().unwrap());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::field::Field;
+use crate::serialize::nbt::{ReadNbt, Tag, WriteNbt, TAG_END};
+use crate::serialize::position::Position;
+use crate::serialize::slot::{ReadSlot, Slot, WriteSlot};
+use crate::serialize::string::{ReadString, WriteString};
+use crate::serialize::var::{DeserializeError, VarIntReader, VarIntWriter};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+// Ends the list of entries
+const END: u8 = 0xFF;
+
+// An entity metadata value in the 1.12.2 format. What each index means
+// depends on the entity, e.g. 7 is a living entity's health.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(i8),
+    VarInt(i32),
+    Float(f32),
+    String(String),
+    Chat(String), // JSON chat component
+    Slot(Option<Slot>),
+    Boolean(bool),
+    Rotation(f32, f32, f32),
+    Position(Position),
+    OptPosition(Option<Position>),
+    Direction(i32), // Down, up, north, south, west, east
+    OptUuid(Option<u128>),
+    BlockId(i32), // 0 for none, otherwise id << 4 | data
+    Nbt(Option<Tag>),
+}
+
+impl Value {
+    fn type_id(&self) -> i32 {
+        match self {
+            Value::Byte(_) => 0,
+            Value::VarInt(_) => 1,
+            Value::Float(_) => 2,
+            Value::String(_) => 3,
+            Value::Chat(_) => 4,
+            Value::Slot(_) => 5,
+            Value::Boolean(_) => 6,
+            Value::Rotation(..) => 7,
+            Value::Position(_) => 8,
+            Value::OptPosition(_) => 9,
+            Value::Direction(_) => 10,
+            Value::OptUuid(_) => 11,
+            Value::BlockId(_) => 12,
+            Value::Nbt(_) => 13,
+        }
+    }
+
+    fn write(&self, buf: &mut ByteBuf) {
+        match self {
+            Value::Byte(v) => buf.write_i8(*v).unwrap(),
+            Value::VarInt(v) | Value::Direction(v) | Value::BlockId(v) => buf.write_var_int(*v),
+            Value::Float(v) => v.write_to(buf),
+            Value::String(v) | Value::Chat(v) => buf.write_string(v),
+            Value::Slot(v) => buf.write_slot(v.as_ref()),
+            Value::Boolean(v) => v.write_to(buf),
+            Value::Rotation(x, y, z) => {
+                for v in [x, y, z] {
+                    v.write_to(buf);
+                }
+            }
+            Value::Position(v) => v.write_to(buf),
+            Value::OptPosition(v) => {
+                v.is_some().write_to(buf);
+                if let Some(v) = v {
+                    v.write_to(buf);
+                }
+            }
+            Value::OptUuid(v) => {
+                v.is_some().write_to(buf);
+                if let Some(v) = v {
+                    v.write_to(buf);
+                }
+            }
+            // Sent as none if it's too long to write, like a slot's NBT
+            Value::Nbt(Some(tag)) => {
+                if buf.write_nbt("", tag).is_err() {
+                    buf.write_u8(TAG_END).unwrap();
+                }
+            }
+            Value::Nbt(None) => buf.write_u8(TAG_END).unwrap(),
+        }
+    }
+
+    fn read(type_id: i32, buf: &mut ByteBuf) -> Result<Value, DeserializeError> {
+        Ok(match type_id {
+            0 => Value::Byte(buf.read_i8()?),
+            1 => Value::VarInt(buf.read_var_int()?),
+            2 => Value::Float(buf.read_f32::<BigEndian>()?),
+            3 => Value::String(buf.read_string()?),
+            4 => Value::Chat(buf.read_string()?),
+            5 => Value::Slot(buf.read_slot()?),
+            6 => Value::Boolean(bool::read_from(buf)?),
+            7 => Value::Rotation(
+                f32::read_from(buf)?,
+                f32::read_from(buf)?,
+                f32::read_from(buf)?,
+            ),
+            8 => Value::Position(Position::read_from(buf)?),
+            9 => match bool::read_from(buf)? {
+                true => Value::OptPosition(Some(Position::read_from(buf)?)),
+                false => Value::OptPosition(None),
+            },
+            10 => Value::Direction(buf.read_var_int()?),
+            11 => match bool::read_from(buf)? {
+                true => Value::OptUuid(Some(u128::read_from(buf)?)),
+                false => Value::OptUuid(None),
+            },
+            12 => Value::BlockId(buf.read_var_int()?),
+            13 => Value::Nbt(buf.read_nbt()?.map(|(_, tag)| tag)),
+            _ => return Err(DeserializeError::InvalidValue),
+        })
+    }
+}
+
+// Entries by index in the order they were sent, an index sent again
+// replaces the earlier value
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub entries: Vec<(u8, Value)>,
+}
+
+impl Metadata {
+    pub fn get(&self, index: u8) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, value)| value)
+    }
+
+    pub fn set(&mut self, index: u8, value: Value) {
+        match self.entries.iter_mut().find(|(i, _)| *i == index) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((index, value)),
+        }
+    }
+
+    // Applies an Entity Metadata update
+    pub fn merge(&mut self, other: Metadata) {
+        for (index, value) in other.entries {
+            self.set(index, value);
+        }
+    }
+}
+
+impl Field for Metadata {
+    fn write_to(&self, buf: &mut ByteBuf) {
+        for (index, value) in &self.entries {
+            buf.write_u8(*index).unwrap();
+            buf.write_var_int(value.type_id());
+            value.write(buf);
+        }
+        buf.write_u8(END).unwrap();
+    }
+
+    fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+        let mut metadata = Metadata::default();
+        loop {
+            let index = buf.read_u8()?;
+            if index == END {
+                return Ok(metadata);
+            }
+            let type_id = buf.read_var_int()?;
+            metadata.set(index, Value::read(type_id, buf)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::metadata::*;
+
+    #[test]
+    fn metadata_round_trips() {
+        let mut metadata = Metadata::default();
+        metadata.set(0, Value::Byte(0x02)); // Sneaking
+        metadata.set(2, Value::String("Steve".to_string()));
+        metadata.set(7, Value::Float(20.0));
+        metadata.set(
+            6,
+            Value::Slot(Some(Slot {
+                item_id: 1,
+                count: 64,
+                ..Slot::default()
+            })),
+        );
+        metadata.set(12, Value::OptUuid(Some(0xc0ffee)));
+        metadata.set(13, Value::OptPosition(None));
+        metadata.set(14, Value::Nbt(None));
+        metadata.set(7, Value::Float(12.5));
+
+        let mut buf = ByteBuf::new();
+        metadata.write_to(&mut buf);
+        let read = Metadata::read_from(&mut buf).unwrap();
+        assert!(buf.end());
+        assert_eq!(metadata, read);
+        assert_eq!(7, read.entries.len());
+        assert_eq!(Some(&Value::Float(12.5)), read.get(7));
+
+        let mut update = Metadata::default();
+        update.set(0, Value::Byte(0));
+        let mut merged = read.clone();
+        merged.merge(update);
+        assert_eq!(Some(&Value::Byte(0)), merged.get(0));
+        assert_eq!(read.get(2), merged.get(2));
+    }
+
+    #[test]
+    fn unknown_types_are_errors() {
+        let mut buf = ByteBuf::new();
+        buf.write_u8(0).unwrap();
+        buf.write_var_int(14);
+        assert_eq!(
+            DeserializeError::InvalidValue,
+            Metadata::read_from(&mut buf).unwrap_err()
+        );
+
+        // Missing its end
+        let mut buf = ByteBuf::new();
+        buf.write_u8(0).unwrap();
+        buf.write_var_int(0);
+        buf.write_i8(1).unwrap();
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            Metadata::read_from(&mut buf).unwrap_err()
+        );
+    }
+}
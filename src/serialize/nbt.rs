@@ -0,0 +1,542 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::bytes::WriteBytes;
+use crate::serialize::var::DeserializeError;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use std::io::{Read, Write};
+
+pub const TAG_END: u8 = 0;
+pub const TAG_BYTE: u8 = 1;
+pub const TAG_SHORT: u8 = 2;
+pub const TAG_INT: u8 = 3;
+pub const TAG_LONG: u8 = 4;
+pub const TAG_FLOAT: u8 = 5;
+pub const TAG_DOUBLE: u8 = 6;
+pub const TAG_BYTE_ARRAY: u8 = 7;
+pub const TAG_STRING: u8 = 8;
+pub const TAG_LIST: u8 = 9;
+pub const TAG_COMPOUND: u8 = 10;
+pub const TAG_INT_ARRAY: u8 = 11;
+pub const TAG_LONG_ARRAY: u8 = 12;
+
+// Same limit as vanilla
+const MAX_DEPTH: usize = 512;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(u8, Vec<Tag>),           // Element type, empty lists still have one
+    Compound(Vec<(String, Tag)>), // In the order they were read
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+// Anything short is truncated data
+fn eof(_: std::io::Error) -> DeserializeError {
+    DeserializeError::BufferTooSmall
+}
+
+// An array length, which must fit in what's left of `buf` at `size` bytes
+// per element
+fn read_len(buf: &mut ByteBuf, size: usize) -> Result<usize, DeserializeError> {
+    let len = buf.read_i32::<BigEndian>().map_err(eof)?;
+    if len < 0 {
+        return Err(DeserializeError::InvalidLength);
+    }
+    if !buf.has_readable_bytes(len as usize * size) {
+        return Err(DeserializeError::BufferTooSmall);
+    }
+    Ok(len as usize)
+}
+
+// Strings are Java's modified UTF-8: NUL takes two bytes and characters
+// outside the BMP are written as two three byte surrogates
+fn read_string(buf: &mut ByteBuf) -> Result<String, DeserializeError> {
+    let len = buf.read_u16::<BigEndian>().map_err(eof)? as usize;
+    let bytes = buf
+        .read_bytes(len)
+        .ok_or(DeserializeError::BufferTooSmall)?;
+
+    let mut units = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < len {
+        let b = bytes[i] as u16;
+        let (unit, width) = if b & 0x80 == 0 {
+            (b, 1)
+        } else if b & 0xE0 == 0xC0 && i + 1 < len {
+            ((b & 0x1F) << 6 | (bytes[i + 1] as u16 & 0x3F), 2)
+        } else if b & 0xF0 == 0xE0 && i + 2 < len {
+            let unit =
+                (b & 0x0F) << 12 | (bytes[i + 1] as u16 & 0x3F) << 6 | (bytes[i + 2] as u16 & 0x3F);
+            (unit, 3)
+        } else {
+            return Err(DeserializeError::InvalidString);
+        };
+        if bytes[i + 1..i + width].iter().any(|b| b & 0xC0 != 0x80) {
+            return Err(DeserializeError::InvalidString);
+        }
+        units.push(unit);
+        i += width;
+    }
+    String::from_utf16(&units).map_err(|_| DeserializeError::InvalidString)
+}
+
+// Fails with `InvalidLength` if it's over the 65535 bytes its length can say
+fn write_string(buf: &mut ByteBuf, value: &str) -> Result<(), DeserializeError> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00..=0x7FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | (unit >> 6 & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    if bytes.len() > u16::MAX as usize {
+        return Err(DeserializeError::InvalidLength);
+    }
+    buf.write_u16::<BigEndian>(bytes.len() as u16).unwrap();
+    buf.write_bytes(&bytes);
+    Ok(())
+}
+
+impl Tag {
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => TAG_BYTE,
+            Tag::Short(_) => TAG_SHORT,
+            Tag::Int(_) => TAG_INT,
+            Tag::Long(_) => TAG_LONG,
+            Tag::Float(_) => TAG_FLOAT,
+            Tag::Double(_) => TAG_DOUBLE,
+            Tag::ByteArray(_) => TAG_BYTE_ARRAY,
+            Tag::String(_) => TAG_STRING,
+            Tag::List(_, _) => TAG_LIST,
+            Tag::Compound(_) => TAG_COMPOUND,
+            Tag::IntArray(_) => TAG_INT_ARRAY,
+            Tag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    // Looks up a tag in a compound
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(tags) => tags.iter().find(|(n, _)| n == name).map(|(_, tag)| tag),
+            _ => None,
+        }
+    }
+
+    fn read(buf: &mut ByteBuf, id: u8, depth: usize) -> Result<Tag, DeserializeError> {
+        if depth > MAX_DEPTH {
+            return Err(DeserializeError::TooDeep);
+        }
+
+        let tag = match id {
+            TAG_BYTE => Tag::Byte(buf.read_i8().map_err(eof)?),
+            TAG_SHORT => Tag::Short(buf.read_i16::<BigEndian>().map_err(eof)?),
+            TAG_INT => Tag::Int(buf.read_i32::<BigEndian>().map_err(eof)?),
+            TAG_LONG => Tag::Long(buf.read_i64::<BigEndian>().map_err(eof)?),
+            TAG_FLOAT => Tag::Float(buf.read_f32::<BigEndian>().map_err(eof)?),
+            TAG_DOUBLE => Tag::Double(buf.read_f64::<BigEndian>().map_err(eof)?),
+            TAG_BYTE_ARRAY => {
+                let len = read_len(buf, 1)?;
+                let mut values = vec![0; len];
+                buf.read_i8_into(&mut values).map_err(eof)?;
+                Tag::ByteArray(values)
+            }
+            TAG_STRING => Tag::String(read_string(buf)?),
+            TAG_LIST => {
+                let kind = buf.read_u8().map_err(eof)?;
+                let len = read_len(buf, 0)?;
+                // Vanilla only writes TAG_End as the type of empty lists
+                if kind == TAG_END && len > 0 {
+                    return Err(DeserializeError::InvalidLength);
+                }
+                let mut tags = Vec::new();
+                for _ in 0..len {
+                    tags.push(Tag::read(buf, kind, depth + 1)?);
+                }
+                Tag::List(kind, tags)
+            }
+            TAG_COMPOUND => {
+                let mut tags = Vec::new();
+                loop {
+                    let id = buf.read_u8().map_err(eof)?;
+                    if id == TAG_END {
+                        break;
+                    }
+                    let name = read_string(buf)?;
+                    tags.push((name, Tag::read(buf, id, depth + 1)?));
+                }
+                Tag::Compound(tags)
+            }
+            TAG_INT_ARRAY => {
+                let len = read_len(buf, 4)?;
+                let mut values = vec![0; len];
+                buf.read_i32_into::<BigEndian>(&mut values).map_err(eof)?;
+                Tag::IntArray(values)
+            }
+            TAG_LONG_ARRAY => {
+                let len = read_len(buf, 8)?;
+                let mut values = vec![0; len];
+                buf.read_i64_into::<BigEndian>(&mut values).map_err(eof)?;
+                Tag::LongArray(values)
+            }
+            id => return Err(DeserializeError::UnknownTag(id)),
+        };
+        Ok(tag)
+    }
+
+    fn write(&self, buf: &mut ByteBuf) -> Result<(), DeserializeError> {
+        match self {
+            Tag::Byte(v) => buf.write_i8(*v).unwrap(),
+            Tag::Short(v) => buf.write_i16::<BigEndian>(*v).unwrap(),
+            Tag::Int(v) => buf.write_i32::<BigEndian>(*v).unwrap(),
+            Tag::Long(v) => buf.write_i64::<BigEndian>(*v).unwrap(),
+            Tag::Float(v) => buf.write_f32::<BigEndian>(*v).unwrap(),
+            Tag::Double(v) => buf.write_f64::<BigEndian>(*v).unwrap(),
+            Tag::ByteArray(values) => {
+                buf.write_i32::<BigEndian>(values.len() as i32).unwrap();
+                for v in values {
+                    buf.write_i8(*v).unwrap();
+                }
+            }
+            Tag::String(v) => write_string(buf, v)?,
+            Tag::List(kind, tags) => {
+                buf.write_u8(*kind).unwrap();
+                buf.write_i32::<BigEndian>(tags.len() as i32).unwrap();
+                for tag in tags {
+                    tag.write(buf)?;
+                }
+            }
+            Tag::Compound(tags) => {
+                for (name, tag) in tags {
+                    buf.write_u8(tag.id()).unwrap();
+                    write_string(buf, name)?;
+                    tag.write(buf)?;
+                }
+                buf.write_u8(TAG_END).unwrap();
+            }
+            Tag::IntArray(values) => {
+                buf.write_i32::<BigEndian>(values.len() as i32).unwrap();
+                for v in values {
+                    buf.write_i32::<BigEndian>(*v).unwrap();
+                }
+            }
+            Tag::LongArray(values) => {
+                buf.write_i32::<BigEndian>(values.len() as i32).unwrap();
+                for v in values {
+                    buf.write_i64::<BigEndian>(*v).unwrap();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// NBT in packets and files is a root tag with a name
+pub trait ReadNbt {
+    // None if there is only a TAG_End, which is how packets send no NBT
+    fn read_nbt(&mut self) -> Result<Option<(String, Tag)>, DeserializeError>;
+}
+
+pub trait WriteNbt {
+    // Nothing is written if a string in it is too long
+    fn write_nbt(&mut self, name: &str, tag: &Tag) -> Result<(), DeserializeError>;
+}
+
+impl ReadNbt for ByteBuf {
+    fn read_nbt(&mut self) -> Result<Option<(String, Tag)>, DeserializeError> {
+        let id = self.read_u8().map_err(eof)?;
+        if id == TAG_END {
+            return Ok(None);
+        }
+        let name = read_string(self)?;
+        Ok(Some((name, Tag::read(self, id, 0)?)))
+    }
+}
+
+impl WriteNbt for ByteBuf {
+    fn write_nbt(&mut self, name: &str, tag: &Tag) -> Result<(), DeserializeError> {
+        let mut nbt = ByteBuf::new();
+        nbt.write_u8(tag.id()).unwrap();
+        write_string(&mut nbt, name)?;
+        tag.write(&mut nbt)?;
+        self.write_bytes(nbt.as_slice());
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FileCompression {
+    None,
+    Gzip, // level.dat, player data and structures
+    Zlib, // Chunks in region files
+}
+
+// Reads an NBT file, working out how it was compressed from its first bytes
+pub fn read_file(data: &[u8]) -> Result<(String, Tag), DeserializeError> {
+    let mut raw = Vec::new();
+    let data = match data {
+        [0x1F, 0x8B, ..] => {
+            GzDecoder::new(data)
+                .read_to_end(&mut raw)
+                .map_err(|_| DeserializeError::Decompression)?;
+            raw.as_slice()
+        }
+        [0x78, ..] => {
+            ZlibDecoder::new(data)
+                .read_to_end(&mut raw)
+                .map_err(|_| DeserializeError::Decompression)?;
+            raw.as_slice()
+        }
+        _ => data,
+    };
+
+    // A file must have a root tag
+    ByteBuf::from(data)
+        .read_nbt()?
+        .ok_or(DeserializeError::UnknownTag(TAG_END))
+}
+
+pub fn write_file(
+    name: &str,
+    tag: &Tag,
+    compression: FileCompression,
+) -> Result<Vec<u8>, DeserializeError> {
+    let mut buf = ByteBuf::new();
+    buf.write_nbt(name, tag)?;
+    Ok(match compression {
+        FileCompression::None => buf.as_slice().to_vec(),
+        FileCompression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(buf.as_slice()).unwrap();
+            encoder.finish().unwrap()
+        }
+        FileCompression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(buf.as_slice()).unwrap();
+            encoder.finish().unwrap()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::nbt::*;
+
+    // hello_world.nbt and bigtest.nbt hold the test data from the original
+    // NBT specification, bigtest.zlib.nbt is the same data compressed with zlib
+    const HELLO_WORLD: &[u8] = include_bytes!("../../tests/fixtures/hello_world.nbt");
+    const BIGTEST: &[u8] = include_bytes!("../../tests/fixtures/bigtest.nbt");
+    const BIGTEST_ZLIB: &[u8] = include_bytes!("../../tests/fixtures/bigtest.zlib.nbt");
+
+    fn decompressed(data: &[u8]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut raw).unwrap();
+        raw
+    }
+
+    #[test]
+    fn hello_world() {
+        let (name, tag) = read_file(HELLO_WORLD).unwrap();
+        assert_eq!("hello world", name);
+        assert_eq!(
+            Tag::Compound(vec![(
+                "name".to_string(),
+                Tag::String("Bananrama".to_string())
+            )]),
+            tag
+        );
+        assert_eq!(
+            HELLO_WORLD,
+            write_file(&name, &tag, FileCompression::None)
+                .unwrap()
+                .as_slice()
+        );
+    }
+
+    #[test]
+    fn bigtest() {
+        let (name, tag) = read_file(BIGTEST).unwrap();
+        assert_eq!("Level", name);
+        match &tag {
+            Tag::Compound(tags) => assert_eq!(11, tags.len()),
+            other => panic!("{:?}", other),
+        }
+
+        assert_eq!(Some(&Tag::Long(i64::MAX)), tag.get("longTest"));
+        assert_eq!(Some(&Tag::Short(i16::MAX)), tag.get("shortTest"));
+        assert_eq!(Some(&Tag::Int(i32::MAX)), tag.get("intTest"));
+        assert_eq!(Some(&Tag::Byte(127)), tag.get("byteTest"));
+        assert_eq!(Some(&Tag::Float(0.49823147)), tag.get("floatTest"));
+        assert_eq!(
+            Some(&Tag::Double(0.4931287132182315)),
+            tag.get("doubleTest")
+        );
+        assert_eq!(
+            Some(&Tag::String(
+                "HELLO WORLD THIS IS A TEST STRING ÅÄÖ!".to_string()
+            )),
+            tag.get("stringTest")
+        );
+
+        let egg = tag
+            .get("nested compound test")
+            .and_then(|t| t.get("egg"))
+            .unwrap();
+        assert_eq!(Some(&Tag::String("Eggbert".to_string())), egg.get("name"));
+        assert_eq!(Some(&Tag::Float(0.5)), egg.get("value"));
+
+        assert_eq!(
+            Some(&Tag::List(TAG_LONG, (11..16).map(Tag::Long).collect())),
+            tag.get("listTest (long)")
+        );
+        match tag.get("listTest (compound)") {
+            Some(Tag::List(TAG_COMPOUND, tags)) => {
+                assert_eq!(2, tags.len());
+                assert_eq!(
+                    Some(&Tag::String("Compound tag #1".to_string())),
+                    tags[1].get("name")
+                );
+                assert_eq!(Some(&Tag::Long(1264099775885)), tags[1].get("created-on"));
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let name = "byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))";
+        let expected: Vec<i8> = (0..1000)
+            .map(|n| ((n * n * 255 + n * 7) % 100) as i8)
+            .collect();
+        assert_eq!(Some(&Tag::ByteArray(expected)), tag.get(name));
+    }
+
+    #[test]
+    fn compressed_files_round_trip() {
+        let (name, tag) = read_file(BIGTEST).unwrap();
+        assert_eq!(
+            decompressed(BIGTEST),
+            write_file(&name, &tag, FileCompression::None).unwrap()
+        );
+        assert_eq!(
+            (name.clone(), tag.clone()),
+            read_file(BIGTEST_ZLIB).unwrap()
+        );
+
+        for compression in [FileCompression::Gzip, FileCompression::Zlib] {
+            let file = write_file(&name, &tag, compression).unwrap();
+            assert_eq!((name.clone(), tag.clone()), read_file(&file).unwrap());
+        }
+    }
+
+    #[test]
+    fn modified_utf8_strings() {
+        let tag = Tag::String("a\0b😀".to_string());
+        let mut buf = ByteBuf::new();
+        buf.write_nbt("", &tag).unwrap();
+        assert_eq!(
+            &[TAG_STRING, 0, 0, 0, 10, b'a', 0xC0, 0x80, b'b', 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80],
+            buf.as_slice()
+        );
+        assert_eq!(Some(("".to_string(), tag)), buf.read_nbt().unwrap());
+
+        // A lone surrogate can't be a Rust string
+        let mut buf = ByteBuf::from(&[TAG_STRING, 0, 0, 0, 3, 0xED, 0xA0, 0xBD][..]);
+        assert_eq!(DeserializeError::InvalidString, buf.read_nbt().unwrap_err());
+    }
+
+    #[test]
+    fn strings_too_long_to_write() {
+        // NUL takes two bytes, so this is twice what a length can say
+        let tag = Tag::Compound(vec![(
+            "name".to_string(),
+            Tag::String("\0".repeat(u16::MAX as usize)),
+        )]);
+        let mut buf = ByteBuf::new();
+        assert_eq!(
+            DeserializeError::InvalidLength,
+            buf.write_nbt("", &tag).unwrap_err()
+        );
+        assert!(buf.end());
+        assert_eq!(
+            DeserializeError::InvalidLength,
+            write_file(
+                &"a".repeat(u16::MAX as usize + 1),
+                &Tag::Int(0),
+                FileCompression::None
+            )
+            .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn packets_send_end_for_no_nbt() {
+        let mut buf = ByteBuf::from(&[TAG_END][..]);
+        assert_eq!(None, buf.read_nbt().unwrap());
+        assert!(buf.end());
+    }
+
+    #[test]
+    fn empty_lists_keep_their_type() {
+        let tag = Tag::Compound(vec![
+            ("ints".to_string(), Tag::List(TAG_INT, Vec::new())),
+            ("none".to_string(), Tag::List(TAG_END, Vec::new())),
+            ("longs".to_string(), Tag::LongArray(vec![-1, 2])),
+            ("ints2".to_string(), Tag::IntArray(vec![3])),
+        ]);
+        let mut buf = ByteBuf::new();
+        buf.write_nbt("root", &tag).unwrap();
+        assert_eq!(Some(("root".to_string(), tag)), buf.read_nbt().unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_nbt() {
+        let mut buf = ByteBuf::from(&[13_u8, 0, 0][..]);
+        assert_eq!(
+            DeserializeError::UnknownTag(13),
+            buf.read_nbt().unwrap_err()
+        );
+
+        let truncated = &HELLO_WORLD[..HELLO_WORLD.len() - 4];
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            read_file(truncated).unwrap_err()
+        );
+
+        // A byte array claiming more than there is
+        let mut buf = ByteBuf::from(&[TAG_BYTE_ARRAY, 0, 0, 0x7F, 0xFF, 0xFF, 0xFF][..]);
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            buf.read_nbt().unwrap_err()
+        );
+
+        let mut buf = ByteBuf::from(&[TAG_LIST, 0, 0, TAG_END, 0, 0, 0, 1][..]);
+        assert_eq!(DeserializeError::InvalidLength, buf.read_nbt().unwrap_err());
+
+        // Lists nested past the limit
+        let mut buf = ByteBuf::from(&[TAG_LIST, 0, 0][..]);
+        for _ in 0..MAX_DEPTH + 1 {
+            buf.write_bytes(&[TAG_LIST, 0, 0, 0, 1]);
+        }
+        assert_eq!(DeserializeError::TooDeep, buf.read_nbt().unwrap_err());
+
+        assert_eq!(
+            DeserializeError::Decompression,
+            read_file(&[0x1F, 0x8B, 0, 0]).unwrap_err()
+        );
+    }
+}
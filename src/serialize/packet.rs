@@ -1,49 +1,228 @@
 use crate::serialize::buffer::ByteBuf;
 use crate::serialize::protocol::{ProtocolToID, ProtocolVersion};
-use crate::serialize::var::VarIntWriter;
+use crate::serialize::registry;
+use crate::serialize::var::{DeserializeError, VarIntReader, VarIntWriter};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+// Writes the `ProtocolToID` and `PacketSerializer` impls, see mcidle-derive
+pub use mcidle_derive::Packet;
 
 pub trait PacketSerializer: ProtocolToID {
     fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion);
-    fn deserialize(&mut self, buf: &mut ByteBuf);
+    fn deserialize(
+        &mut self,
+        buf: &mut ByteBuf,
+        ver: &ProtocolVersion,
+    ) -> Result<(), DeserializeError>;
 }
 
 pub trait Packet: PacketSerializer + ProtocolToID {
-    fn serialize_with_id(&self, ver: &ProtocolVersion) -> Box<ByteBuf>;
+    // None if `ver` doesn't have the packet
+    fn serialize_with_id(&self, ver: &ProtocolVersion) -> Option<Box<ByteBuf>>;
+
+    // The fields without the id, as the trackers are fed them
+    fn body(&self, ver: &ProtocolVersion) -> Vec<u8>;
 }
 
 impl<T: PacketSerializer + ProtocolToID> Packet for T {
-    fn serialize_with_id(&self, ver: &ProtocolVersion) -> Box<ByteBuf> {
+    fn serialize_with_id(&self, ver: &ProtocolVersion) -> Option<Box<ByteBuf>> {
         let mut buf = Box::new(ByteBuf::new());
-        buf.write_var_int(self.resolve_id(ver));
+        buf.write_var_int(self.resolve_id(ver)?);
+        self.serialize(&mut buf, ver);
+        Some(buf)
+    }
+
+    fn body(&self, ver: &ProtocolVersion) -> Vec<u8> {
+        let mut buf = ByteBuf::new();
         self.serialize(&mut buf, ver);
-        buf
+        buf.as_slice().to_vec()
     }
 }
 
-pub fn deserialize_new<T: Default + Packet>(buf: &mut ByteBuf) -> Box<T> {
+pub fn deserialize_new<T: Default + Packet>(
+    buf: &mut ByteBuf,
+    ver: &ProtocolVersion,
+) -> Result<Box<T>, DeserializeError> {
     let mut p: T = T::default();
-    p.deserialize(buf);
-    Box::new(p)
+    p.deserialize(buf, ver)?;
+    Ok(Box::new(p))
+}
+
+// Keep alive ids were VarInts before 1.12.2
+fn write_keep_alive_id(buf: &mut ByteBuf, id: i64, ver: &ProtocolVersion) {
+    if *ver < ProtocolVersion::V_1_12_2 {
+        buf.write_var_int(id as i32);
+    } else {
+        buf.write_i64::<BigEndian>(id).unwrap();
+    }
+}
+
+fn read_keep_alive_id(buf: &mut ByteBuf, ver: &ProtocolVersion) -> Result<i64, DeserializeError> {
+    if *ver < ProtocolVersion::V_1_12_2 {
+        Ok(buf.read_var_int()? as i64)
+    } else {
+        Ok(buf.read_i64::<BigEndian>()?)
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
-#[repr(i32)]
+// Every packet we know the id of, see `registry`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PacketID {
-    KeepAliveCB = 0x1F,
-    KeepAliveSB = 0x0B,
-    SetCompression = 0x03,
-    Handshake = 0x00,
+    // Handshaking, serverbound
+    Handshake,
+    // Status, clientbound
+    StatusResponse,
+    Pong,
+    // Status, serverbound
+    StatusRequest,
+    Ping,
+    // Login, clientbound
+    LoginDisconnect,
+    EncryptionRequest,
+    LoginSuccess,
+    SetCompression,
+    // Login, serverbound
+    LoginStart,
+    EncryptionResponse,
+    // Play, clientbound
+    SpawnObject,
+    SpawnExperienceOrb,
+    SpawnGlobalEntity,
+    SpawnMob,
+    SpawnPainting,
+    SpawnPlayer,
+    AnimationCB,
+    Statistics,
+    BlockBreakAnimation,
+    UpdateBlockEntity,
+    BlockAction,
+    BlockChange,
+    BossBar,
+    ServerDifficulty,
+    TabCompleteCB,
+    ChatMessageCB,
+    MultiBlockChange,
+    ConfirmTransactionCB,
+    CloseWindowCB,
+    OpenWindow,
+    WindowItems,
+    WindowProperty,
+    SetSlot,
+    SetCooldown,
+    PluginMessageCB,
+    NamedSoundEffect,
+    Disconnect,
+    EntityStatus,
+    Explosion,
+    UnloadChunk,
+    ChangeGameState,
+    KeepAliveCB,
+    ChunkData,
+    Effect,
+    Particle,
+    JoinGame,
+    Map,
+    Entity,
+    EntityRelativeMove,
+    EntityLookAndRelativeMove,
+    EntityLook,
+    VehicleMoveCB,
+    OpenSignEditor,
+    CraftRecipeResponse,
+    PlayerAbilitiesCB,
+    CombatEvent,
+    PlayerListItem,
+    PlayerInfoRemove, // Since 1.19.3, removing was a Player List Item action
+    PlayerPositionAndLookCB,
+    UseBed,
+    UnlockRecipes,
+    DestroyEntities,
+    RemoveEntityEffect,
+    ResourcePackSend,
+    Respawn,
+    EntityHeadLook,
+    SelectAdvancementTab,
+    WorldBorder,
+    Camera,
+    HeldItemChangeCB,
+    DisplayScoreboard,
+    EntityMetadata,
+    AttachEntity,
+    EntityVelocity,
+    EntityEquipment,
+    SetExperience,
+    UpdateHealth,
+    ScoreboardObjective,
+    SetPassengers,
+    Teams,
+    UpdateScore,
+    SpawnPosition,
+    TimeUpdate,
+    Title,
+    SoundEffect,
+    PlayerListHeaderAndFooter,
+    CollectItem,
+    EntityTeleport,
+    Advancements,
+    EntityProperties,
+    EntityEffect,
+    // Play, serverbound
+    TeleportConfirm,
+    TabCompleteSB,
+    ChatMessageSB,
+    ChatCommand, // Since 1.19, commands were chat before
+    ClientStatus,
+    ClientSettings,
+    ConfirmTransactionSB,
+    EnchantItem,
+    ClickWindow,
+    CloseWindowSB,
+    PluginMessageSB,
+    UseEntity,
+    KeepAliveSB,
+    Player,
+    PlayerPosition,
+    PlayerPositionAndLookSB,
+    PlayerLook,
+    VehicleMoveSB,
+    SteerBoat,
+    CraftRecipeRequest,
+    PlayerAbilitiesSB,
+    PlayerDigging,
+    EntityAction,
+    SteerVehicle,
+    CraftingBookData,
+    ResourcePackStatus,
+    AdvancementTab,
+    HeldItemChangeSB,
+    CreativeInventoryAction,
+    UpdateSign,
+    AnimationSB,
+    Spectate,
+    PlayerBlockPlacement,
+    UseItem,
+    Unknown(i32),
 }
 
-pub fn to_packet_id(id: i32) -> PacketID {
-    let packet_id: PacketID = unsafe { ::std::mem::transmute(id) };
-    packet_id
+impl PacketID {
+    // The id written on the wire, only unique within a state and direction.
+    // None if `ver` doesn't have the packet.
+    pub fn id(&self, ver: &ProtocolVersion) -> Option<i32> {
+        registry::id_of(ver, self)
+    }
 }
 
 pub mod clientbound {
-    use super::{PacketID, PacketSerializer};
+    use super::{read_keep_alive_id, write_keep_alive_id, Packet, PacketID, PacketSerializer};
     use crate::serialize::buffer::ByteBuf;
+    use crate::serialize::bytes::*;
+    use crate::serialize::field::Field;
+    use crate::serialize::metadata::Metadata;
+    use crate::serialize::position::Position;
     use crate::serialize::protocol::{ProtocolToID, ProtocolVersion};
+    use crate::serialize::slot::*;
+    use crate::serialize::string::*;
+    use crate::serialize::uuid;
     use crate::serialize::var::*;
     use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
@@ -53,135 +232,1277 @@ pub mod clientbound {
     }
 
     impl ProtocolToID for KeepAlive {
-        fn resolve_id(&self, _ver: &ProtocolVersion) -> i32 {
-            PacketID::KeepAliveCB as i32
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::KeepAliveCB.id(ver)
         }
     }
 
     impl PacketSerializer for KeepAlive {
-        fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
-            buf.write_i64::<BigEndian>(self.id).unwrap();
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            write_keep_alive_id(buf, self.id, ver);
         }
 
-        fn deserialize(&mut self, buf: &mut ByteBuf) {
-            self.id = buf.read_i64::<BigEndian>().unwrap();
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.id = read_keep_alive_id(buf, ver)?;
+            Ok(())
         }
     }
 
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, Packet)]
+    #[id(SetCompression)]
     pub struct SetCompression {
+        #[varint]
         pub threshold: i32,
     }
 
-    impl ProtocolToID for SetCompression {
-        fn resolve_id(&self, _ver: &ProtocolVersion) -> i32 {
-            PacketID::SetCompression as i32
+    #[derive(Debug, Default, Packet)]
+    #[id(StatusResponse)]
+    pub struct StatusResponse {
+        pub json: String,
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(Pong)]
+    pub struct Pong {
+        pub payload: i64, // Same as the Ping's
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(LoginDisconnect)]
+    pub struct LoginDisconnect {
+        pub reason: String, // JSON chat component
+    }
+
+    // Kicked while playing, `LoginDisconnect` is the same during login
+    #[derive(Debug, Default, Packet)]
+    #[id(Disconnect)]
+    pub struct Disconnect {
+        pub reason: String, // JSON chat component
+    }
+
+    // Signed profile data such as the skin, see `auth`
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct Property {
+        pub name: String,
+        pub value: String,
+        pub signature: Option<String>,
+    }
+
+    fn write_properties(buf: &mut ByteBuf, properties: &[Property]) {
+        buf.write_var_int(properties.len() as i32);
+        for property in properties {
+            buf.write_string(&property.name);
+            buf.write_string(&property.value);
+            buf.write_u8(property.signature.is_some() as u8).unwrap();
+            if let Some(signature) = &property.signature {
+                buf.write_string(signature);
+            }
+        }
+    }
+
+    fn read_properties(buf: &mut ByteBuf) -> Result<Vec<Property>, DeserializeError> {
+        let count = buf.read_var_int()?;
+        let mut properties = Vec::new();
+        for _ in 0..count {
+            let name = buf.read_string()?;
+            let value = buf.read_string()?;
+            let signature = match buf.read_u8()? {
+                0 => None,
+                _ => Some(buf.read_string()?),
+            };
+            properties.push(Property {
+                name,
+                value,
+                signature,
+            });
+        }
+        Ok(properties)
+    }
+
+    #[derive(Debug, Default, Clone)]
+    pub struct LoginSuccess {
+        pub uuid: String, // With dashes
+        pub username: String,
+        pub properties: Vec<Property>, // Only sent since 1.20
+    }
+
+    impl ProtocolToID for LoginSuccess {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::LoginSuccess.id(ver)
+        }
+    }
+
+    impl PacketSerializer for LoginSuccess {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            if *ver >= ProtocolVersion::V_1_16_5 {
+                let uuid = uuid::parse(&self.uuid).unwrap_or_default();
+                buf.write_u128::<BigEndian>(uuid).unwrap();
+            } else {
+                buf.write_string(&self.uuid);
+            }
+            buf.write_string(&self.username);
+
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                write_properties(buf, &self.properties);
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.uuid = if *ver >= ProtocolVersion::V_1_16_5 {
+                uuid::to_string(buf.read_u128::<BigEndian>()?)
+            } else {
+                buf.read_string()?
+            };
+            self.username = buf.read_string()?;
+
+            self.properties = if *ver >= ProtocolVersion::V_1_20_1 {
+                read_properties(buf)?
+            } else {
+                Vec::new()
+            };
+            Ok(())
         }
     }
 
-    impl PacketSerializer for SetCompression {
+    #[derive(Debug, Default, Packet)]
+    #[id(EncryptionRequest)]
+    pub struct EncryptionRequest {
+        pub server_id: String, // Empty on vanilla servers
+        #[prefixed]
+        pub public_key: Vec<u8>, // DER encoded
+        #[prefixed]
+        pub verify_token: Vec<u8>,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct ChunkData {
+        pub x: i32,
+        pub z: i32,
+        pub full: bool,    // Whole column including biomes, or just some sections
+        pub bit_mask: i32, // Bit n set if section n is in `data`
+        pub data: Vec<u8>, // Sections, then biomes if `full`, see crate::chunk
+        pub block_entity_count: i32,
+        pub block_entities: Vec<u8>, // NBT compounds, kept as they were read
+    }
+
+    impl ProtocolToID for ChunkData {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::ChunkData.id(ver)
+        }
+    }
+
+    impl PacketSerializer for ChunkData {
         fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
-            buf.write_var_int(self.threshold);
+            buf.write_i32::<BigEndian>(self.x).unwrap();
+            buf.write_i32::<BigEndian>(self.z).unwrap();
+            buf.write_u8(self.full as u8).unwrap();
+            buf.write_var_int(self.bit_mask);
+            buf.write_byte_array(&self.data);
+            buf.write_var_int(self.block_entity_count);
+            buf.write_bytes(&self.block_entities);
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            _: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.x = buf.read_i32::<BigEndian>()?;
+            self.z = buf.read_i32::<BigEndian>()?;
+            self.full = buf.read_u8()? != 0;
+            self.bit_mask = buf.read_var_int()?;
+            self.data = buf.read_byte_array()?;
+            self.block_entity_count = buf.read_var_int()?;
+            self.block_entities = buf
+                .read_bytes(buf.remaining())
+                .ok_or(DeserializeError::BufferTooSmall)?;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(UnloadChunk)]
+    pub struct UnloadChunk {
+        pub x: i32,
+        pub z: i32,
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(BlockChange)]
+    pub struct BlockChange {
+        pub location: Position,
+        #[varint]
+        pub block_state: i32, // id << 4 | metadata
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    pub struct BlockChangeRecord {
+        pub x: u8, // Relative to the chunk, 0-15
+        pub y: u8,
+        pub z: u8, // Relative to the chunk, 0-15
+        pub block_state: i32,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct MultiBlockChange {
+        pub chunk_x: i32,
+        pub chunk_z: i32,
+        pub records: Vec<BlockChangeRecord>,
+    }
+
+    impl ProtocolToID for MultiBlockChange {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::MultiBlockChange.id(ver)
+        }
+    }
+
+    impl PacketSerializer for MultiBlockChange {
+        fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
+            buf.write_i32::<BigEndian>(self.chunk_x).unwrap();
+            buf.write_i32::<BigEndian>(self.chunk_z).unwrap();
+            buf.write_var_int(self.records.len() as i32);
+            for record in &self.records {
+                buf.write_u8(record.x << 4 | (record.z & 0xF)).unwrap();
+                buf.write_u8(record.y).unwrap();
+                buf.write_var_int(record.block_state);
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            _: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.chunk_x = buf.read_i32::<BigEndian>()?;
+            self.chunk_z = buf.read_i32::<BigEndian>()?;
+            let count = buf.read_var_int()?;
+            self.records = (0..count)
+                .map(|_| {
+                    let horizontal = buf.read_u8()?;
+                    Ok(BlockChangeRecord {
+                        x: horizontal >> 4,
+                        y: buf.read_u8()?,
+                        z: horizontal & 0xF,
+                        block_state: buf.read_var_int()?,
+                    })
+                })
+                .collect::<Result<_, DeserializeError>>()?;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct WindowItems {
+        pub window_id: u8, // 0 is the player's inventory
+        pub slots: Vec<Option<Slot>>,
+    }
+
+    impl ProtocolToID for WindowItems {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::WindowItems.id(ver)
+        }
+    }
+
+    impl PacketSerializer for WindowItems {
+        fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
+            buf.write_u8(self.window_id).unwrap();
+            buf.write_i16::<BigEndian>(self.slots.len() as i16).unwrap();
+            for slot in &self.slots {
+                buf.write_slot(slot.as_ref());
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            _: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.window_id = buf.read_u8()?;
+            let count = buf.read_i16::<BigEndian>()?;
+            self.slots = (0..count)
+                .map(|_| buf.read_slot())
+                .collect::<Result<_, _>>()?;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(SetSlot)]
+    pub struct SetSlot {
+        pub window_id: i8, // -1 with slot -1 is the item on the cursor
+        pub slot: i16,
+        pub data: Option<Slot>,
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(HeldItemChangeCB)]
+    pub struct HeldItemChange {
+        pub slot: i8, // Hotbar slot, 0-8
+    }
+
+    // Where a chat message is shown
+    pub const CHAT: i8 = 0;
+    pub const SYSTEM: i8 = 1;
+    pub const GAME_INFO: i8 = 2; // Above the hotbar
+
+    // System Chat since 1.19, which has no sender and only says whether it's
+    // shown above the hotbar
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct ChatMessage {
+        pub json: String, // JSON chat component
+        pub position: i8,
+        pub sender: u128, // Only sent in 1.16, 0 for system messages
+    }
+
+    impl ProtocolToID for ChatMessage {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::ChatMessageCB.id(ver)
+        }
+    }
+
+    impl PacketSerializer for ChatMessage {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_string(&self.json);
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                buf.write_u8((self.position == GAME_INFO) as u8).unwrap();
+                return;
+            }
+            buf.write_i8(self.position).unwrap();
+            if *ver >= ProtocolVersion::V_1_16_5 {
+                buf.write_u128::<BigEndian>(self.sender).unwrap();
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.json = buf.read_string()?;
+            self.sender = 0;
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                self.position = match buf.read_u8()? {
+                    0 => SYSTEM,
+                    _ => GAME_INFO,
+                };
+                return Ok(());
+            }
+            self.position = buf.read_i8()?;
+            if *ver >= ProtocolVersion::V_1_16_5 {
+                self.sender = buf.read_u128::<BigEndian>()?;
+            }
+            Ok(())
+        }
+    }
+
+    // Bits of `PlayerPositionAndLook::flags`, a set bit means the field is
+    // relative to the current position
+    pub const RELATIVE_X: u8 = 0x01;
+    pub const RELATIVE_Y: u8 = 0x02;
+    pub const RELATIVE_Z: u8 = 0x04;
+    pub const RELATIVE_YAW: u8 = 0x08;
+    pub const RELATIVE_PITCH: u8 = 0x10;
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(PlayerPositionAndLookCB)]
+    pub struct PlayerPositionAndLook {
+        pub x: f64,
+        pub y: f64, // Feet
+        pub z: f64,
+        pub yaw: f32,
+        pub pitch: f32,
+        pub flags: u8,
+        #[varint]
+        #[since(107)]
+        pub teleport_id: i32, // Confirmed with Teleport Confirm, not sent in 1.8
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(UpdateHealth)]
+    pub struct UpdateHealth {
+        pub health: f32, // 0 is dead, 20 is full
+        #[varint]
+        pub food: i32, // 0 to 20
+        pub saturation: f32,
+    }
+
+    // Values of `CombatEvent::event`
+    pub const ENTER_COMBAT: i32 = 0;
+    pub const END_COMBAT: i32 = 1;
+    pub const ENTITY_DEAD: i32 = 2;
+
+    // Only deaths are sent in 1.20.1, as Player Combat Kill
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct CombatEvent {
+        pub event: i32,
+        pub duration: i32,   // END_COMBAT, in ticks
+        pub player_id: i32,  // ENTITY_DEAD, who died
+        pub entity_id: i32,  // Who was fought or did the killing, not in 1.20.1
+        pub message: String, // ENTITY_DEAD, a JSON chat component
+    }
+
+    impl ProtocolToID for CombatEvent {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::CombatEvent.id(ver)
+        }
+    }
+
+    impl PacketSerializer for CombatEvent {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                buf.write_var_int(self.player_id);
+                buf.write_string(&self.message);
+                return;
+            }
+            buf.write_var_int(self.event);
+            match self.event {
+                END_COMBAT => {
+                    buf.write_var_int(self.duration);
+                    buf.write_i32::<BigEndian>(self.entity_id).unwrap();
+                }
+                ENTITY_DEAD => {
+                    buf.write_var_int(self.player_id);
+                    buf.write_i32::<BigEndian>(self.entity_id).unwrap();
+                    buf.write_string(&self.message);
+                }
+                _ => {}
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                self.event = ENTITY_DEAD;
+                self.player_id = buf.read_var_int()?;
+                self.message = buf.read_string()?;
+                return Ok(());
+            }
+            self.event = buf.read_var_int()?;
+            match self.event {
+                ENTER_COMBAT => {}
+                END_COMBAT => {
+                    self.duration = buf.read_var_int()?;
+                    self.entity_id = buf.read_i32::<BigEndian>()?;
+                }
+                ENTITY_DEAD => {
+                    self.player_id = buf.read_var_int()?;
+                    self.entity_id = buf.read_i32::<BigEndian>()?;
+                    self.message = buf.read_string()?;
+                }
+                _ => return Err(DeserializeError::InvalidValue),
+            }
+            Ok(())
+        }
+    }
+
+    // Entity positions in 1.8 are fixed point 32nds of a block
+    fn write_coord(buf: &mut ByteBuf, coord: f64, ver: &ProtocolVersion) {
+        if *ver == ProtocolVersion::V_1_8_9 {
+            buf.write_i32::<BigEndian>((coord * 32.0).floor() as i32)
+                .unwrap();
+        } else {
+            buf.write_f64::<BigEndian>(coord).unwrap();
+        }
+    }
+
+    fn read_coord(buf: &mut ByteBuf, ver: &ProtocolVersion) -> Result<f64, DeserializeError> {
+        if *ver == ProtocolVersion::V_1_8_9 {
+            Ok(buf.read_i32::<BigEndian>()? as f64 / 32.0)
+        } else {
+            Ok(buf.read_f64::<BigEndian>()?)
+        }
+    }
+
+    // Relative moves are 4096ths of a block, 32nds in 1.8
+    fn write_delta(buf: &mut ByteBuf, delta: f64, ver: &ProtocolVersion) {
+        if *ver == ProtocolVersion::V_1_8_9 {
+            buf.write_i8((delta * 32.0).round() as i8).unwrap();
+        } else {
+            buf.write_i16::<BigEndian>((delta * 4096.0).round() as i16)
+                .unwrap();
+        }
+    }
+
+    fn read_delta(buf: &mut ByteBuf, ver: &ProtocolVersion) -> Result<f64, DeserializeError> {
+        if *ver == ProtocolVersion::V_1_8_9 {
+            Ok(buf.read_i8()? as f64 / 32.0)
+        } else {
+            Ok(buf.read_i16::<BigEndian>()? as f64 / 4096.0)
+        }
+    }
+
+    // Another player coming into view. Their metadata (and held item in 1.8)
+    // isn't read.
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct SpawnPlayer {
+        pub entity_id: i32,
+        pub uuid: u128,
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+        pub yaw: u8, // 256ths of a turn
+        pub pitch: u8,
+    }
+
+    impl ProtocolToID for SpawnPlayer {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::SpawnPlayer.id(ver)
+        }
+    }
+
+    impl PacketSerializer for SpawnPlayer {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_var_int(self.entity_id);
+            buf.write_u128::<BigEndian>(self.uuid).unwrap();
+            for coord in [self.x, self.y, self.z] {
+                write_coord(buf, coord, ver);
+            }
+            buf.write_u8(self.yaw).unwrap();
+            buf.write_u8(self.pitch).unwrap();
+            // Nothing held and no metadata
+            match ver {
+                ProtocolVersion::V_1_8_9 => {
+                    buf.write_i16::<BigEndian>(0).unwrap();
+                    buf.write_u8(0x7F).unwrap();
+                }
+                ProtocolVersion::V_1_12_2 => buf.write_u8(0xFF).unwrap(),
+                _ => {}
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.entity_id = buf.read_var_int()?;
+            self.uuid = buf.read_u128::<BigEndian>()?;
+            self.x = read_coord(buf, ver)?;
+            self.y = read_coord(buf, ver)?;
+            self.z = read_coord(buf, ver)?;
+            self.yaw = buf.read_u8()?;
+            self.pitch = buf.read_u8()?;
+            Ok(())
+        }
+    }
+
+    // Objects and mobs are only read in the 1.12.2 format, where the other
+    // versions differ they look up as `PacketID::Unknown`
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(SpawnObject)]
+    pub struct SpawnObject {
+        #[varint]
+        pub entity_id: i32,
+        pub uuid: u128,
+        pub kind: i8, // Boats, minecarts, items, arrows...
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+        pub pitch: u8,
+        pub yaw: u8,
+        pub data: i32, // Depends on the kind
+        pub velocity_x: i16,
+        pub velocity_y: i16,
+        pub velocity_z: i16,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(SpawnMob)]
+    pub struct SpawnMob {
+        #[varint]
+        pub entity_id: i32,
+        pub uuid: u128,
+        #[varint]
+        pub kind: i32,
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+        pub yaw: u8,
+        pub pitch: u8,
+        pub head_pitch: u8,
+        pub velocity_x: i16,
+        pub velocity_y: i16,
+        pub velocity_z: i16,
+        pub metadata: Metadata,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(EntityMetadata)]
+    pub struct EntityMetadata {
+        #[varint]
+        pub entity_id: i32,
+        pub metadata: Metadata, // Only what changed
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct DestroyEntities {
+        pub entity_ids: Vec<i32>,
+    }
+
+    impl ProtocolToID for DestroyEntities {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::DestroyEntities.id(ver)
+        }
+    }
+
+    impl PacketSerializer for DestroyEntities {
+        fn serialize(&self, buf: &mut ByteBuf, _ver: &ProtocolVersion) {
+            buf.write_var_int(self.entity_ids.len() as i32);
+            for id in &self.entity_ids {
+                buf.write_var_int(*id);
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            _ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            let count = buf.read_var_int()?;
+            if count < 0 {
+                return Err(DeserializeError::InvalidLength);
+            }
+            self.entity_ids.clear();
+            for _ in 0..count {
+                self.entity_ids.push(buf.read_var_int()?);
+            }
+            Ok(())
+        }
+    }
+
+    // A move of under 8 blocks
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct EntityRelativeMove {
+        pub entity_id: i32,
+        pub dx: f64, // Blocks
+        pub dy: f64,
+        pub dz: f64,
+        pub on_ground: bool,
+    }
+
+    impl ProtocolToID for EntityRelativeMove {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::EntityRelativeMove.id(ver)
+        }
+    }
+
+    impl PacketSerializer for EntityRelativeMove {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_var_int(self.entity_id);
+            for delta in [self.dx, self.dy, self.dz] {
+                write_delta(buf, delta, ver);
+            }
+            self.on_ground.write_to(buf);
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.entity_id = buf.read_var_int()?;
+            self.dx = read_delta(buf, ver)?;
+            self.dy = read_delta(buf, ver)?;
+            self.dz = read_delta(buf, ver)?;
+            self.on_ground = bool::read_from(buf)?;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct EntityLookAndRelativeMove {
+        pub entity_id: i32,
+        pub dx: f64, // Blocks
+        pub dy: f64,
+        pub dz: f64,
+        pub yaw: u8,
+        pub pitch: u8,
+        pub on_ground: bool,
+    }
+
+    impl ProtocolToID for EntityLookAndRelativeMove {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::EntityLookAndRelativeMove.id(ver)
+        }
+    }
+
+    impl PacketSerializer for EntityLookAndRelativeMove {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_var_int(self.entity_id);
+            for delta in [self.dx, self.dy, self.dz] {
+                write_delta(buf, delta, ver);
+            }
+            buf.write_u8(self.yaw).unwrap();
+            buf.write_u8(self.pitch).unwrap();
+            self.on_ground.write_to(buf);
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.entity_id = buf.read_var_int()?;
+            self.dx = read_delta(buf, ver)?;
+            self.dy = read_delta(buf, ver)?;
+            self.dz = read_delta(buf, ver)?;
+            self.yaw = buf.read_u8()?;
+            self.pitch = buf.read_u8()?;
+            self.on_ground = bool::read_from(buf)?;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(EntityLook)]
+    pub struct EntityLook {
+        #[varint]
+        pub entity_id: i32,
+        pub yaw: u8,
+        pub pitch: u8,
+        pub on_ground: bool,
+    }
+
+    // A move of 8 blocks or more
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct EntityTeleport {
+        pub entity_id: i32,
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+        pub yaw: u8,
+        pub pitch: u8,
+        pub on_ground: bool,
+    }
+
+    impl ProtocolToID for EntityTeleport {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::EntityTeleport.id(ver)
+        }
+    }
+
+    impl PacketSerializer for EntityTeleport {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_var_int(self.entity_id);
+            for coord in [self.x, self.y, self.z] {
+                write_coord(buf, coord, ver);
+            }
+            buf.write_u8(self.yaw).unwrap();
+            buf.write_u8(self.pitch).unwrap();
+            self.on_ground.write_to(buf);
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.entity_id = buf.read_var_int()?;
+            self.x = read_coord(buf, ver)?;
+            self.y = read_coord(buf, ver)?;
+            self.z = read_coord(buf, ver)?;
+            self.yaw = buf.read_u8()?;
+            self.pitch = buf.read_u8()?;
+            self.on_ground = bool::read_from(buf)?;
+            Ok(())
+        }
+    }
+
+    // Bits of `PlayerListItem::actions`, as 1.20.1 sends them
+    pub const ADD_PLAYER: u8 = 0x01;
+    pub const INITIALIZE_CHAT: u8 = 0x02; // Chat signing, never read
+    pub const UPDATE_GAMEMODE: u8 = 0x04;
+    pub const UPDATE_LISTED: u8 = 0x08;
+    pub const UPDATE_LATENCY: u8 = 0x10;
+    pub const UPDATE_DISPLAY_NAME: u8 = 0x20;
+    // Not a 1.20.1 bit, it sends `PlayerInfoRemove` instead
+    pub const REMOVE_PLAYER: u8 = 0x80;
+
+    // What each action before 1.19.3 does, by id. Adding set everything.
+    const OLD_ACTIONS: [u8; 5] = [
+        ADD_PLAYER | UPDATE_GAMEMODE | UPDATE_LISTED | UPDATE_LATENCY | UPDATE_DISPLAY_NAME,
+        UPDATE_GAMEMODE,
+        UPDATE_LATENCY,
+        UPDATE_DISPLAY_NAME,
+        REMOVE_PLAYER,
+    ];
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct PlayerListEntry {
+        pub uuid: u128,
+        pub name: String,
+        pub properties: Vec<Property>, // "textures" is the skin and cape
+        pub gamemode: i32,
+        pub listed: bool, // Shown in the tab list, always before 1.19.3
+        pub ping: i32,    // Milliseconds
+        pub display_name: Option<String>, // JSON chat component shown instead of the name
+    }
+
+    impl PlayerListEntry {
+        // The skin and cape, base64 encoded JSON signed by Mojang
+        pub fn textures(&self) -> Option<&Property> {
+            self.properties
+                .iter()
+                .find(|property| property.name == "textures")
         }
+    }
 
-        fn deserialize(&mut self, buf: &mut ByteBuf) {
-            self.threshold = buf.read_var_int().unwrap();
+    // Adds, updates or removes players in the tab list. Only the fields of
+    // `actions` are sent. 1.20.1 can send several at once, older versions
+    // one per packet.
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct PlayerListItem {
+        pub actions: u8,
+        pub players: Vec<PlayerListEntry>,
+    }
+
+    impl ProtocolToID for PlayerListItem {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::PlayerListItem.id(ver)
         }
     }
+
+    impl PacketSerializer for PlayerListItem {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            let new = *ver >= ProtocolVersion::V_1_20_1;
+            let actions = if new {
+                buf.write_u8(self.actions & !REMOVE_PLAYER).unwrap();
+                self.actions
+            } else {
+                let id = if self.actions & REMOVE_PLAYER != 0 {
+                    4
+                } else if self.actions & ADD_PLAYER != 0 {
+                    0
+                } else if self.actions & UPDATE_GAMEMODE != 0 {
+                    1
+                } else if self.actions & UPDATE_LATENCY != 0 {
+                    2
+                } else {
+                    3
+                };
+                buf.write_var_int(id as i32);
+                OLD_ACTIONS[id] & !UPDATE_LISTED
+            };
+
+            buf.write_var_int(self.players.len() as i32);
+            for player in &self.players {
+                buf.write_u128::<BigEndian>(player.uuid).unwrap();
+                if actions & ADD_PLAYER != 0 {
+                    buf.write_string(&player.name);
+                    write_properties(buf, &player.properties);
+                }
+                if new && actions & INITIALIZE_CHAT != 0 {
+                    buf.write_u8(0).unwrap();
+                }
+                if actions & UPDATE_GAMEMODE != 0 {
+                    buf.write_var_int(player.gamemode);
+                }
+                if new && actions & UPDATE_LISTED != 0 {
+                    player.listed.write_to(buf);
+                }
+                if actions & UPDATE_LATENCY != 0 {
+                    buf.write_var_int(player.ping);
+                }
+                if actions & UPDATE_DISPLAY_NAME != 0 {
+                    buf.write_u8(player.display_name.is_some() as u8).unwrap();
+                    if let Some(display_name) = &player.display_name {
+                        buf.write_string(display_name);
+                    }
+                }
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            let new = *ver >= ProtocolVersion::V_1_20_1;
+            self.actions = if new {
+                buf.read_u8()? & !REMOVE_PLAYER
+            } else {
+                let id = buf.read_var_int()?;
+                *OLD_ACTIONS
+                    .get(id as usize)
+                    .ok_or(DeserializeError::InvalidValue)?
+            };
+
+            let count = buf.read_var_int()?;
+            if count < 0 {
+                return Err(DeserializeError::InvalidLength);
+            }
+            self.players.clear();
+            for _ in 0..count {
+                let mut player = PlayerListEntry {
+                    uuid: buf.read_u128::<BigEndian>()?,
+                    ..PlayerListEntry::default()
+                };
+                if self.actions & ADD_PLAYER != 0 {
+                    player.name = buf.read_string()?;
+                    player.properties = read_properties(buf)?;
+                    player.listed = !new;
+                }
+                if new && self.actions & INITIALIZE_CHAT != 0 && bool::read_from(buf)? {
+                    buf.read_u128::<BigEndian>()?; // Session
+                    buf.read_i64::<BigEndian>()?; // Key expiry
+                    for _ in 0..2 {
+                        // Key and its signature
+                        let len = buf.read_var_int()?;
+                        if len < 0 {
+                            return Err(DeserializeError::InvalidLength);
+                        }
+                        buf.read_bytes(len as usize)
+                            .ok_or(DeserializeError::BufferTooSmall)?;
+                    }
+                }
+                if self.actions & UPDATE_GAMEMODE != 0 {
+                    player.gamemode = buf.read_var_int()?;
+                }
+                if new && self.actions & UPDATE_LISTED != 0 {
+                    player.listed = bool::read_from(buf)?;
+                }
+                if self.actions & UPDATE_LATENCY != 0 {
+                    player.ping = buf.read_var_int()?;
+                }
+                if self.actions & UPDATE_DISPLAY_NAME != 0 && bool::read_from(buf)? {
+                    player.display_name = Some(buf.read_string()?);
+                }
+                self.players.push(player);
+            }
+            Ok(())
+        }
+    }
+
+    // Removes players from the tab list in 1.20.1
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(PlayerInfoRemove)]
+    pub struct PlayerInfoRemove {
+        #[prefixed]
+        pub uuids: Vec<u128>,
+    }
 }
 
 pub mod serverbound {
-    use super::{PacketID, PacketSerializer};
+    use super::{read_keep_alive_id, write_keep_alive_id, Packet, PacketID, PacketSerializer};
 
     use crate::serialize::buffer::*;
+    use crate::serialize::bytes::*;
+    use crate::serialize::field::Field;
     use crate::serialize::protocol::{ProtocolToID, ProtocolVersion};
     use crate::serialize::string::*;
     use crate::serialize::var::*;
 
     use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+    use std::time::{SystemTime, UNIX_EPOCH};
 
-    #[derive(Debug, Clone, PartialEq)]
+    #[derive(Debug, Clone, PartialEq, Default)]
     #[repr(i32)]
     pub enum LoginState {
+        #[default]
         Undefined = 0,
+        Status = 1,
         Login = 2,
     }
 
-    impl Default for LoginState {
-        fn default() -> Self {
-            LoginState::Undefined
+    // Sent as a VarInt
+    impl Field for LoginState {
+        fn write_to(&self, buf: &mut ByteBuf) {
+            buf.write_var_int(self.clone() as i32);
+        }
+
+        fn read_from(buf: &mut ByteBuf) -> Result<Self, DeserializeError> {
+            match buf.read_var_int()? {
+                1 => Ok(LoginState::Status),
+                2 => Ok(LoginState::Login),
+                _ => Err(DeserializeError::InvalidValue),
+            }
         }
     }
 
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, Clone, Packet)]
+    #[id(Handshake)]
     pub struct Handshake {
+        #[varint]
         pub protocol_version: i32,
         pub address: String,
         pub port: u16,
         pub next_state: LoginState,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Default)]
     pub struct KeepAlive {
         pub id: i64,
     }
 
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, Clone)]
     pub struct LoginStart {
         pub username: String,
+        pub uuid: Option<u128>, // Only sent since 1.20, the server ignores it
     }
 
-    impl ProtocolToID for Handshake {
-        fn resolve_id(&self, _ver: &ProtocolVersion) -> i32 {
-            PacketID::Handshake as i32
+    impl ProtocolToID for KeepAlive {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::KeepAliveSB.id(ver)
         }
     }
 
-    impl ProtocolToID for KeepAlive {
-        fn resolve_id(&self, _ver: &ProtocolVersion) -> i32 {
-            PacketID::KeepAliveSB as i32
+    impl PacketSerializer for KeepAlive {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            write_keep_alive_id(buf, self.id, ver);
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.id = read_keep_alive_id(buf, ver)?;
+            Ok(())
         }
     }
 
-    impl PacketSerializer for KeepAlive {
-        fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
-            buf.write_i64::<BigEndian>(self.id).unwrap();
+    impl ProtocolToID for LoginStart {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::LoginStart.id(ver)
         }
+    }
 
-        fn deserialize(&mut self, buf: &mut ByteBuf) {
-            self.id = buf.read_i64::<BigEndian>().unwrap();
+    impl PacketSerializer for LoginStart {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_string(&self.username);
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                buf.write_u8(self.uuid.is_some() as u8).unwrap();
+                if let Some(uuid) = self.uuid {
+                    buf.write_u128::<BigEndian>(uuid).unwrap();
+                }
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.username = buf.read_string()?;
+            self.uuid = None;
+            if *ver >= ProtocolVersion::V_1_20_1 && buf.read_u8()? != 0 {
+                self.uuid = Some(buf.read_u128::<BigEndian>()?);
+            }
+            Ok(())
         }
     }
 
-    impl PacketSerializer for Handshake {
-        fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
-            buf.write_var_int(self.protocol_version);
-            buf.write_string(&self.address);
-            buf.write_u16::<BigEndian>(self.port).unwrap();
-            buf.write_var_int(self.next_state.clone() as i32);
+    #[derive(Debug, Default, Packet)]
+    #[id(StatusRequest)]
+    pub struct StatusRequest {}
+
+    #[derive(Debug, Default, Packet)]
+    #[id(Ping)]
+    pub struct Ping {
+        pub payload: i64,
+    }
+
+    // Both fields are encrypted with the server's public key
+    #[derive(Debug, Default, Packet)]
+    #[id(EncryptionResponse)]
+    pub struct EncryptionResponse {
+        #[prefixed]
+        pub shared_secret: Vec<u8>,
+        #[prefixed]
+        pub verify_token: Vec<u8>,
+    }
+
+    #[derive(Debug, Default, Packet)]
+    #[id(HeldItemChangeSB)]
+    pub struct HeldItemChange {
+        pub slot: i16, // Hotbar slot, 0-8
+    }
+
+    // Since 1.19 chat is signed, ours is sent unsigned which servers accept
+    // unless they enforce secure profiles
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct ChatMessage {
+        pub message: String, // Commands start with a slash before 1.19
+    }
+
+    impl ChatMessage {
+        // Servers kick players who send anything longer
+        pub fn max_length(ver: &ProtocolVersion) -> usize {
+            match ver {
+                ProtocolVersion::V_1_8_9 => 100,
+                _ => 256,
+            }
         }
+    }
 
-        fn deserialize(&mut self, buf: &mut ByteBuf) {
-            self.protocol_version = buf.read_var_int().unwrap();
-            self.address = buf.read_string().unwrap();
-            self.port = buf.read_u16::<BigEndian>().unwrap();
-            self.next_state = unsafe { ::std::mem::transmute(buf.read_var_int().unwrap()) };
+    impl ProtocolToID for ChatMessage {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::ChatMessageSB.id(ver)
         }
     }
 
-    impl ProtocolToID for LoginStart {
-        fn resolve_id(&self, _ver: &ProtocolVersion) -> i32 {
-            0x00
+    impl PacketSerializer for ChatMessage {
+        fn serialize(&self, buf: &mut ByteBuf, ver: &ProtocolVersion) {
+            buf.write_string(&self.message);
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as i64);
+                buf.write_i64::<BigEndian>(timestamp).unwrap();
+                buf.write_i64::<BigEndian>(0).unwrap(); // Salt
+                buf.write_u8(0).unwrap(); // No signature
+                buf.write_var_int(0); // No messages acknowledged
+                buf.write_bytes(&[0; 3]);
+            }
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.message = buf.read_string()?;
+            if *ver >= ProtocolVersion::V_1_20_1 {
+                buf.read_i64::<BigEndian>()?;
+                buf.read_i64::<BigEndian>()?;
+                if buf.read_u8()? != 0 {
+                    buf.read_bytes(256)
+                        .ok_or(DeserializeError::BufferTooSmall)?;
+                }
+                buf.read_var_int()?;
+                buf.read_bytes(3).ok_or(DeserializeError::BufferTooSmall)?;
+            }
+            Ok(())
         }
     }
 
-    impl PacketSerializer for LoginStart {
-        fn serialize(&self, buf: &mut ByteBuf, _: &ProtocolVersion) {
-            buf.write_string(&self.username);
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(PlayerPosition)]
+    pub struct PlayerPosition {
+        pub x: f64,
+        pub y: f64, // Feet
+        pub z: f64,
+        pub on_ground: bool,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(PlayerPositionAndLookSB)]
+    pub struct PlayerPositionAndLook {
+        pub x: f64,
+        pub y: f64, // Feet
+        pub z: f64,
+        pub yaw: f32,
+        pub pitch: f32,
+        pub on_ground: bool,
+    }
+
+    // Accepts the position of a Player Position And Look, not sent in 1.8
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(TeleportConfirm)]
+    pub struct TeleportConfirm {
+        #[varint]
+        pub teleport_id: i32,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(PlayerLook)]
+    pub struct PlayerLook {
+        pub yaw: f32,
+        pub pitch: f32,
+        pub on_ground: bool,
+    }
+
+    // Sent when nothing else changed
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(Player)]
+    pub struct Player {
+        pub on_ground: bool,
+    }
+
+    pub const PERFORM_RESPAWN: i32 = 0;
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(ClientStatus)]
+    pub struct ClientStatus {
+        #[varint]
+        pub action: i32,
+    }
+
+    // Swings an arm, 1.8 only has the one
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(AnimationSB)]
+    pub struct Animation {
+        #[varint]
+        #[since(107)]
+        pub hand: i32, // 0 is the main hand
+    }
+
+    pub const START_SNEAKING: i32 = 0;
+    pub const STOP_SNEAKING: i32 = 1;
+
+    #[derive(Debug, Default, Clone, PartialEq, Packet)]
+    #[id(EntityAction)]
+    pub struct EntityAction {
+        #[varint]
+        pub entity_id: i32, // Our own
+        #[varint]
+        pub action: i32,
+        #[varint]
+        pub jump_boost: i32, // Only for horses
+    }
+
+    // A command without its slash, unsigned like `ChatMessage`
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct ChatCommand {
+        pub command: String,
+    }
+
+    impl ProtocolToID for ChatCommand {
+        fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32> {
+            PacketID::ChatCommand.id(ver)
         }
+    }
 
-        fn deserialize(&mut self, buf: &mut ByteBuf) {
-            self.username = buf.read_string().unwrap();
+    impl PacketSerializer for ChatCommand {
+        fn serialize(&self, buf: &mut ByteBuf, _ver: &ProtocolVersion) {
+            buf.write_string(&self.command);
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as i64);
+            buf.write_i64::<BigEndian>(timestamp).unwrap();
+            buf.write_i64::<BigEndian>(0).unwrap(); // Salt
+            buf.write_var_int(0); // No argument signatures
+            buf.write_var_int(0); // No messages acknowledged
+            buf.write_bytes(&[0; 3]);
+        }
+
+        fn deserialize(
+            &mut self,
+            buf: &mut ByteBuf,
+            _ver: &ProtocolVersion,
+        ) -> Result<(), DeserializeError> {
+            self.command = buf.read_string()?;
+            buf.read_i64::<BigEndian>()?;
+            buf.read_i64::<BigEndian>()?;
+            for _ in 0..buf.read_var_int()? {
+                buf.read_string()?;
+                buf.read_bytes(256)
+                    .ok_or(DeserializeError::BufferTooSmall)?;
+            }
+            buf.read_var_int()?;
+            buf.read_bytes(3).ok_or(DeserializeError::BufferTooSmall)?;
+            Ok(())
         }
     }
 }
@@ -191,6 +1512,7 @@ mod tests {
     use super::PacketID;
     use crate::serialize::packet::serverbound::*;
     use crate::serialize::packet::*;
+    use crate::serialize::protocol::State;
     use crate::serialize::var::VarIntReader;
 
     #[test]
@@ -202,14 +1524,371 @@ mod tests {
             next_state: LoginState::Login,
         };
 
-        let mut buf = h.serialize_with_id(&ProtocolVersion::V_1_12_2);
+        let mut buf = h.serialize_with_id(&ProtocolVersion::V_1_12_2).unwrap();
         assert_eq!(16, buf.len());
 
-        assert_eq!(PacketID::Handshake as i32, buf.read_var_int().unwrap());
-        let h2 = deserialize_new::<Handshake>(&mut buf);
+        assert_eq!(
+            PacketID::Handshake.id(&ProtocolVersion::V_1_12_2).unwrap(),
+            buf.read_var_int().unwrap()
+        );
+        let h2 = deserialize_new::<Handshake>(&mut buf, &ProtocolVersion::V_1_12_2).unwrap();
         assert_eq!(h.protocol_version, h2.protocol_version);
         assert_eq!(h.address, h2.address);
         assert_eq!(h.port, h2.port);
         assert_eq!(h.next_state, h2.next_state);
     }
+
+    #[test]
+    fn valid_encryption_request_test() {
+        let req = clientbound::EncryptionRequest {
+            server_id: "".to_string(),
+            public_key: vec![0x30, 0x81, 0x9f, 0x30, 0x0d],
+            verify_token: vec![0xde, 0xad, 0xbe, 0xef],
+        };
+
+        let mut buf = req.serialize_with_id(&ProtocolVersion::V_1_12_2).unwrap();
+        assert_eq!(13, buf.len());
+
+        assert_eq!(
+            PacketID::EncryptionRequest
+                .id(&ProtocolVersion::V_1_12_2)
+                .unwrap(),
+            buf.read_var_int().unwrap()
+        );
+        let req2 =
+            deserialize_new::<clientbound::EncryptionRequest>(&mut buf, &ProtocolVersion::V_1_12_2)
+                .unwrap();
+        assert_eq!(req.server_id, req2.server_id);
+        assert_eq!(req.public_key, req2.public_key);
+        assert_eq!(req.verify_token, req2.verify_token);
+    }
+
+    #[test]
+    fn truncated_packets_are_errors() {
+        let req = clientbound::EncryptionRequest {
+            server_id: "".to_string(),
+            public_key: vec![0x30, 0x81, 0x9f, 0x30, 0x0d],
+            verify_token: vec![0xde, 0xad, 0xbe, 0xef],
+        };
+        let mut buf = ByteBuf::new();
+        req.serialize(&mut buf, &ProtocolVersion::V_1_12_2);
+
+        for len in 0..buf.len() {
+            let mut truncated = ByteBuf::from(&buf.as_slice()[..len]);
+            assert_eq!(
+                DeserializeError::BufferTooSmall,
+                deserialize_new::<clientbound::EncryptionRequest>(
+                    &mut truncated,
+                    &ProtocolVersion::V_1_12_2
+                )
+                .unwrap_err()
+            );
+        }
+
+        // Strings must be UTF-8
+        let mut buf = ByteBuf::from(&[2, 0xC3, 0x28][..]);
+        assert_eq!(
+            DeserializeError::InvalidString,
+            deserialize_new::<clientbound::LoginDisconnect>(&mut buf, &ProtocolVersion::V_1_12_2)
+                .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn valid_multi_block_change_test() {
+        let change = clientbound::MultiBlockChange {
+            chunk_x: -2,
+            chunk_z: 9,
+            records: vec![
+                clientbound::BlockChangeRecord {
+                    x: 15,
+                    y: 255,
+                    z: 1,
+                    block_state: 1 << 4,
+                },
+                clientbound::BlockChangeRecord {
+                    x: 0,
+                    y: 64,
+                    z: 14,
+                    block_state: 0,
+                },
+            ],
+        };
+
+        let mut buf = change
+            .serialize_with_id(&ProtocolVersion::V_1_12_2)
+            .unwrap();
+        assert_eq!(16, buf.len());
+        // Id, x, z and count, then the first record's packed x and z
+        assert_eq!(0xF1, buf.as_slice()[10]);
+
+        assert_eq!(
+            PacketID::MultiBlockChange
+                .id(&ProtocolVersion::V_1_12_2)
+                .unwrap(),
+            buf.read_var_int().unwrap()
+        );
+        let change2 =
+            deserialize_new::<clientbound::MultiBlockChange>(&mut buf, &ProtocolVersion::V_1_12_2)
+                .unwrap();
+        assert_eq!(change.chunk_x, change2.chunk_x);
+        assert_eq!(change.chunk_z, change2.chunk_z);
+        assert_eq!(change.records, change2.records);
+    }
+
+    // Writes `packet` as `ver` and reads it back, returning the packet and
+    // how long its body was
+    fn round_trip<T: Default + Packet>(packet: &T, ver: &ProtocolVersion) -> (Box<T>, usize) {
+        let mut buf = ByteBuf::new();
+        packet.serialize(&mut buf, ver);
+        let len = buf.len();
+        let read = deserialize_new::<T>(&mut buf, ver).unwrap();
+        assert!(buf.end());
+        (read, len)
+    }
+
+    #[test]
+    fn keep_alive_layouts() {
+        let keep_alive = clientbound::KeepAlive { id: 1337 };
+        for ver in ProtocolVersion::ALL {
+            let (read, len) = round_trip(&keep_alive, ver);
+            assert_eq!(1337, read.id);
+            // A VarInt in 1.8, a Long after
+            let expected = if *ver == ProtocolVersion::V_1_8_9 {
+                2
+            } else {
+                8
+            };
+            assert_eq!(expected, len);
+            assert_eq!(expected, round_trip(&KeepAlive { id: 1337 }, ver).1);
+        }
+    }
+
+    #[test]
+    fn login_layouts() {
+        let success = clientbound::LoginSuccess {
+            uuid: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string(),
+            username: "Notch".to_string(),
+            properties: vec![clientbound::Property {
+                name: "textures".to_string(),
+                value: "e30=".to_string(),
+                signature: Some("c2ln".to_string()),
+            }],
+        };
+        for ver in ProtocolVersion::ALL {
+            let (read, len) = round_trip(&success, ver);
+            assert_eq!(success.uuid, read.uuid);
+            assert_eq!(success.username, read.username);
+            match ver {
+                ProtocolVersion::V_1_8_9 | ProtocolVersion::V_1_12_2 => assert_eq!(43, len),
+                ProtocolVersion::V_1_16_5 => assert_eq!(22, len),
+                ProtocolVersion::V_1_20_1 => {
+                    assert_eq!(success.properties, read.properties);
+                    assert_eq!(43, len);
+                }
+            }
+            if *ver < ProtocolVersion::V_1_20_1 {
+                assert!(read.properties.is_empty());
+            }
+        }
+
+        let login_start = LoginStart {
+            username: "Notch".to_string(),
+            uuid: Some(1),
+        };
+        assert_eq!(6, round_trip(&login_start, &ProtocolVersion::V_1_16_5).1);
+        let (read, len) = round_trip(&login_start, &ProtocolVersion::V_1_20_1);
+        assert_eq!(Some(1), read.uuid);
+        assert_eq!(23, len);
+    }
+
+    #[test]
+    fn chat_layouts() {
+        let chat = clientbound::ChatMessage {
+            json: "{\"text\":\"hi\"}".to_string(),
+            position: clientbound::CHAT,
+            sender: 7,
+        };
+        let json_len = chat.json.len() + 1;
+
+        let (read, len) = round_trip(&chat, &ProtocolVersion::V_1_8_9);
+        assert_eq!((clientbound::CHAT, 0), (read.position, read.sender));
+        assert_eq!(json_len + 1, len);
+        assert_eq!(
+            json_len + 1,
+            round_trip(&chat, &ProtocolVersion::V_1_12_2).1
+        );
+
+        let (read, len) = round_trip(&chat, &ProtocolVersion::V_1_16_5);
+        assert_eq!(chat, *read);
+        assert_eq!(json_len + 17, len);
+
+        // System Chat only knows whether it's shown above the hotbar
+        let (read, _) = round_trip(&chat, &ProtocolVersion::V_1_20_1);
+        assert_eq!((clientbound::SYSTEM, 0), (read.position, read.sender));
+        let game_info = clientbound::ChatMessage {
+            position: clientbound::GAME_INFO,
+            ..chat
+        };
+        let (read, _) = round_trip(&game_info, &ProtocolVersion::V_1_20_1);
+        assert_eq!(clientbound::GAME_INFO, read.position);
+
+        let chat = serverbound::ChatMessage {
+            message: "/afk".to_string(),
+        };
+        for ver in ProtocolVersion::ALL {
+            let (read, len) = round_trip(&chat, ver);
+            assert_eq!(chat, *read);
+            // Unsigned with nothing acknowledged
+            let expected = if *ver == ProtocolVersion::V_1_20_1 {
+                26
+            } else {
+                5
+            };
+            assert_eq!(expected, len);
+        }
+    }
+
+    #[test]
+    fn position_layouts() {
+        let teleport = clientbound::PlayerPositionAndLook {
+            x: 0.5,
+            y: 64.0,
+            z: -10.5,
+            yaw: 90.0,
+            pitch: -45.0,
+            flags: clientbound::RELATIVE_YAW | clientbound::RELATIVE_PITCH,
+            teleport_id: 300,
+        };
+        for ver in ProtocolVersion::ALL {
+            let (read, len) = round_trip(&teleport, ver);
+            if *ver == ProtocolVersion::V_1_8_9 {
+                // Teleports weren't confirmed yet
+                assert_eq!(0, read.teleport_id);
+                assert_eq!(33, len);
+            } else {
+                assert_eq!(teleport, *read);
+                assert_eq!(35, len);
+            }
+        }
+
+        let position = PlayerPositionAndLook {
+            x: 0.5,
+            y: 64.0,
+            z: -10.5,
+            yaw: 90.0,
+            pitch: -45.0,
+            on_ground: true,
+        };
+        let moved = PlayerPosition {
+            x: 1.0,
+            y: 65.0,
+            z: 2.0,
+            on_ground: false,
+        };
+        for ver in ProtocolVersion::ALL {
+            assert_eq!(position, *round_trip(&position, ver).0);
+            assert_eq!(moved, *round_trip(&moved, ver).0);
+            assert_ne!(position.resolve_id(ver), moved.resolve_id(ver));
+        }
+    }
+
+    #[test]
+    fn combat_layouts() {
+        let death = clientbound::CombatEvent {
+            event: clientbound::ENTITY_DEAD,
+            player_id: 7,
+            entity_id: -1,
+            message: r#"{"text":"idler fell"}"#.to_string(),
+            ..clientbound::CombatEvent::default()
+        };
+        for ver in ProtocolVersion::ALL {
+            let (read, _) = round_trip(&death, ver);
+            if *ver == ProtocolVersion::V_1_20_1 {
+                // Player Combat Kill doesn't say who did it
+                assert_eq!((7, 0), (read.player_id, read.entity_id));
+                assert_eq!(death.message, read.message);
+            } else {
+                assert_eq!(death, *read);
+            }
+        }
+        let mut buf = ByteBuf::new();
+        buf.write_var_int(3);
+        assert_eq!(
+            DeserializeError::InvalidValue,
+            deserialize_new::<clientbound::CombatEvent>(&mut buf, &ProtocolVersion::V_1_12_2)
+                .unwrap_err()
+        );
+
+        let spawn = clientbound::SpawnPlayer {
+            entity_id: 12,
+            uuid: 0xc0ffee,
+            x: 100.5,
+            y: 64.0,
+            z: -20.25,
+            yaw: 64,
+            pitch: 0,
+        };
+        for ver in ProtocolVersion::ALL {
+            let mut buf = ByteBuf::new();
+            spawn.serialize(&mut buf, ver);
+            // The metadata is left unread
+            let expected = match ver {
+                ProtocolVersion::V_1_8_9 => 1 + 16 + 12 + 2 + 3,
+                ProtocolVersion::V_1_12_2 => 1 + 16 + 24 + 2 + 1,
+                _ => 1 + 16 + 24 + 2,
+            };
+            assert_eq!(expected, buf.len());
+            let read = deserialize_new::<clientbound::SpawnPlayer>(&mut buf, ver).unwrap();
+            assert_eq!(spawn, *read);
+        }
+    }
+
+    // A packet the registry doesn't know, with every field attribute
+    #[derive(Debug, Default, PartialEq, Packet)]
+    #[id(0x7F, state = "play")]
+    struct Custom {
+        #[varint]
+        small: i32,
+        #[prefixed]
+        values: Vec<i16>,
+        #[since(754)]
+        #[prefixed]
+        names: Vec<String>,
+        flag: bool,
+    }
+
+    #[test]
+    fn derived_packets() {
+        let custom = Custom {
+            small: 1,
+            values: vec![-1, 2],
+            names: vec!["a".to_string()],
+            flag: true,
+        };
+        assert_eq!(State::Play, Custom::STATE);
+
+        let ver = ProtocolVersion::V_1_16_5;
+        let mut buf = custom.serialize_with_id(&ver).unwrap();
+        assert_eq!(0x7F, buf.read_var_int().unwrap());
+        assert_eq!(
+            &[1, 2, 0xFF, 0xFF, 0, 2, 1, 1, b'a', 1],
+            buf.remaining_slice()
+        );
+        assert_eq!(custom, *deserialize_new::<Custom>(&mut buf, &ver).unwrap());
+
+        // Older versions don't have `names`
+        let (read, len) = round_trip(&custom, &ProtocolVersion::V_1_12_2);
+        assert_eq!(7, len);
+        assert!(read.names.is_empty());
+        assert_eq!(custom.values, read.values);
+        assert!(read.flag);
+
+        // Handshakes can only ask for status or login
+        let mut buf = ByteBuf::from(&[0xD4, 0x02, 0, 0x63, 0xDD, 3][..]);
+        assert_eq!(
+            DeserializeError::InvalidValue,
+            deserialize_new::<Handshake>(&mut buf, &ver).unwrap_err()
+        );
+    }
 }
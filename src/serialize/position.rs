@@ -0,0 +1,77 @@
+// A block position, packed into a long on the wire as
+// x: 26 bits, y: 12 bits, z: 26 bits
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Position {
+    pub fn from_packed(v: i64) -> Position {
+        // Shift each field to the top of the long so it is sign extended
+        Position {
+            x: (v >> 38) as i32,
+            y: ((v << 26) >> 52) as i32,
+            z: ((v << 38) >> 38) as i32,
+        }
+    }
+
+    pub fn packed(&self) -> i64 {
+        ((self.x as i64 & 0x3FFFFFF) << 38)
+            | ((self.y as i64 & 0xFFF) << 26)
+            | (self.z as i64 & 0x3FFFFFF)
+    }
+
+    // The chunk column this block is in
+    pub fn chunk(&self) -> (i32, i32) {
+        (self.x >> 4, self.z >> 4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::position::*;
+
+    #[test]
+    fn packing_round_trip() {
+        let positions = [
+            Position { x: 0, y: 0, z: 0 },
+            Position {
+                x: -3,
+                y: 64,
+                z: 40,
+            },
+            Position {
+                x: 33554431,
+                y: 255,
+                z: -33554432,
+            },
+            Position {
+                x: -33554432,
+                y: 2047,
+                z: 33554431,
+            },
+        ];
+        for pos in positions.iter() {
+            assert_eq!(*pos, Position::from_packed(pos.packed()));
+        }
+    }
+
+    #[test]
+    fn known_packing() {
+        let pos = Position::from_packed(0x4607630cfec15b48);
+        assert_eq!(18357644, pos.x);
+        assert_eq!(831, pos.y);
+        assert_eq!(-20882616, pos.z);
+        assert_eq!(
+            (-1, 2),
+            Position {
+                x: -3,
+                y: 64,
+                z: 40
+            }
+            .chunk()
+        );
+    }
+}
@@ -1,8 +1,110 @@
+// Declared oldest first so versions compare by age
 #[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ProtocolVersion {
+    V_1_8_9 = 47,   // 1.8 to 1.8.9
     V_1_12_2 = 340, // 1.12.2
+    V_1_16_5 = 754, // 1.16.4 and 1.16.5
+    V_1_20_1 = 763, // 1.20 and 1.20.1
+}
+
+impl ProtocolVersion {
+    pub const ALL: &'static [ProtocolVersion] = &[
+        ProtocolVersion::V_1_8_9,
+        ProtocolVersion::V_1_12_2,
+        ProtocolVersion::V_1_16_5,
+        ProtocolVersion::V_1_20_1,
+    ];
+
+    // The version a server advertising `protocol` in its status speaks,
+    // None if we can't talk to it
+    pub fn from_protocol(protocol: i32) -> Option<ProtocolVersion> {
+        ProtocolVersion::ALL
+            .iter()
+            .copied()
+            .find(|ver| *ver as i32 == protocol)
+    }
+
+    // The newest release speaking this version
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtocolVersion::V_1_8_9 => "1.8.9",
+            ProtocolVersion::V_1_12_2 => "1.12.2",
+            ProtocolVersion::V_1_16_5 => "1.16.5",
+            ProtocolVersion::V_1_20_1 => "1.20.1",
+        }
+    }
+
+    // Any release speaking a version we support, e.g. "1.16.4"
+    pub fn from_name(name: &str) -> Option<ProtocolVersion> {
+        match name {
+            "1.8" | "1.8.1" | "1.8.2" | "1.8.3" | "1.8.4" | "1.8.5" | "1.8.6" | "1.8.7"
+            | "1.8.8" | "1.8.9" => Some(ProtocolVersion::V_1_8_9),
+            "1.12.2" => Some(ProtocolVersion::V_1_12_2),
+            "1.16.4" | "1.16.5" => Some(ProtocolVersion::V_1_16_5),
+            "1.20" | "1.20.1" => Some(ProtocolVersion::V_1_20_1),
+            _ => None,
+        }
+    }
 }
 
 pub trait ProtocolToID {
-    fn resolve_id(&self, ver: &ProtocolVersion) -> i32;
+    // None if `ver` doesn't have the packet
+    fn resolve_id(&self, ver: &ProtocolVersion) -> Option<i32>;
+}
+
+// Which set of packets the connection is currently speaking
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum State {
+    Handshaking,
+    Status,
+    Login,
+    Play,
+    Disconnected, // The server sent Disconnect, nothing more will be read
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::protocol::*;
+
+    #[test]
+    fn versions_from_protocol() {
+        assert_eq!(
+            Some(ProtocolVersion::V_1_8_9),
+            ProtocolVersion::from_protocol(47)
+        );
+        assert_eq!(
+            Some(ProtocolVersion::V_1_12_2),
+            ProtocolVersion::from_protocol(340)
+        );
+        assert_eq!(
+            Some(ProtocolVersion::V_1_16_5),
+            ProtocolVersion::from_protocol(754)
+        );
+        assert_eq!(
+            Some(ProtocolVersion::V_1_20_1),
+            ProtocolVersion::from_protocol(763)
+        );
+        // 1.12.1 and 1.20.2
+        assert_eq!(None, ProtocolVersion::from_protocol(338));
+        assert_eq!(None, ProtocolVersion::from_protocol(764));
+        assert!(ProtocolVersion::V_1_8_9 < ProtocolVersion::V_1_12_2);
+    }
+
+    #[test]
+    fn versions_from_name() {
+        for &ver in ProtocolVersion::ALL {
+            assert_eq!(Some(ver), ProtocolVersion::from_name(ver.name()));
+        }
+        assert_eq!(
+            Some(ProtocolVersion::V_1_16_5),
+            ProtocolVersion::from_name("1.16.4")
+        );
+        assert_eq!(
+            Some(ProtocolVersion::V_1_20_1),
+            ProtocolVersion::from_name("1.20")
+        );
+        assert_eq!(None, ProtocolVersion::from_name("1.12.1"));
+        assert_eq!(None, ProtocolVersion::from_name("1.20.2"));
+    }
 }
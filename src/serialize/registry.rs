@@ -0,0 +1,548 @@
+use crate::serialize::packet::PacketID;
+use crate::serialize::protocol::{ProtocolVersion, State};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Clientbound,
+    Serverbound,
+}
+
+use Direction::*;
+
+type Entry = (State, Direction, i32, PacketID);
+
+// Every packet in protocol 340, see https://wiki.vg/index.php?title=Protocol&oldid=14204
+const V_1_12_2: &[Entry] = &[
+    (State::Handshaking, Serverbound, 0x00, PacketID::Handshake),
+    (State::Status, Clientbound, 0x00, PacketID::StatusResponse),
+    (State::Status, Clientbound, 0x01, PacketID::Pong),
+    (State::Status, Serverbound, 0x00, PacketID::StatusRequest),
+    (State::Status, Serverbound, 0x01, PacketID::Ping),
+    (State::Login, Clientbound, 0x00, PacketID::LoginDisconnect),
+    (State::Login, Clientbound, 0x01, PacketID::EncryptionRequest),
+    (State::Login, Clientbound, 0x02, PacketID::LoginSuccess),
+    (State::Login, Clientbound, 0x03, PacketID::SetCompression),
+    (State::Login, Serverbound, 0x00, PacketID::LoginStart),
+    (
+        State::Login,
+        Serverbound,
+        0x01,
+        PacketID::EncryptionResponse,
+    ),
+    (State::Play, Clientbound, 0x00, PacketID::SpawnObject),
+    (State::Play, Clientbound, 0x01, PacketID::SpawnExperienceOrb),
+    (State::Play, Clientbound, 0x02, PacketID::SpawnGlobalEntity),
+    (State::Play, Clientbound, 0x03, PacketID::SpawnMob),
+    (State::Play, Clientbound, 0x04, PacketID::SpawnPainting),
+    (State::Play, Clientbound, 0x05, PacketID::SpawnPlayer),
+    (State::Play, Clientbound, 0x06, PacketID::AnimationCB),
+    (State::Play, Clientbound, 0x07, PacketID::Statistics),
+    (
+        State::Play,
+        Clientbound,
+        0x08,
+        PacketID::BlockBreakAnimation,
+    ),
+    (State::Play, Clientbound, 0x09, PacketID::UpdateBlockEntity),
+    (State::Play, Clientbound, 0x0A, PacketID::BlockAction),
+    (State::Play, Clientbound, 0x0B, PacketID::BlockChange),
+    (State::Play, Clientbound, 0x0C, PacketID::BossBar),
+    (State::Play, Clientbound, 0x0D, PacketID::ServerDifficulty),
+    (State::Play, Clientbound, 0x0E, PacketID::TabCompleteCB),
+    (State::Play, Clientbound, 0x0F, PacketID::ChatMessageCB),
+    (State::Play, Clientbound, 0x10, PacketID::MultiBlockChange),
+    (
+        State::Play,
+        Clientbound,
+        0x11,
+        PacketID::ConfirmTransactionCB,
+    ),
+    (State::Play, Clientbound, 0x12, PacketID::CloseWindowCB),
+    (State::Play, Clientbound, 0x13, PacketID::OpenWindow),
+    (State::Play, Clientbound, 0x14, PacketID::WindowItems),
+    (State::Play, Clientbound, 0x15, PacketID::WindowProperty),
+    (State::Play, Clientbound, 0x16, PacketID::SetSlot),
+    (State::Play, Clientbound, 0x17, PacketID::SetCooldown),
+    (State::Play, Clientbound, 0x18, PacketID::PluginMessageCB),
+    (State::Play, Clientbound, 0x19, PacketID::NamedSoundEffect),
+    (State::Play, Clientbound, 0x1A, PacketID::Disconnect),
+    (State::Play, Clientbound, 0x1B, PacketID::EntityStatus),
+    (State::Play, Clientbound, 0x1C, PacketID::Explosion),
+    (State::Play, Clientbound, 0x1D, PacketID::UnloadChunk),
+    (State::Play, Clientbound, 0x1E, PacketID::ChangeGameState),
+    (State::Play, Clientbound, 0x1F, PacketID::KeepAliveCB),
+    (State::Play, Clientbound, 0x20, PacketID::ChunkData),
+    (State::Play, Clientbound, 0x21, PacketID::Effect),
+    (State::Play, Clientbound, 0x22, PacketID::Particle),
+    (State::Play, Clientbound, 0x23, PacketID::JoinGame),
+    (State::Play, Clientbound, 0x24, PacketID::Map),
+    (State::Play, Clientbound, 0x25, PacketID::Entity),
+    (State::Play, Clientbound, 0x26, PacketID::EntityRelativeMove),
+    (
+        State::Play,
+        Clientbound,
+        0x27,
+        PacketID::EntityLookAndRelativeMove,
+    ),
+    (State::Play, Clientbound, 0x28, PacketID::EntityLook),
+    (State::Play, Clientbound, 0x29, PacketID::VehicleMoveCB),
+    (State::Play, Clientbound, 0x2A, PacketID::OpenSignEditor),
+    (
+        State::Play,
+        Clientbound,
+        0x2B,
+        PacketID::CraftRecipeResponse,
+    ),
+    (State::Play, Clientbound, 0x2C, PacketID::PlayerAbilitiesCB),
+    (State::Play, Clientbound, 0x2D, PacketID::CombatEvent),
+    (State::Play, Clientbound, 0x2E, PacketID::PlayerListItem),
+    (
+        State::Play,
+        Clientbound,
+        0x2F,
+        PacketID::PlayerPositionAndLookCB,
+    ),
+    (State::Play, Clientbound, 0x30, PacketID::UseBed),
+    (State::Play, Clientbound, 0x31, PacketID::UnlockRecipes),
+    (State::Play, Clientbound, 0x32, PacketID::DestroyEntities),
+    (State::Play, Clientbound, 0x33, PacketID::RemoveEntityEffect),
+    (State::Play, Clientbound, 0x34, PacketID::ResourcePackSend),
+    (State::Play, Clientbound, 0x35, PacketID::Respawn),
+    (State::Play, Clientbound, 0x36, PacketID::EntityHeadLook),
+    (
+        State::Play,
+        Clientbound,
+        0x37,
+        PacketID::SelectAdvancementTab,
+    ),
+    (State::Play, Clientbound, 0x38, PacketID::WorldBorder),
+    (State::Play, Clientbound, 0x39, PacketID::Camera),
+    (State::Play, Clientbound, 0x3A, PacketID::HeldItemChangeCB),
+    (State::Play, Clientbound, 0x3B, PacketID::DisplayScoreboard),
+    (State::Play, Clientbound, 0x3C, PacketID::EntityMetadata),
+    (State::Play, Clientbound, 0x3D, PacketID::AttachEntity),
+    (State::Play, Clientbound, 0x3E, PacketID::EntityVelocity),
+    (State::Play, Clientbound, 0x3F, PacketID::EntityEquipment),
+    (State::Play, Clientbound, 0x40, PacketID::SetExperience),
+    (State::Play, Clientbound, 0x41, PacketID::UpdateHealth),
+    (
+        State::Play,
+        Clientbound,
+        0x42,
+        PacketID::ScoreboardObjective,
+    ),
+    (State::Play, Clientbound, 0x43, PacketID::SetPassengers),
+    (State::Play, Clientbound, 0x44, PacketID::Teams),
+    (State::Play, Clientbound, 0x45, PacketID::UpdateScore),
+    (State::Play, Clientbound, 0x46, PacketID::SpawnPosition),
+    (State::Play, Clientbound, 0x47, PacketID::TimeUpdate),
+    (State::Play, Clientbound, 0x48, PacketID::Title),
+    (State::Play, Clientbound, 0x49, PacketID::SoundEffect),
+    (
+        State::Play,
+        Clientbound,
+        0x4A,
+        PacketID::PlayerListHeaderAndFooter,
+    ),
+    (State::Play, Clientbound, 0x4B, PacketID::CollectItem),
+    (State::Play, Clientbound, 0x4C, PacketID::EntityTeleport),
+    (State::Play, Clientbound, 0x4D, PacketID::Advancements),
+    (State::Play, Clientbound, 0x4E, PacketID::EntityProperties),
+    (State::Play, Clientbound, 0x4F, PacketID::EntityEffect),
+    (State::Play, Serverbound, 0x00, PacketID::TeleportConfirm),
+    (State::Play, Serverbound, 0x01, PacketID::TabCompleteSB),
+    (State::Play, Serverbound, 0x02, PacketID::ChatMessageSB),
+    (State::Play, Serverbound, 0x03, PacketID::ClientStatus),
+    (State::Play, Serverbound, 0x04, PacketID::ClientSettings),
+    (
+        State::Play,
+        Serverbound,
+        0x05,
+        PacketID::ConfirmTransactionSB,
+    ),
+    (State::Play, Serverbound, 0x06, PacketID::EnchantItem),
+    (State::Play, Serverbound, 0x07, PacketID::ClickWindow),
+    (State::Play, Serverbound, 0x08, PacketID::CloseWindowSB),
+    (State::Play, Serverbound, 0x09, PacketID::PluginMessageSB),
+    (State::Play, Serverbound, 0x0A, PacketID::UseEntity),
+    (State::Play, Serverbound, 0x0B, PacketID::KeepAliveSB),
+    (State::Play, Serverbound, 0x0C, PacketID::Player),
+    (State::Play, Serverbound, 0x0D, PacketID::PlayerPosition),
+    (
+        State::Play,
+        Serverbound,
+        0x0E,
+        PacketID::PlayerPositionAndLookSB,
+    ),
+    (State::Play, Serverbound, 0x0F, PacketID::PlayerLook),
+    (State::Play, Serverbound, 0x10, PacketID::VehicleMoveSB),
+    (State::Play, Serverbound, 0x11, PacketID::SteerBoat),
+    (State::Play, Serverbound, 0x12, PacketID::CraftRecipeRequest),
+    (State::Play, Serverbound, 0x13, PacketID::PlayerAbilitiesSB),
+    (State::Play, Serverbound, 0x14, PacketID::PlayerDigging),
+    (State::Play, Serverbound, 0x15, PacketID::EntityAction),
+    (State::Play, Serverbound, 0x16, PacketID::SteerVehicle),
+    (State::Play, Serverbound, 0x17, PacketID::CraftingBookData),
+    (State::Play, Serverbound, 0x18, PacketID::ResourcePackStatus),
+    (State::Play, Serverbound, 0x19, PacketID::AdvancementTab),
+    (State::Play, Serverbound, 0x1A, PacketID::HeldItemChangeSB),
+    (
+        State::Play,
+        Serverbound,
+        0x1B,
+        PacketID::CreativeInventoryAction,
+    ),
+    (State::Play, Serverbound, 0x1C, PacketID::UpdateSign),
+    (State::Play, Serverbound, 0x1D, PacketID::AnimationSB),
+    (State::Play, Serverbound, 0x1E, PacketID::Spectate),
+    (
+        State::Play,
+        Serverbound,
+        0x1F,
+        PacketID::PlayerBlockPlacement,
+    ),
+    (State::Play, Serverbound, 0x20, PacketID::UseItem),
+];
+
+// Only the packets we read or write in the other versions, everything else
+// looks up as `PacketID::Unknown` and is passed through untouched
+
+// See https://wiki.vg/index.php?title=Protocol&oldid=7368
+const V_1_8_9: &[Entry] = &[
+    (State::Handshaking, Serverbound, 0x00, PacketID::Handshake),
+    (State::Status, Clientbound, 0x00, PacketID::StatusResponse),
+    (State::Status, Clientbound, 0x01, PacketID::Pong),
+    (State::Status, Serverbound, 0x00, PacketID::StatusRequest),
+    (State::Status, Serverbound, 0x01, PacketID::Ping),
+    (State::Login, Clientbound, 0x00, PacketID::LoginDisconnect),
+    (State::Login, Clientbound, 0x01, PacketID::EncryptionRequest),
+    (State::Login, Clientbound, 0x02, PacketID::LoginSuccess),
+    (State::Login, Clientbound, 0x03, PacketID::SetCompression),
+    (State::Login, Serverbound, 0x00, PacketID::LoginStart),
+    (
+        State::Login,
+        Serverbound,
+        0x01,
+        PacketID::EncryptionResponse,
+    ),
+    (State::Play, Clientbound, 0x00, PacketID::KeepAliveCB),
+    (State::Play, Clientbound, 0x01, PacketID::JoinGame),
+    (State::Play, Clientbound, 0x02, PacketID::ChatMessageCB),
+    (State::Play, Clientbound, 0x06, PacketID::UpdateHealth),
+    (State::Play, Clientbound, 0x07, PacketID::Respawn),
+    (
+        State::Play,
+        Clientbound,
+        0x08,
+        PacketID::PlayerPositionAndLookCB,
+    ),
+    (State::Play, Clientbound, 0x0C, PacketID::SpawnPlayer),
+    (State::Play, Clientbound, 0x13, PacketID::DestroyEntities),
+    (State::Play, Clientbound, 0x15, PacketID::EntityRelativeMove),
+    (State::Play, Clientbound, 0x16, PacketID::EntityLook),
+    (
+        State::Play,
+        Clientbound,
+        0x17,
+        PacketID::EntityLookAndRelativeMove,
+    ),
+    (State::Play, Clientbound, 0x18, PacketID::EntityTeleport),
+    (State::Play, Clientbound, 0x38, PacketID::PlayerListItem),
+    (State::Play, Clientbound, 0x40, PacketID::Disconnect),
+    (State::Play, Clientbound, 0x42, PacketID::CombatEvent),
+    (State::Play, Serverbound, 0x00, PacketID::KeepAliveSB),
+    (State::Play, Serverbound, 0x01, PacketID::ChatMessageSB),
+    (State::Play, Serverbound, 0x03, PacketID::Player),
+    (State::Play, Serverbound, 0x04, PacketID::PlayerPosition),
+    (State::Play, Serverbound, 0x05, PacketID::PlayerLook),
+    (
+        State::Play,
+        Serverbound,
+        0x06,
+        PacketID::PlayerPositionAndLookSB,
+    ),
+    (State::Play, Serverbound, 0x0A, PacketID::AnimationSB),
+    (State::Play, Serverbound, 0x0B, PacketID::EntityAction),
+    (State::Play, Serverbound, 0x16, PacketID::ClientStatus),
+];
+
+// See https://wiki.vg/index.php?title=Protocol&oldid=16681
+const V_1_16_5: &[Entry] = &[
+    (State::Handshaking, Serverbound, 0x00, PacketID::Handshake),
+    (State::Status, Clientbound, 0x00, PacketID::StatusResponse),
+    (State::Status, Clientbound, 0x01, PacketID::Pong),
+    (State::Status, Serverbound, 0x00, PacketID::StatusRequest),
+    (State::Status, Serverbound, 0x01, PacketID::Ping),
+    (State::Login, Clientbound, 0x00, PacketID::LoginDisconnect),
+    (State::Login, Clientbound, 0x01, PacketID::EncryptionRequest),
+    (State::Login, Clientbound, 0x02, PacketID::LoginSuccess),
+    (State::Login, Clientbound, 0x03, PacketID::SetCompression),
+    (State::Login, Serverbound, 0x00, PacketID::LoginStart),
+    (
+        State::Login,
+        Serverbound,
+        0x01,
+        PacketID::EncryptionResponse,
+    ),
+    (State::Play, Clientbound, 0x04, PacketID::SpawnPlayer),
+    (State::Play, Clientbound, 0x0E, PacketID::ChatMessageCB),
+    (State::Play, Clientbound, 0x19, PacketID::Disconnect),
+    (State::Play, Clientbound, 0x1F, PacketID::KeepAliveCB),
+    (State::Play, Clientbound, 0x24, PacketID::JoinGame),
+    (State::Play, Clientbound, 0x27, PacketID::EntityRelativeMove),
+    (
+        State::Play,
+        Clientbound,
+        0x28,
+        PacketID::EntityLookAndRelativeMove,
+    ),
+    (State::Play, Clientbound, 0x29, PacketID::EntityLook),
+    (State::Play, Clientbound, 0x31, PacketID::CombatEvent),
+    (State::Play, Clientbound, 0x32, PacketID::PlayerListItem),
+    (
+        State::Play,
+        Clientbound,
+        0x34,
+        PacketID::PlayerPositionAndLookCB,
+    ),
+    (State::Play, Clientbound, 0x36, PacketID::DestroyEntities),
+    (State::Play, Clientbound, 0x39, PacketID::Respawn),
+    (State::Play, Clientbound, 0x49, PacketID::UpdateHealth),
+    (State::Play, Clientbound, 0x56, PacketID::EntityTeleport),
+    (State::Play, Serverbound, 0x00, PacketID::TeleportConfirm),
+    (State::Play, Serverbound, 0x03, PacketID::ChatMessageSB),
+    (State::Play, Serverbound, 0x04, PacketID::ClientStatus),
+    (State::Play, Serverbound, 0x10, PacketID::KeepAliveSB),
+    (State::Play, Serverbound, 0x12, PacketID::PlayerPosition),
+    (
+        State::Play,
+        Serverbound,
+        0x13,
+        PacketID::PlayerPositionAndLookSB,
+    ),
+    (State::Play, Serverbound, 0x14, PacketID::PlayerLook),
+    (State::Play, Serverbound, 0x15, PacketID::Player),
+    (State::Play, Serverbound, 0x1C, PacketID::EntityAction),
+    (State::Play, Serverbound, 0x2C, PacketID::AnimationSB),
+];
+
+// See https://wiki.vg/index.php?title=Protocol&oldid=18375. Chat sent by
+// players is signed and isn't decoded, `ChatMessageCB` is System Chat.
+const V_1_20_1: &[Entry] = &[
+    (State::Handshaking, Serverbound, 0x00, PacketID::Handshake),
+    (State::Status, Clientbound, 0x00, PacketID::StatusResponse),
+    (State::Status, Clientbound, 0x01, PacketID::Pong),
+    (State::Status, Serverbound, 0x00, PacketID::StatusRequest),
+    (State::Status, Serverbound, 0x01, PacketID::Ping),
+    (State::Login, Clientbound, 0x00, PacketID::LoginDisconnect),
+    (State::Login, Clientbound, 0x01, PacketID::EncryptionRequest),
+    (State::Login, Clientbound, 0x02, PacketID::LoginSuccess),
+    (State::Login, Clientbound, 0x03, PacketID::SetCompression),
+    (State::Login, Serverbound, 0x00, PacketID::LoginStart),
+    (
+        State::Login,
+        Serverbound,
+        0x01,
+        PacketID::EncryptionResponse,
+    ),
+    (State::Play, Clientbound, 0x03, PacketID::SpawnPlayer),
+    (State::Play, Clientbound, 0x1A, PacketID::Disconnect),
+    (State::Play, Clientbound, 0x23, PacketID::KeepAliveCB),
+    (State::Play, Clientbound, 0x28, PacketID::JoinGame),
+    (State::Play, Clientbound, 0x2B, PacketID::EntityRelativeMove),
+    (
+        State::Play,
+        Clientbound,
+        0x2C,
+        PacketID::EntityLookAndRelativeMove,
+    ),
+    (State::Play, Clientbound, 0x2D, PacketID::EntityLook),
+    (State::Play, Clientbound, 0x38, PacketID::CombatEvent), // Player Combat Kill
+    (
+        State::Play,
+        Clientbound,
+        0x3C,
+        PacketID::PlayerPositionAndLookCB,
+    ),
+    (State::Play, Clientbound, 0x39, PacketID::PlayerInfoRemove),
+    (State::Play, Clientbound, 0x3A, PacketID::PlayerListItem), // Player Info Update
+    (State::Play, Clientbound, 0x3E, PacketID::DestroyEntities), // Remove Entities
+    (State::Play, Clientbound, 0x41, PacketID::Respawn),
+    (State::Play, Clientbound, 0x57, PacketID::UpdateHealth),
+    (State::Play, Clientbound, 0x64, PacketID::ChatMessageCB),
+    (State::Play, Clientbound, 0x68, PacketID::EntityTeleport),
+    (State::Play, Serverbound, 0x00, PacketID::TeleportConfirm),
+    (State::Play, Serverbound, 0x04, PacketID::ChatCommand),
+    (State::Play, Serverbound, 0x05, PacketID::ChatMessageSB),
+    (State::Play, Serverbound, 0x07, PacketID::ClientStatus),
+    (State::Play, Serverbound, 0x12, PacketID::KeepAliveSB),
+    (State::Play, Serverbound, 0x14, PacketID::PlayerPosition),
+    (
+        State::Play,
+        Serverbound,
+        0x15,
+        PacketID::PlayerPositionAndLookSB,
+    ),
+    (State::Play, Serverbound, 0x16, PacketID::PlayerLook),
+    (State::Play, Serverbound, 0x17, PacketID::Player),
+    (State::Play, Serverbound, 0x1E, PacketID::EntityAction),
+    (State::Play, Serverbound, 0x2F, PacketID::AnimationSB),
+];
+
+fn table(ver: &ProtocolVersion) -> &'static [Entry] {
+    match ver {
+        ProtocolVersion::V_1_8_9 => V_1_8_9,
+        ProtocolVersion::V_1_12_2 => V_1_12_2,
+        ProtocolVersion::V_1_16_5 => V_1_16_5,
+        ProtocolVersion::V_1_20_1 => V_1_20_1,
+    }
+}
+
+// Never fails, ids the registry doesn't know about come back as `PacketID::Unknown`
+pub fn lookup(ver: &ProtocolVersion, state: &State, direction: &Direction, id: i32) -> PacketID {
+    table(ver)
+        .iter()
+        .find(|(s, d, i, _)| s == state && d == direction && *i == id)
+        .map_or(PacketID::Unknown(id), |entry| entry.3)
+}
+
+// The wire id of `kind`, None if it doesn't exist in `ver`
+pub fn id_of(ver: &ProtocolVersion, kind: &PacketID) -> Option<i32> {
+    match kind {
+        PacketID::Unknown(id) => Some(*id),
+        _ => table(ver)
+            .iter()
+            .find(|(_, _, _, k)| k == kind)
+            .map(|entry| entry.2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::registry::*;
+
+    const VERSIONS: &[ProtocolVersion] = ProtocolVersion::ALL;
+
+    #[test]
+    fn lookup_known_ids() {
+        let ver = ProtocolVersion::V_1_12_2;
+        assert_eq!(
+            PacketID::SetCompression,
+            lookup(&ver, &State::Login, &Clientbound, 0x03)
+        );
+        assert_eq!(
+            PacketID::SpawnMob,
+            lookup(&ver, &State::Play, &Clientbound, 0x03)
+        );
+        assert_eq!(
+            PacketID::ClientStatus,
+            lookup(&ver, &State::Play, &Serverbound, 0x03)
+        );
+        assert_eq!(
+            PacketID::KeepAliveCB,
+            lookup(&ver, &State::Play, &Clientbound, 0x1F)
+        );
+        assert_eq!(
+            PacketID::EntityEffect,
+            lookup(&ver, &State::Play, &Clientbound, 0x4F)
+        );
+    }
+
+    #[test]
+    fn ids_depend_on_version() {
+        let ids = |kind| {
+            VERSIONS
+                .iter()
+                .map(|ver| id_of(ver, &kind).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![0x00, 0x1F, 0x1F, 0x23], ids(PacketID::KeepAliveCB));
+        assert_eq!(vec![0x00, 0x0B, 0x10, 0x12], ids(PacketID::KeepAliveSB));
+        assert_eq!(vec![0x02, 0x0F, 0x0E, 0x64], ids(PacketID::ChatMessageCB));
+        assert_eq!(vec![0x01, 0x02, 0x03, 0x05], ids(PacketID::ChatMessageSB));
+        assert_eq!(vec![0x40, 0x1A, 0x19, 0x1A], ids(PacketID::Disconnect));
+        assert_eq!(
+            vec![0x08, 0x2F, 0x34, 0x3C],
+            ids(PacketID::PlayerPositionAndLookCB)
+        );
+        assert_eq!(vec![0x04, 0x0D, 0x12, 0x14], ids(PacketID::PlayerPosition));
+        assert_eq!(vec![0x0A, 0x1D, 0x2C, 0x2F], ids(PacketID::AnimationSB));
+        assert_eq!(vec![0x0B, 0x15, 0x1C, 0x1E], ids(PacketID::EntityAction));
+        assert_eq!(vec![0x01, 0x23, 0x24, 0x28], ids(PacketID::JoinGame));
+        assert_eq!(vec![0x06, 0x41, 0x49, 0x57], ids(PacketID::UpdateHealth));
+        assert_eq!(vec![0x42, 0x2D, 0x31, 0x38], ids(PacketID::CombatEvent));
+        assert_eq!(vec![0x16, 0x03, 0x04, 0x07], ids(PacketID::ClientStatus));
+        assert_eq!(
+            vec![0x15, 0x26, 0x27, 0x2B],
+            ids(PacketID::EntityRelativeMove)
+        );
+        assert_eq!(vec![0x18, 0x4C, 0x56, 0x68], ids(PacketID::EntityTeleport));
+        assert_eq!(vec![0x13, 0x32, 0x36, 0x3E], ids(PacketID::DestroyEntities));
+        assert_eq!(vec![0x38, 0x2E, 0x32, 0x3A], ids(PacketID::PlayerListItem));
+
+        // 1.8 has no teleport ids to confirm
+        assert_eq!(
+            None,
+            id_of(&ProtocolVersion::V_1_8_9, &PacketID::TeleportConfirm)
+        );
+        // Mobs are spawned differently everywhere else
+        assert_eq!(None, id_of(&ProtocolVersion::V_1_16_5, &PacketID::SpawnMob));
+        // Commands were chat before 1.19
+        assert_eq!(
+            None,
+            id_of(&ProtocolVersion::V_1_16_5, &PacketID::ChatCommand)
+        );
+        // Removing players was an action before 1.19.3
+        assert_eq!(
+            None,
+            id_of(&ProtocolVersion::V_1_16_5, &PacketID::PlayerInfoRemove)
+        );
+        // Only 1.12.2 chunks are cached
+        assert_eq!(
+            PacketID::Unknown(0x20),
+            lookup(&ProtocolVersion::V_1_16_5, &State::Play, &Clientbound, 0x20)
+        );
+    }
+
+    #[test]
+    fn lookup_unknown_ids() {
+        let ver = ProtocolVersion::V_1_12_2;
+        for id in [-1, 0x50, 0x7F, i32::MAX, i32::MIN] {
+            assert_eq!(
+                PacketID::Unknown(id),
+                lookup(&ver, &State::Play, &Clientbound, id)
+            );
+        }
+        assert_eq!(
+            PacketID::Unknown(0x1F),
+            lookup(&ver, &State::Login, &Clientbound, 0x1F)
+        );
+        assert_eq!(
+            PacketID::Unknown(0x00),
+            lookup(&ver, &State::Handshaking, &Clientbound, 0x00)
+        );
+    }
+
+    #[test]
+    fn ids_round_trip() {
+        for ver in VERSIONS {
+            for (state, direction, id, kind) in table(ver) {
+                assert_eq!(Some(*id), id_of(ver, kind));
+                assert_eq!(*kind, lookup(ver, state, direction, *id));
+            }
+        }
+    }
+
+    #[test]
+    fn entries_are_unique() {
+        for ver in VERSIONS {
+            let entries = table(ver);
+            for (i, a) in entries.iter().enumerate() {
+                for b in &entries[i + 1..] {
+                    assert_ne!(a.3, b.3);
+                    assert!(a.0 != b.0 || a.1 != b.1 || a.2 != b.2);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::nbt::{ReadNbt, Tag, WriteNbt, TAG_END};
+use crate::serialize::var::DeserializeError;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+// An item stack, an empty slot is None
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Slot {
+    pub item_id: i16,
+    pub count: i8,
+    pub damage: i16,      // Also the item's variant, e.g. wool colour
+    pub nbt: Option<Tag>, // Enchantments, display name, etc.
+}
+
+const EMPTY: i16 = -1;
+
+pub trait ReadSlot {
+    fn read_slot(&mut self) -> Result<Option<Slot>, DeserializeError>;
+}
+
+pub trait WriteSlot {
+    fn write_slot(&mut self, slot: Option<&Slot>);
+}
+
+impl ReadSlot for ByteBuf {
+    fn read_slot(&mut self) -> Result<Option<Slot>, DeserializeError> {
+        let item_id = self.read_i16::<BigEndian>()?;
+        if item_id == EMPTY {
+            return Ok(None);
+        }
+        let count = self.read_i8()?;
+        let damage = self.read_i16::<BigEndian>()?;
+        let nbt = self.read_nbt()?.map(|(_, tag)| tag);
+        Ok(Some(Slot {
+            item_id,
+            count,
+            damage,
+            nbt,
+        }))
+    }
+}
+
+impl WriteSlot for ByteBuf {
+    fn write_slot(&mut self, slot: Option<&Slot>) {
+        let slot = match slot {
+            Some(slot) => slot,
+            None => {
+                self.write_i16::<BigEndian>(EMPTY).unwrap();
+                return;
+            }
+        };
+        self.write_i16::<BigEndian>(slot.item_id).unwrap();
+        self.write_i8(slot.count).unwrap();
+        self.write_i16::<BigEndian>(slot.damage).unwrap();
+        // Item NBT always has an empty root name. NBT too long to write is
+        // sent as none rather than as a malformed slot.
+        let written = match slot.nbt.as_ref() {
+            Some(tag) => self.write_nbt("", tag).is_ok(),
+            None => false,
+        };
+        if !written {
+            self.write_u8(TAG_END).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::slot::*;
+
+    #[test]
+    fn empty_slot() {
+        let mut buf = ByteBuf::new();
+        buf.write_slot(None);
+        assert_eq!(&[0xFF, 0xFF], buf.as_slice());
+        assert_eq!(None, buf.read_slot().unwrap());
+    }
+
+    #[test]
+    fn slot_round_trip() {
+        let stone = Slot {
+            item_id: 1,
+            count: 64,
+            damage: 0,
+            nbt: None,
+        };
+        let mut buf = ByteBuf::new();
+        buf.write_slot(Some(&stone));
+        assert_eq!(&[0, 1, 64, 0, 0, 0], buf.as_slice());
+        assert_eq!(Some(stone), buf.read_slot().unwrap());
+
+        // Sharpness V diamond sword, one use in
+        let enchantment = Tag::Compound(vec![
+            ("id".to_string(), Tag::Short(16)),
+            ("lvl".to_string(), Tag::Short(5)),
+        ]);
+        let sword = Slot {
+            item_id: 276,
+            count: 1,
+            damage: 1,
+            nbt: Some(Tag::Compound(vec![(
+                "ench".to_string(),
+                Tag::List(10, vec![enchantment]),
+            )])),
+        };
+        let mut buf = ByteBuf::new();
+        buf.write_slot(Some(&sword));
+        buf.write_slot(None);
+        assert_eq!(Some(sword), buf.read_slot().unwrap());
+        assert_eq!(None, buf.read_slot().unwrap());
+        assert!(buf.end());
+    }
+
+    #[test]
+    fn nbt_too_long_to_write_is_dropped() {
+        let book = Slot {
+            item_id: 387,
+            count: 1,
+            damage: 0,
+            nbt: Some(Tag::Compound(vec![(
+                "title".to_string(),
+                Tag::String("x".repeat(u16::MAX as usize + 1)),
+            )])),
+        };
+        let mut buf = ByteBuf::new();
+        buf.write_slot(Some(&book));
+        assert_eq!(&[1, 131, 1, 0, 0, TAG_END], buf.as_slice());
+    }
+
+    #[test]
+    fn truncated_slot() {
+        let mut buf = ByteBuf::from(&[0, 1, 64][..]);
+        assert_eq!(
+            DeserializeError::BufferTooSmall,
+            buf.read_slot().unwrap_err()
+        );
+    }
+}
@@ -6,6 +6,9 @@ use crate::serialize::var::{DeserializeError, VarIntReader, VarIntWriter};
 // also we can reuse a VarInt prefixed byte array thing here
 pub trait VarIntString: VarIntWriter + VarIntReader {
     fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     fn extend_from_slice(&mut self, other: &[u8]);
 }
 
@@ -29,15 +32,13 @@ where
 
 impl ReadString for ByteBuf {
     fn read_string(&mut self) -> Result<String, DeserializeError> {
-        match self.read_var_int() {
-            Ok(len) => {
-                if len <= 0 {
-                    return Err(DeserializeError::InvalidLength);
-                }
-                let byte_vec = self.read_bytes(len as usize).unwrap();
-                Ok(String::from_utf8(byte_vec).unwrap())
-            }
-            Err(err) => Err(err),
+        let len = self.read_var_int()?;
+        if len < 0 {
+            return Err(DeserializeError::InvalidLength);
         }
+        let byte_vec = self
+            .read_bytes(len as usize)
+            .ok_or(DeserializeError::BufferTooSmall)?;
+        String::from_utf8(byte_vec).map_err(|_| DeserializeError::InvalidString)
     }
 }
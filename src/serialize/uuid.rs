@@ -0,0 +1,39 @@
+// Newer versions send UUIDs as two longs instead of strings
+
+// Hyphenated, e.g. "069a79f4-44e9-4726-a5be-fca90e38aaf5"
+pub fn to_string(uuid: u128) -> String {
+    let hex = format!("{:032x}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+// Accepts UUIDs with or without hyphens
+pub fn parse(uuid: &str) -> Option<u128> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::uuid::*;
+
+    #[test]
+    fn uuids_round_trip() {
+        let uuid = 0x069a79f4_44e9_4726_a5be_fca90e38aaf5;
+        assert_eq!("069a79f4-44e9-4726-a5be-fca90e38aaf5", to_string(uuid));
+        assert_eq!(Some(uuid), parse(&to_string(uuid)));
+        assert_eq!(Some(uuid), parse("069a79f444e94726a5befca90e38aaf5"));
+        assert_eq!(Some(1), parse(&to_string(1)));
+        assert_eq!(None, parse("069a79f4"));
+        assert_eq!(None, parse("zz9a79f4-44e9-4726-a5be-fca90e38aaf5"));
+    }
+}
@@ -2,7 +2,19 @@
 pub enum DeserializeError {
     VarIntTooBig, // Longer than 5 bytes
     BufferTooSmall,
-    InvalidLength, // Length <= 0
+    InvalidLength,  // Length < 0 or not what the format requires
+    UnknownTag(u8), // NBT tag type that doesn't exist
+    InvalidString,  // Not valid (modified) UTF-8
+    TooDeep,        // NBT nested deeper than vanilla allows
+    Decompression,
+    InvalidValue, // Not one of an enum's values, or out of range
+}
+
+// byteorder reads from a ByteBuf only fail when it runs out
+impl From<std::io::Error> for DeserializeError {
+    fn from(_: std::io::Error) -> Self {
+        DeserializeError::BufferTooSmall
+    }
 }
 
 // Special trait for writing VarInt/VarLong
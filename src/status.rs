@@ -0,0 +1,103 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+// The JSON a server answers a status request with
+#[derive(Debug, Deserialize)]
+pub struct ServerStatus {
+    pub version: Version,
+    pub players: Players,
+    // Either a plain string or a chat component
+    #[serde(default)]
+    pub description: serde_json::Value,
+    // PNG data URI
+    pub favicon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Version {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Players {
+    pub max: i32,
+    pub online: i32,
+    #[serde(default)]
+    pub sample: Vec<PlayerSample>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
+impl ServerStatus {
+    pub fn from_json(json: &str) -> Result<ServerStatus, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[derive(Debug)]
+pub struct StatusPing {
+    pub status: ServerStatus,
+    pub latency: Duration, // Time between sending Ping and reading Pong
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::status::*;
+
+    #[test]
+    fn parse_status() {
+        let json = r#"{
+            "version": {"name": "1.12.2", "protocol": 340},
+            "players": {
+                "max": 100,
+                "online": 2,
+                "sample": [
+                    {"name": "thinkofdeath", "id": "4566e69f-c907-48ee-8d71-d7ba5aa00d20"}
+                ]
+            },
+            "description": {"text": "Hello world"},
+            "favicon": "data:image/png;base64,iVBORw0KGgo="
+        }"#;
+
+        let status = ServerStatus::from_json(json).unwrap();
+        assert_eq!("1.12.2", status.version.name);
+        assert_eq!(340, status.version.protocol);
+        assert_eq!(100, status.players.max);
+        assert_eq!(2, status.players.online);
+        assert_eq!(1, status.players.sample.len());
+        assert_eq!("thinkofdeath", status.players.sample[0].name);
+        assert_eq!(
+            "4566e69f-c907-48ee-8d71-d7ba5aa00d20",
+            status.players.sample[0].id
+        );
+        assert_eq!("Hello world", status.description["text"]);
+        assert_eq!(
+            Some("data:image/png;base64,iVBORw0KGgo=".to_string()),
+            status.favicon
+        );
+    }
+
+    #[test]
+    fn parse_minimal_status() {
+        let json = r#"{
+            "version": {"name": "Spigot 1.12.2", "protocol": 340},
+            "players": {"max": 20, "online": 0},
+            "description": "A Minecraft Server"
+        }"#;
+
+        let status = ServerStatus::from_json(json).unwrap();
+        assert!(status.players.sample.is_empty());
+        assert_eq!("A Minecraft Server", status.description);
+        assert_eq!(None, status.favicon);
+    }
+
+    #[test]
+    fn parse_invalid_status() {
+        assert!(ServerStatus::from_json(r#"{"version": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,453 @@
+use crate::auth::{Profile, SessionServer};
+use crate::error::{Error, Result};
+use crate::mc::{BufferSize, Connection};
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::packet::clientbound::{Disconnect, LoginSuccess};
+use crate::serialize::packet::serverbound::{Handshake, LoginStart};
+use crate::serialize::packet::{deserialize_new, Packet, PacketID};
+use crate::serialize::protocol::{ProtocolVersion, State};
+use crate::serialize::registry::{self, Direction};
+use rand::Rng;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// A connection that stays up this long resets the backoff
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+// What happened to the upstream connection
+#[derive(Clone, Debug)]
+pub enum Event {
+    Connected(LoginSuccess),
+    Disconnected(String),        // Why, kicks are a JSON chat component
+    Reconnecting(u32, Duration), // Attempt number and the wait before it
+}
+
+// Exponential backoff between reconnects. Each delay is cut by a random
+// fraction up to `jitter` so many idlers don't reconnect in lockstep.
+#[derive(Copy, Clone, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub jitter: f64,               // 0 to 1
+    pub max_attempts: Option<u32>, // None to retry forever
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5 * 60),
+            jitter: 0.5,
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    // Wait before reconnect attempt `attempt`, which starts at 1
+    pub fn delay(&self, attempt: u32, rng: &mut impl Rng) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        let delay = self.initial.saturating_mul(1 << doublings).min(self.max);
+        delay.mul_f64(1.0 - self.jitter * rng.gen::<f64>())
+    }
+}
+
+// Keeps an upstream connection logged in. Kicks, the server closing the
+// connection, read errors and silence all make it log in again after a
+// backoff, with every step reported to subscribers.
+pub struct Supervisor {
+    addr: String,
+    ver: ProtocolVersion,
+    handshake: Handshake,
+    login_start: LoginStart,
+    auth: Option<(SessionServer, Profile)>,
+    backoff: Backoff,
+    timeout: Duration,      // Silence before the server is presumed gone
+    read_timeout: Duration, // How long a read waits for packets
+    conn: Option<Connection>,
+    connected_at: Instant,
+    last_packet: Instant,
+    failures: u32, // Since the last stable connection
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl Supervisor {
+    pub fn new(
+        addr: String,
+        ver: ProtocolVersion,
+        handshake: Handshake,
+        login_start: LoginStart,
+    ) -> Supervisor {
+        Supervisor {
+            addr,
+            ver,
+            handshake,
+            login_start,
+            auth: None,
+            backoff: Backoff::default(),
+            timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_millis(20),
+            conn: None,
+            connected_at: Instant::now(),
+            last_packet: Instant::now(),
+            failures: 0,
+            subscribers: Vec::new(),
+        }
+    }
+
+    // Join through `session` as `profile` when the server asks for encryption
+    pub fn set_authentication(&mut self, session: SessionServer, profile: Profile) {
+        self.auth = Some((session, profile));
+    }
+
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    // Vanilla servers send a keep alive every 15 seconds
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = timeout;
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    // What a packet from `read_packets` is, they're all play packets
+    pub fn packet_id(&self, id: i32) -> PacketID {
+        registry::lookup(&self.ver, &State::Play, &Direction::Clientbound, id)
+    }
+
+    // The current connection, None while reconnecting
+    pub fn connection(&mut self) -> Option<&mut Connection> {
+        self.conn.as_mut()
+    }
+
+    // Logs in, waiting and retrying until it works. Fails with the last
+    // error once `Backoff::max_attempts` attempts in a row have failed.
+    pub fn connect(&mut self) -> Result<LoginSuccess> {
+        loop {
+            if self.failures > 0 {
+                let delay = self.backoff.delay(self.failures, &mut rand::thread_rng());
+                self.emit(Event::Reconnecting(self.failures, delay));
+                thread::sleep(delay);
+            }
+
+            match self.login() {
+                Ok((conn, success)) => {
+                    self.conn = Some(conn);
+                    self.connected_at = Instant::now();
+                    self.last_packet = Instant::now();
+                    self.emit(Event::Connected(success.clone()));
+                    return Ok(success);
+                }
+                Err(e) => {
+                    self.failures += 1;
+                    self.emit(Event::Disconnected(e.to_string()));
+                    if matches!(self.backoff.max_attempts, Some(max) if self.failures >= max) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn login(&self) -> Result<(Connection, LoginSuccess)> {
+        let mut conn = Connection::new(self.addr.clone(), self.ver, BufferSize::Medium)?;
+        if let Some((session, profile)) = self.auth.clone() {
+            conn.set_authentication(session, profile);
+        }
+        // A server that's restarting can accept us and then say nothing
+        conn.set_read_timeout(Some(self.read_timeout))?;
+        let success = conn.login_within(&self.handshake, &self.login_start, self.timeout)?;
+        Ok((conn, success))
+    }
+
+    // Reads play packets, logging in again first if the connection was lost.
+    // Packets from after a kick aren't returned. Only fails when `connect`
+    // gives up.
+    pub fn read_packets(&mut self) -> Result<Vec<(i32, ByteBuf)>> {
+        let conn = match self.conn.as_mut() {
+            Some(conn) => conn,
+            None => {
+                self.connect()?;
+                return Ok(Vec::new());
+            }
+        };
+
+        let mut pkts = match conn.read_packets() {
+            Ok(pkts) => pkts,
+            Err(e) => {
+                self.lost(e.to_string());
+                return Ok(Vec::new());
+            }
+        };
+
+        let kick = pkts
+            .iter()
+            .position(|(id, _)| conn.packet_id(*id) == PacketID::Disconnect);
+        if let Some(i) = kick {
+            let reason = match deserialize_new::<Disconnect>(&mut pkts[i].1, &self.ver) {
+                Ok(disconnect) => disconnect.reason,
+                Err(e) => format!("Kicked, {}", Error::from(e)),
+            };
+            pkts.truncate(i);
+            self.lost(reason);
+        } else if conn.is_closed() {
+            self.lost(Error::Closed.to_string());
+        } else if !pkts.is_empty() {
+            self.last_packet = Instant::now();
+        } else if self.last_packet.elapsed() > self.timeout {
+            self.lost(format!("Nothing received for {:?}", self.timeout));
+        }
+        Ok(pkts)
+    }
+
+    pub fn send_packet(&mut self, packet: &impl Packet) -> Result<usize> {
+        match self.conn.as_mut() {
+            Some(conn) => conn.send_packet(packet),
+            None => Err(Error::Closed),
+        }
+    }
+
+    fn lost(&mut self, reason: String) {
+        self.conn = None;
+        if self.connected_at.elapsed() >= STABLE_AFTER {
+            self.failures = 0;
+        }
+        self.failures += 1;
+        self.emit(Event::Disconnected(reason));
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::packet::clientbound::KeepAlive;
+    use crate::serialize::packet::serverbound::LoginState;
+    use crate::supervisor::*;
+    use rand::rngs::mock::StepRng;
+    use std::net::{TcpListener, TcpStream};
+
+    const VER: ProtocolVersion = ProtocolVersion::V_1_12_2;
+
+    fn supervisor(port: u16) -> Supervisor {
+        let handshake = Handshake {
+            protocol_version: 340,
+            address: "127.0.0.1".to_string(),
+            port,
+            next_state: LoginState::Login,
+        };
+        let login_start = LoginStart {
+            username: "idler".to_string(),
+            uuid: None,
+        };
+        let mut supervisor =
+            Supervisor::new(format!("127.0.0.1:{}", port), VER, handshake, login_start);
+        supervisor.set_backoff(Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(40),
+            jitter: 0.0,
+            max_attempts: None,
+        });
+        supervisor
+    }
+
+    // Logs in a client as the server would, leaving it in play
+    fn accept(listener: &TcpListener) -> Connection {
+        let (stream, _) = listener.accept().unwrap();
+        let mut conn = Connection::from_stream(stream, VER, BufferSize::Medium);
+        let mut pkts = Vec::new();
+        while pkts.len() < 2 {
+            pkts.append(&mut conn.read_packets().unwrap());
+        }
+        conn.send_packet(&LoginSuccess {
+            uuid: "c0ffee00-0000-4000-8000-000000000000".to_string(),
+            username: "idler".to_string(),
+            properties: Vec::new(),
+        })
+        .unwrap();
+        conn.set_state(State::Play);
+        conn
+    }
+
+    // Reads until a keep alive arrives, returning its id
+    fn read_keep_alive(supervisor: &mut Supervisor) -> i64 {
+        loop {
+            for (id, mut buf) in supervisor.read_packets().unwrap() {
+                if supervisor.packet_id(id) == PacketID::KeepAliveCB {
+                    return deserialize_new::<KeepAlive>(&mut buf, &VER).unwrap().id;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            jitter: 0.5,
+            max_attempts: None,
+        };
+
+        // StepRng(0, 0) always gives 0.0, so no jitter
+        let mut none = StepRng::new(0, 0);
+        let delays: Vec<u64> = (1..=8)
+            .map(|attempt| backoff.delay(attempt, &mut none).as_secs())
+            .collect();
+        assert_eq!(vec![1, 2, 4, 8, 16, 32, 60, 60], delays);
+        assert_eq!(Duration::from_secs(60), backoff.delay(1000, &mut none));
+
+        // At most half is taken off
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let delay = backoff.delay(3, &mut rng);
+            assert!(delay > Duration::from_secs(2) && delay <= Duration::from_secs(4));
+        }
+    }
+
+    #[test]
+    fn reconnects_after_kick() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut supervisor = supervisor(listener.local_addr().unwrap().port());
+        let events = supervisor.subscribe();
+
+        let server = thread::spawn(move || {
+            let mut first = accept(&listener);
+            first.send_packet(&KeepAlive { id: 1 }).unwrap();
+            first
+                .send_packet(&Disconnect {
+                    reason: "{\"text\":\"Server restarting\"}".to_string(),
+                })
+                .unwrap();
+            // Sent after the kick, so never seen
+            first.send_packet(&KeepAlive { id: 2 }).unwrap();
+
+            let mut second = accept(&listener);
+            second.send_packet(&KeepAlive { id: 3 }).unwrap();
+            (first, second)
+        });
+
+        supervisor.connect().unwrap();
+        assert_eq!(1, read_keep_alive(&mut supervisor));
+        assert_eq!(3, read_keep_alive(&mut supervisor));
+        let _ = server.join().unwrap();
+
+        let events: Vec<Event> = events.try_iter().collect();
+        assert!(matches!(events[0], Event::Connected(_)));
+        assert!(matches!(&events[1], Event::Disconnected(reason) if reason.contains("restarting")));
+        assert!(matches!(events[2], Event::Reconnecting(1, _)));
+        assert!(matches!(events[3], Event::Connected(_)));
+        assert_eq!(4, events.len());
+    }
+
+    #[test]
+    fn reconnects_after_silence_and_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut supervisor = supervisor(listener.local_addr().unwrap().port());
+        supervisor.set_timeout(Duration::from_millis(100));
+        let events = supervisor.subscribe();
+
+        let server = thread::spawn(move || {
+            // Says nothing until the client gives up on it
+            let mut silent = accept(&listener);
+            while !silent.is_closed() {
+                silent.read_packets().unwrap();
+            }
+
+            // Closes the connection straight away
+            drop(accept(&listener));
+
+            let mut last = accept(&listener);
+            last.send_packet(&KeepAlive { id: 7 }).unwrap();
+            last
+        });
+
+        supervisor.connect().unwrap();
+        assert_eq!(7, read_keep_alive(&mut supervisor));
+        let _ = server.join().unwrap();
+
+        let reasons: Vec<String> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Disconnected(reason) => Some(reason),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(2, reasons.len());
+        assert!(reasons[0].starts_with("Nothing received"));
+        assert_eq!(Error::Closed.to_string(), reasons[1]);
+    }
+
+    #[test]
+    fn stalled_login_is_a_failed_attempt() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut supervisor = supervisor(listener.local_addr().unwrap().port());
+        supervisor.set_timeout(Duration::from_millis(100));
+        supervisor.set_backoff(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            jitter: 0.0,
+            max_attempts: Some(2),
+        });
+        let events = supervisor.subscribe();
+
+        // Accepts the connection but never answers the login
+        let server = thread::spawn(move || {
+            let mut stalled = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                stalled.push(stream);
+            }
+            stalled
+        });
+
+        assert!(supervisor.connect().unwrap_err().is_timeout());
+        let _ = server.join().unwrap();
+        let events: Vec<Event> = events.try_iter().collect();
+        assert!(matches!(&events[0], Event::Disconnected(reason) if reason.contains("in time")));
+        assert!(matches!(events[1], Event::Reconnecting(1, _)));
+        assert_eq!(3, events.len());
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        // Nothing listens on a port that was just freed
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+
+        let mut supervisor = supervisor(port);
+        supervisor.set_backoff(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            jitter: 0.0,
+            max_attempts: Some(3),
+        });
+        let events = supervisor.subscribe();
+
+        assert!(supervisor.connect().is_err());
+        let events: Vec<Event> = events.try_iter().collect();
+        let reconnects = events
+            .iter()
+            .filter(|event| matches!(event, Event::Reconnecting(..)))
+            .count();
+        assert_eq!(2, reconnects);
+        assert_eq!(5, events.len());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::serialize::buffer::ByteBuf;
+use crate::serialize::packet::clientbound::*;
+use crate::serialize::packet::{deserialize_new, PacketID};
+use crate::serialize::protocol::ProtocolVersion;
+use crate::serialize::var::DeserializeError;
+
+// Everything an add sets
+const ADD_ALL: u8 =
+    ADD_PLAYER | UPDATE_GAMEMODE | UPDATE_LISTED | UPDATE_LATENCY | UPDATE_DISPLAY_NAME;
+
+// Who came or went in an update
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Joined(PlayerListEntry),
+    Left(PlayerListEntry),
+}
+
+// The players on the server as the tab list shows them, in the order they
+// were added
+#[derive(Debug)]
+pub struct TabList {
+    ver: ProtocolVersion,
+    players: Vec<PlayerListEntry>,
+}
+
+impl TabList {
+    pub fn new(ver: ProtocolVersion) -> TabList {
+        TabList {
+            ver,
+            players: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, uuid: u128) -> Option<&PlayerListEntry> {
+        self.players.iter().find(|player| player.uuid == uuid)
+    }
+
+    // Names are unique regardless of case
+    pub fn find(&self, name: &str) -> Option<&PlayerListEntry> {
+        self.players
+            .iter()
+            .find(|player| player.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PlayerListEntry> {
+        self.players.iter()
+    }
+
+    // Who joined or left, in the order the packet lists them
+    pub fn update(&mut self, kind: PacketID, data: &[u8]) -> Result<Vec<Change>, DeserializeError> {
+        let mut fields = ByteBuf::from(data);
+        let mut changes = Vec::new();
+        match kind {
+            PacketID::PlayerListItem => {
+                let item = deserialize_new::<PlayerListItem>(&mut fields, &self.ver)?;
+                for update in item.players {
+                    if item.actions & REMOVE_PLAYER != 0 {
+                        changes.extend(self.remove(update.uuid));
+                    } else if item.actions & ADD_PLAYER != 0 {
+                        // Added again, e.g. after changing skin, isn't a join
+                        match self.players.iter_mut().find(|p| p.uuid == update.uuid) {
+                            Some(player) => *player = update,
+                            None => {
+                                self.players.push(update.clone());
+                                changes.push(Change::Joined(update));
+                            }
+                        }
+                    } else if let Some(player) =
+                        self.players.iter_mut().find(|p| p.uuid == update.uuid)
+                    {
+                        if item.actions & UPDATE_GAMEMODE != 0 {
+                            player.gamemode = update.gamemode;
+                        }
+                        if item.actions & UPDATE_LISTED != 0 {
+                            player.listed = update.listed;
+                        }
+                        if item.actions & UPDATE_LATENCY != 0 {
+                            player.ping = update.ping;
+                        }
+                        if item.actions & UPDATE_DISPLAY_NAME != 0 {
+                            player.display_name = update.display_name;
+                        }
+                    }
+                }
+            }
+            PacketID::PlayerInfoRemove => {
+                let remove = deserialize_new::<PlayerInfoRemove>(&mut fields, &self.ver)?;
+                for uuid in remove.uuids {
+                    changes.extend(self.remove(uuid));
+                }
+            }
+            _ => {}
+        }
+        Ok(changes)
+    }
+
+    fn remove(&mut self, uuid: u128) -> Option<Change> {
+        let index = self.players.iter().position(|player| player.uuid == uuid)?;
+        Some(Change::Left(self.players.remove(index)))
+    }
+
+    // Adds everyone at once, for a client that just logged in
+    pub fn replay(&self) -> Option<PlayerListItem> {
+        if self.players.is_empty() {
+            return None;
+        }
+        Some(PlayerListItem {
+            actions: ADD_ALL,
+            players: self.players.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::packet::Packet;
+    use crate::tablist::*;
+
+    const STEVE: u128 = 0xc0ffee00_0000_4000_8000_000000000001;
+    const ALEX: u128 = 0xc0ffee00_0000_4000_8000_000000000002;
+
+    fn steve() -> PlayerListEntry {
+        PlayerListEntry {
+            uuid: STEVE,
+            name: "Steve".to_string(),
+            properties: vec![Property {
+                name: "textures".to_string(),
+                value: "eyJ0ZXh0dXJlcyI6e319".to_string(),
+                signature: Some("c2lnbmVk".to_string()),
+            }],
+            gamemode: 0,
+            listed: true,
+            ping: 35,
+            display_name: None,
+        }
+    }
+
+    fn alex() -> PlayerListEntry {
+        PlayerListEntry {
+            uuid: ALEX,
+            name: "Alex".to_string(),
+            gamemode: 1,
+            listed: true,
+            ping: 120,
+            display_name: Some(r#"{"text":"[Admin] Alex"}"#.to_string()),
+            ..PlayerListEntry::default()
+        }
+    }
+
+    #[test]
+    fn players_come_and_go_in_every_version() {
+        for &ver in ProtocolVersion::ALL {
+            let mut list = TabList::new(ver);
+            let add = PlayerListItem {
+                actions: ADD_ALL,
+                players: vec![steve(), alex()],
+            };
+            let changes = list
+                .update(PacketID::PlayerListItem, &add.body(&list.ver))
+                .unwrap();
+            assert_eq!(
+                vec![Change::Joined(steve()), Change::Joined(alex())],
+                changes,
+                "{:?}",
+                ver
+            );
+            assert_eq!(2, list.len());
+            assert_eq!(Some(&steve()), list.find("steve"));
+            assert_eq!(
+                "eyJ0ZXh0dXJlcyI6e319",
+                list.get(STEVE).unwrap().textures().unwrap().value
+            );
+            assert_eq!(None, list.get(ALEX).unwrap().textures());
+
+            let ping = PlayerListItem {
+                actions: UPDATE_LATENCY,
+                players: vec![PlayerListEntry {
+                    uuid: STEVE,
+                    ping: 250,
+                    ..PlayerListEntry::default()
+                }],
+            };
+            assert!(list
+                .update(PacketID::PlayerListItem, &ping.body(&list.ver))
+                .unwrap()
+                .is_empty());
+            let renamed = PlayerListItem {
+                actions: UPDATE_DISPLAY_NAME,
+                players: vec![PlayerListEntry {
+                    uuid: ALEX,
+                    ..PlayerListEntry::default()
+                }],
+            };
+            list.update(PacketID::PlayerListItem, &renamed.body(&list.ver))
+                .unwrap();
+            assert_eq!(250, list.get(STEVE).unwrap().ping);
+            assert_eq!("Steve", list.get(STEVE).unwrap().name);
+            assert_eq!(None, list.get(ALEX).unwrap().display_name);
+            assert_eq!(1, list.get(ALEX).unwrap().gamemode);
+
+            // 1.20.1 removes players with a packet of its own
+            let changes = if ver >= ProtocolVersion::V_1_20_1 {
+                let remove = PlayerInfoRemove { uuids: vec![STEVE] };
+                list.update(PacketID::PlayerInfoRemove, &remove.body(&list.ver))
+                    .unwrap()
+            } else {
+                let remove = PlayerListItem {
+                    actions: REMOVE_PLAYER,
+                    players: vec![PlayerListEntry {
+                        uuid: STEVE,
+                        ..PlayerListEntry::default()
+                    }],
+                };
+                list.update(PacketID::PlayerListItem, &remove.body(&list.ver))
+                    .unwrap()
+            };
+            assert_eq!(1, changes.len());
+            assert!(matches!(&changes[0], Change::Left(p) if p.uuid == STEVE && p.ping == 250));
+            assert_eq!(None, list.find("Steve"));
+            assert_eq!(1, list.len());
+        }
+    }
+
+    #[test]
+    fn adding_someone_again_replaces_them() {
+        let mut list = TabList::new(ProtocolVersion::V_1_12_2);
+        let add = PlayerListItem {
+            actions: ADD_ALL,
+            players: vec![steve(), alex()],
+        };
+        list.update(PacketID::PlayerListItem, &add.body(&list.ver))
+            .unwrap();
+
+        let reskinned = PlayerListEntry {
+            properties: Vec::new(),
+            ..steve()
+        };
+        let add = PlayerListItem {
+            actions: ADD_ALL,
+            players: vec![reskinned.clone()],
+        };
+        assert!(list
+            .update(PacketID::PlayerListItem, &add.body(&list.ver))
+            .unwrap()
+            .is_empty());
+        assert_eq!(vec![&reskinned, &alex()], list.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn replay_adds_everyone() {
+        let ver = ProtocolVersion::V_1_12_2;
+        let mut list = TabList::new(ver);
+        assert_eq!(None, list.replay());
+        for player in [steve(), alex()] {
+            let add = PlayerListItem {
+                actions: ADD_ALL,
+                players: vec![player],
+            };
+            list.update(PacketID::PlayerListItem, &add.body(&list.ver))
+                .unwrap();
+        }
+
+        // A client reading the replay ends up with the same list
+        let mut copy = TabList::new(ver);
+        let changes = copy
+            .update(
+                PacketID::PlayerListItem,
+                &list.replay().unwrap().body(&copy.ver),
+            )
+            .unwrap();
+        assert_eq!(2, changes.len());
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            copy.iter().collect::<Vec<_>>()
+        );
+    }
+}